@@ -3,6 +3,7 @@ use crate::help;
 use crate::signing::ReportSignature;
 use crate::submission::submit_report;
 use futures::stream::{FuturesUnordered, StreamExt};
+use stackmuncher_lib::blob_cache::BlobCache;
 use stackmuncher_lib::contributor::Contributor;
 use stackmuncher_lib::report::TechOverview;
 use stackmuncher_lib::{code_rules::CodeRules, config::Config, git, report::Report, utils::hash_str_sha1};
@@ -14,6 +15,29 @@ pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
 
     // load code rules
     let mut code_rules = CodeRules::new();
+    code_rules.add_muncher_path_overrides(&config.lib_config.muncher_path_overrides);
+    code_rules.strip_control_chars = config.lib_config.strip_control_chars.clone();
+    code_rules.max_processing_duration = config.lib_config.max_processing_duration;
+    if let Some(muncher_name) = config.lib_config.default_text_muncher.as_ref() {
+        code_rules.set_default_text_muncher(muncher_name);
+    }
+    code_rules.track_ref_first_seen = config.lib_config.track_ref_first_seen;
+    code_rules.max_file_size_bytes = config.lib_config.max_file_size_bytes;
+    code_rules.max_avg_line_length = config.lib_config.max_avg_line_length;
+    code_rules.max_concurrent_files = config.lib_config.max_concurrent_files;
+    code_rules.detect_munchers_by_content = config.lib_config.detect_munchers_by_content;
+    code_rules.ignore_patterns = config.lib_config.ignore_patterns.clone();
+    code_rules.test_path_patterns = config.lib_config.test_path_patterns.clone();
+    code_rules.set_generated_markers(&config.lib_config.generated_markers);
+    code_rules.set_bot_patterns(&config.lib_config.bot_patterns);
+    code_rules.mailmap_path = config.lib_config.mailmap_path.clone();
+    code_rules.include_working_tree = config.lib_config.include_working_tree;
+    code_rules.munch_fenced_code_blocks = config.lib_config.munch_fenced_code_blocks;
+    code_rules.detect_duplicate_lines = config.lib_config.detect_duplicate_lines;
+    code_rules.blob_cache = config.lib_config.blob_cache_dir.as_ref().map(|dir| BlobCache::new(dir));
+    if let Some(dir) = config.lib_config.muncher_cache_dir.as_ref() {
+        code_rules.load_muncher_cache(dir);
+    }
 
     // Reports are grouped per project with a canonical project name as the last subfolder
     let report_dir = Path::new(
@@ -28,7 +52,18 @@ pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
     // load a previously generated report if it exists
     let project_report_filename =
         report_dir.join([Config::PROJECT_REPORT_FILE_NAME, Config::REPORT_FILE_EXTENSION].concat());
-    let cached_project_report = Report::from_disk(&project_report_filename);
+    // a corrupt report is as good as none, but the user should know why the repo is processed in full
+    let cached_project_report = match Report::load_from_disk(&project_report_filename) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!(
+                "Ignoring the cached report at {} due to {}. The repo will be processed in full.",
+                project_report_filename.to_string_lossy(),
+                e
+            );
+            None
+        }
+    };
 
     // get and retain a copy of the full git lot to re-use in multiple places
     let git_log = git::get_log(&config.lib_config.project_dir, None, &code_rules.ignore_paths).await?;
@@ -48,17 +83,25 @@ pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
             cached_project_report.expect("Cannot unwrap cached report. It's a bug.")
         }
         Some(v) => {
-            let _ = v.save_as_local_file(&project_report_filename, true);
+            v.save_as_local_file(&project_report_filename, true).map_err(|_| ())?;
             info!("Project stack analyzed in {}ms", instant.elapsed().as_millis());
             v
         }
     };
 
+    // all munchers needed for this repo are loaded by now
+    if let Some(dir) = config.lib_config.muncher_cache_dir.as_ref() {
+        if let Err(e) = code_rules.save_muncher_cache(dir) {
+            warn!("{}", e);
+        }
+    }
+
     info!("Contributor reports requested for: {:?}", config.lib_config.git_identities);
 
     // check if there are multiple contributors and generate individual reports
     if let Some(contributors) = &project_report.contributors {
-        let last_commit_author = project_report.last_commit_author.as_ref().unwrap().clone();
+        // it may be missing if all contributors were bots
+        let last_commit_author = project_report.last_commit_author.clone().unwrap_or_default();
 
         // prepare a combined list of commit IDs from all known identities
         let list_of_commits = git::get_contributor_commits_from_log(&git_log, &config.lib_config.git_identities);
@@ -115,7 +158,9 @@ pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
                 )
                 .await?;
 
-            contributor_report.save_as_local_file(&contributor_report_filename, false);
+            contributor_report
+                .save_as_local_file(&contributor_report_filename, false)
+                .map_err(|_| ())?;
 
             info!(
                 "Contributor stack for {} analyzed in {}ms",
@@ -142,7 +187,8 @@ pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
             }
 
             // combine all added per-file-tech into appropriate tech records
-            combined_report.recompute_tech_section();
+            // the summaries are regenerated when the report is merged into a profile
+            combined_report.recompute_tech_section(false);
 
             // add any personal details supplied via CLI or taken from the environment
             combined_report.primary_email = config.primary_email.clone();
@@ -160,7 +206,9 @@ pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
             let first_run = !combined_report_file_name.exists();
 
             // save the combine report for inspection by the user
-            combined_report.save_as_local_file(&combined_report_file_name, true);
+            combined_report
+                .save_as_local_file(&combined_report_file_name, true)
+                .map_err(|_| ())?;
 
             // produce a sanitized version of the combined report, save and submit it if needed
             if let Ok(combined_report) = combined_report.sanitize(ReportSignature::get_salt(&config.user_key_pair)) {
@@ -174,7 +222,9 @@ pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
                 );
 
                 // save the sanitized report
-                combined_report.save_as_local_file(sanitized_report_file_name, true);
+                combined_report
+                    .save_as_local_file(sanitized_report_file_name, true)
+                    .map_err(|_| ())?;
 
                 print_combined_stats(&combined_report);
 
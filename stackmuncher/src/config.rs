@@ -375,6 +375,25 @@ pub(crate) async fn new_lib_config_with_defaults(current_dir: PathBuf) -> (LibCo
         user_name: String::new(),
         repo_name: String::new(),
         git_identities,
+        muncher_path_overrides: Vec::new(),
+        strip_control_chars: None,
+        max_processing_duration: None,
+        default_text_muncher: None,
+        blob_cache_dir: None,
+        muncher_cache_dir: None,
+        track_ref_first_seen: false,
+        max_file_size_bytes: Some(LibConfig::DEFAULT_MAX_FILE_SIZE_BYTES),
+        max_avg_line_length: Some(LibConfig::DEFAULT_MAX_AVG_LINE_LENGTH),
+        max_concurrent_files: LibConfig::default_max_concurrent_files(),
+        detect_munchers_by_content: true,
+        ignore_patterns: Vec::new(),
+        test_path_patterns: LibConfig::default_test_path_patterns(),
+        generated_markers: LibConfig::default_generated_markers(),
+        bot_patterns: LibConfig::default_bot_patterns(),
+        mailmap_path: None,
+        include_working_tree: false,
+        munch_fenced_code_blocks: false,
+        detect_duplicate_lines: false,
     };
 
     (config, config_dir)
@@ -0,0 +1,137 @@
+//! Benchmarks for the hot paths of munching: line classification and report merging.
+//! Run with `cargo bench -p stackmuncher_lib`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use stackmuncher_lib::code_rules::CodeRules;
+use stackmuncher_lib::processors::{analyze_bytes, count_lines};
+use stackmuncher_lib::report::Report;
+use std::fs::File;
+
+/// The number of times the sample snippet is repeated to make a large synthetic file
+const SYNTHETIC_FILE_REPEATS: usize = 1000;
+
+/// The number of lines of the synthetic file used to compare regex lists and sets
+const MATCH_LINE_FIXTURE_LINES: usize = 10_000;
+
+/// A snippet with a mix of code, comments, blank and bracket-only lines
+const RUST_SNIPPET: &str = r#"use std::collections::HashMap;
+
+/// Adds up the values in the map.
+/// Returns 0 for an empty map.
+pub fn sum(map: &HashMap<String, u64>) -> u64 {
+    // the map may be large
+    let mut total = 0;
+    for value in map.values() {
+        total += value; // no overflow checks
+    }
+    /* a block comment
+       spanning multiple lines */
+    total
+}
+"#;
+
+/// Returns a Rust file made of `RUST_SNIPPET` repeated `SYNTHETIC_FILE_REPEATS` times.
+fn synthetic_rust_file() -> Vec<String> {
+    RUST_SNIPPET
+        .repeat(SYNTHETIC_FILE_REPEATS)
+        .lines()
+        .map(|line| line.to_owned())
+        .collect()
+}
+
+/// Loads a report fixture from `test-files` in the root of the repo.
+fn report_fixture(file_name: &str) -> Report {
+    let file_name = [env!("CARGO_MANIFEST_DIR"), "/../test-files/", file_name].concat();
+    let file = File::open(&file_name).unwrap_or_else(|e| panic!("Cannot open {} due to {}", file_name, e));
+    serde_json::from_reader(file).unwrap_or_else(|e| panic!("Cannot parse {} due to {}", file_name, e))
+}
+
+fn bench_count_lines(c: &mut Criterion) {
+    let mut code_rules = CodeRules::new();
+    let muncher = code_rules
+        .get_muncher(&"bench.rs".to_owned())
+        .expect("Missing Rust muncher")
+        .clone();
+    let lines = synthetic_rust_file();
+
+    c.bench_function("count_lines synthetic rust file", |b| {
+        b.iter_batched(
+            || (analyze_bytes(&[], &muncher, "bench.rs"), lines.clone()),
+            |(mut tech, lines)| count_lines(black_box(&mut tech), lines, &muncher),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_merge(c: &mut Criterion) {
+    let report1 = report_fixture("report1.json");
+    let report2 = report_fixture("report2.json");
+
+    c.bench_function("merge report fixtures", |b| {
+        b.iter_batched(
+            || (report1.clone(), report2.clone()),
+            |(report1, report2)| Report::merge(Some(report1), report2),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+/// Compares testing every line against the regex of every line category one by one and with a single `RegexSet`
+/// per category, the same as `count_lines` does.
+fn bench_match_line(c: &mut Criterion) {
+    let mut code_rules = CodeRules::new();
+    let muncher = code_rules
+        .get_muncher(&"bench.rs".to_owned())
+        .expect("Missing Rust muncher")
+        .clone();
+    let lines = synthetic_rust_file()
+        .into_iter()
+        .take(MATCH_LINE_FIXTURE_LINES)
+        .collect::<Vec<String>>();
+
+    let regex_lists = [
+        &muncher.block_comments_start_regex,
+        &muncher.doc_comments_regex,
+        &muncher.line_comments_regex,
+        &muncher.inline_comments_regex,
+        &muncher.bracket_only_regex,
+        &muncher.blank_line_regex,
+    ];
+    let regex_sets = [
+        &muncher.block_comments_start_regex_set,
+        &muncher.doc_comments_regex_set,
+        &muncher.line_comments_regex_set,
+        &muncher.inline_comments_regex_set,
+        &muncher.bracket_only_regex_set,
+        &muncher.blank_line_regex_set,
+    ];
+
+    c.bench_function("match_line with regex lists", |b| {
+        b.iter(|| {
+            lines
+                .iter()
+                .filter_map(|line| {
+                    regex_lists
+                        .iter()
+                        .position(|list| list.iter().flatten().any(|regex| regex.is_match(black_box(line))))
+                })
+                .sum::<usize>()
+        })
+    });
+
+    c.bench_function("match_line with regex sets", |b| {
+        b.iter(|| {
+            lines
+                .iter()
+                .filter_map(|line| {
+                    regex_sets
+                        .iter()
+                        .position(|set| set.as_ref().is_some_and(|set| set.is_match(black_box(line))))
+                })
+                .sum::<usize>()
+        })
+    });
+}
+
+criterion_group!(benches, bench_count_lines, bench_merge, bench_match_line);
+criterion_main!(benches);
@@ -0,0 +1,139 @@
+use crate::git::GitBlob;
+use crate::report::Tech;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tracing::{debug, error, warn};
+
+/// A disk-backed cache of per-file tech records keyed on the blob SHA1, the muncher hash and the settings hash.
+/// The same blob munched with the same rules and settings always produces the same result, so unchanged files are
+/// never reprocessed, even if they moved to a different path or commit.
+/// The cached records are stored at `<dir>/<muncher_hash>/<settings_hash>/<blob_sha1>.json`.
+///
+/// Refs that look like local imports are removed before caching, so the settings hash includes the list of files
+/// in the tree, see `ProcessFileSettings::results_hash()`.
+#[derive(Debug, Clone)]
+pub struct BlobCache {
+    dir: PathBuf,
+}
+
+impl BlobCache {
+    /// Creates a cache at `dir`. The folder is created on the first insert.
+    pub fn new(dir: &Path) -> Self {
+        BlobCache { dir: dir.to_path_buf() }
+    }
+
+    /// Returns the path of the cache entry for the blob, muncher and settings combination.
+    fn entry_path(&self, blob_sha1: &str, muncher_hash: u64, settings_hash: u64) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}", muncher_hash))
+            .join(format!("{:016x}", settings_hash))
+            .join([blob_sha1, ".json"].concat())
+    }
+
+    /// Returns cached tech records for the blob with the file name and commit info of `file_name` and `blob`.
+    /// Returns None if there is no cache entry or it cannot be read.
+    pub fn get(&self, file_name: &String, blob: &GitBlob, muncher_hash: u64, settings_hash: u64) -> Option<Vec<Tech>> {
+        if blob.sha1.is_empty() {
+            return None;
+        }
+
+        let path = self.entry_path(&blob.sha1, muncher_hash, settings_hash);
+        if !path.exists() {
+            return None;
+        }
+
+        let file = match File::open(&path) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Cannot read blob cache entry {} due to {}", path.to_string_lossy(), e);
+                return None;
+            }
+        };
+
+        let techs = match serde_json::from_reader::<File, Vec<Tech>>(file) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Invalid blob cache entry {} due to {}", path.to_string_lossy(), e);
+                return None;
+            }
+        };
+
+        debug!("Blob cache hit for {} / {}", file_name, blob.sha1);
+
+        // the same blob may be at a different path or in a different commit
+        let techs = techs
+            .into_iter()
+            .map(|tech| tech.with_file_and_commit_info(file_name, blob))
+            .collect::<Vec<Tech>>();
+
+        Some(techs)
+    }
+
+    /// Saves the tech records munched from the blob. Errors are logged and ignored because the cache is optional.
+    pub fn insert(&self, blob_sha1: &str, muncher_hash: u64, settings_hash: u64, techs: &[Tech]) {
+        if blob_sha1.is_empty() {
+            return;
+        }
+
+        let path = self.entry_path(blob_sha1, muncher_hash, settings_hash);
+        if let Some(parent) = path.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                error!("Cannot create blob cache folder {} due to {}", parent.to_string_lossy(), e);
+                return;
+            }
+        }
+
+        let contents = match serde_json::to_vec(techs) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Cannot serialize blob cache entry for {} due to {}", blob_sha1, e);
+                return;
+            }
+        };
+
+        if let Err(e) = fs::write(&path, contents) {
+            error!("Cannot save blob cache entry {} due to {}", path.to_string_lossy(), e);
+        }
+    }
+}
+
+#[test]
+fn test_blob_cache_roundtrip() {
+    let dir = std::env::temp_dir().join(format!("stm_blob_cache_{}", uuid::Uuid::new_v4()));
+    let cache = BlobCache::new(&dir);
+
+    let tech = Tech {
+        total_lines: 10,
+        blank_lines: 1,
+        bracket_only_lines: 2,
+        muncher_name: "rust.rs".to_owned(),
+        file_name: Some("src/old.rs".to_owned()),
+        commit_sha1: Some("old_commit".to_owned()),
+        ..Tech::for_test("Rust", 7)
+    };
+    let blob = GitBlob {
+        sha1: "blob_sha1".to_owned(),
+        commit_sha1: "new_commit".to_owned(),
+        commit_date_epoch: 1,
+        commit_date_iso: "2021-01-01T00:00:00+00:00".to_owned(),
+        size: None,
+    };
+
+    // nothing is cached yet
+    assert!(cache.get(&"src/new.rs".to_owned(), &blob, 42, 7).is_none());
+
+    cache.insert(&blob.sha1, 42, 7, &[tech]);
+
+    // a different muncher or settings hash is a miss
+    assert!(cache.get(&"src/new.rs".to_owned(), &blob, 43, 7).is_none());
+    assert!(cache.get(&"src/new.rs".to_owned(), &blob, 42, 8).is_none());
+
+    // a hit gets the new file name and commit info
+    let techs = cache.get(&"src/new.rs".to_owned(), &blob, 42, 7).unwrap();
+    assert_eq!(techs.len(), 1);
+    assert_eq!(techs[0].code_lines, 7);
+    assert_eq!(techs[0].file_name.as_deref(), Some("src/new.rs"));
+    assert_eq!(techs[0].commit_sha1.as_deref(), Some("new_commit"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
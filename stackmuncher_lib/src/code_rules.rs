@@ -1,9 +1,15 @@
+use super::blob_cache::BlobCache;
+use super::config::Config;
 use super::file_type::FileType;
-use super::muncher::Muncher;
+use super::ignore::glob_to_regex;
+use super::muncher::{detect_muncher_by_content, muncher_for_path, Muncher};
+use super::muncher_cache::MuncherCacheError;
 use regex::Regex;
 use rust_embed::RustEmbed;
+use serde_json::{Map, Value};
 use std::collections::{BTreeMap, HashSet};
-use tracing::{debug, info, trace};
+use std::path::{Path, PathBuf};
+use tracing::{debug, error, info, trace};
 
 /// A container for embedded file_type rules
 #[derive(RustEmbed)]
@@ -33,9 +39,98 @@ pub struct CodeRules {
 
     /// Compiled regex for file names and paths that should be ignored regardless of any other rules
     pub ignore_paths: Vec<Regex>,
+
+    /// Path globs compiled into regex with the name of the muncher that should be used for matching files
+    /// regardless of their extension. They are checked before the file-type rules.
+    pub muncher_path_overrides: Vec<(Regex, String)>,
+
+    /// Control characters to remove from every line before munching, see `Config.strip_control_chars`
+    pub strip_control_chars: Option<Vec<char>>,
+
+    /// The max time to spend on munching files of a single repo, see `Config.max_processing_duration`
+    pub max_processing_duration: Option<std::time::Duration>,
+
+    /// The name of the muncher for files with no matching rules, see `Config.default_text_muncher`
+    pub default_text_muncher: Option<String>,
+
+    /// Munched files cached by blob SHA1, muncher hash and settings hash, see `Config.blob_cache_dir`
+    pub blob_cache: Option<BlobCache>,
+
+    /// Record the line number of the first occurrence of every ref per file, see `Config.track_ref_first_seen`
+    pub track_ref_first_seen: bool,
+
+    /// Files larger than this are not munched, see `Config.max_file_size_bytes`
+    pub max_file_size_bytes: Option<u64>,
+
+    /// Files that look minified are not munched, see `Config.max_avg_line_length`
+    pub max_avg_line_length: Option<usize>,
+
+    /// The max number of files munched at the same time, see `Config.max_concurrent_files`
+    pub max_concurrent_files: usize,
+
+    /// Check the contents of files with no muncher by name, see `Config.detect_munchers_by_content`
+    pub detect_munchers_by_content: bool,
+
+    /// Gitignore-style patterns for files that should not be processed, see `Config.ignore_patterns`
+    pub ignore_patterns: Vec<String>,
+
+    /// Gitignore-style patterns for test files, see `Config.test_path_patterns`
+    pub test_path_patterns: Vec<String>,
+
+    /// Compiled regex for headers of generated files, see `Config.generated_markers`
+    pub generated_markers: Vec<Regex>,
+
+    /// Compiled regex for names and emails of automated accounts, see `Config.bot_patterns`
+    pub bot_patterns: Vec<Regex>,
+
+    /// A custom `.mailmap` location, see `Config.mailmap_path`
+    pub mailmap_path: Option<PathBuf>,
+
+    /// Read files from disk and include untracked files, see `Config.include_working_tree`
+    pub include_working_tree: bool,
+
+    /// Munch fenced code blocks in Markdown files, see `Config.munch_fenced_code_blocks`
+    pub munch_fenced_code_blocks: bool,
+
+    /// Count code lines duplicated across files, see `Config.detect_duplicate_lines`
+    pub detect_duplicate_lines: bool,
+
+    /// All embedded munchers with no compiled regex for lookups by file name or contents, see `muncher_index`
+    muncher_index: Option<Vec<Muncher>>,
+
+    /// Munchers from `load_muncher_cache` that were not used yet. They are moved to `munchers` on the first use
+    /// if their rules did not change.
+    cached_munchers: BTreeMap<String, Muncher>,
+
+    /// The number of files sent for munching by `process_project_files` with these rules.
+    /// Files with their results taken from the cache or from an identical blob are not counted.
+    pub(crate) munched_files: usize,
 }
 
 impl CodeRules {
+    /// The version of the embedded rule set. Update it after any changes to file_type or muncher rules.
+    pub const RULES_TIMESTAMP: &'static str = "2021-11-02T00:23:00+00:00";
+
+    /// Languages that were renamed between rule set versions as old name / current name pairs.
+    /// Reports produced with older rules are canonicalized with them when merged, see `canonical_language()`.
+    pub const LANGUAGE_ALIASES: [(&'static str, &'static str); 6] = [
+        ("JS", "JavaScript"),
+        ("Cpp", "C++"),
+        ("CSharp", "C#"),
+        ("Golang", "Go"),
+        ("TS", "TypeScript"),
+        ("Bash", "Shell"),
+    ];
+
+    /// Returns the current name of a language from an older rule set, e.g. `JavaScript` for `JS`,
+    /// or None if the name is current.
+    pub fn canonical_language(language: &str) -> Option<&'static str> {
+        CodeRules::LANGUAGE_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == language)
+            .map(|(_, canonical)| *canonical)
+    }
+
     /// Create a new instance from a a list of file-type files at `file_type_dir`
     /// File-type rules are loaded upfront, munchers are loaded dynamically
     pub fn new() -> Self {
@@ -62,8 +157,32 @@ impl CodeRules {
             file_ext_regex: Regex::new(r#"[\.\\/][a-zA-Z0-1_]+$|^[a-zA-Z0-1_]+$"#).unwrap(),
             new_munchers: None,
             ignore_paths: crate::ignore_paths::compile_ignore_paths(),
+            muncher_path_overrides: Vec::new(),
+            strip_control_chars: None,
+            max_processing_duration: None,
+            default_text_muncher: None,
+            blob_cache: None,
+            track_ref_first_seen: false,
+            max_file_size_bytes: Some(Config::DEFAULT_MAX_FILE_SIZE_BYTES),
+            max_avg_line_length: Some(Config::DEFAULT_MAX_AVG_LINE_LENGTH),
+            max_concurrent_files: Config::default_max_concurrent_files(),
+            detect_munchers_by_content: true,
+            ignore_patterns: Vec::new(),
+            test_path_patterns: Config::default_test_path_patterns(),
+            generated_markers: Vec::new(),
+            bot_patterns: Vec::new(),
+            mailmap_path: None,
+            include_working_tree: false,
+            munch_fenced_code_blocks: false,
+            detect_duplicate_lines: false,
+            muncher_index: None,
+            cached_munchers: BTreeMap::new(),
+            munched_files: 0,
         };
 
+        code_rules.set_generated_markers(&Config::default_generated_markers());
+        code_rules.set_bot_patterns(&Config::default_bot_patterns());
+
         // load the contents of file_type definitions one by one
         for file in file_names {
             let contents = EmbeddedCodeRulesFileTypes::get(&file)
@@ -81,9 +200,71 @@ impl CodeRules {
         code_rules
     }
 
+    /// Adds `(glob, muncher_name)` pairs from `Config.muncher_path_overrides` to the list of overrides checked before
+    /// the file-type rules. Overrides with an unknown muncher name or an invalid glob are logged and ignored.
+    pub fn add_muncher_path_overrides(&mut self, overrides: &Vec<(String, String)>) {
+        for (glob, muncher_name) in overrides {
+            // check if the muncher exists to avoid a panic when it is loaded on the first use
+            if !muncher_exists(muncher_name) {
+                error!("Unknown muncher {} in path override for {}", muncher_name, glob);
+                continue;
+            }
+
+            match Regex::new(&glob_to_regex(glob)) {
+                Ok(v) => {
+                    debug!("Muncher path override: {} -> {}", glob, muncher_name);
+                    self.muncher_path_overrides.push((v, muncher_name.clone()));
+                }
+                Err(e) => {
+                    error!("Invalid muncher path override {} due to {}", glob, e);
+                }
+            }
+        }
+    }
+
+    /// Replaces the list of regex for headers of generated files with `Config.generated_markers`.
+    /// Invalid regex are logged and ignored.
+    pub fn set_generated_markers(&mut self, generated_markers: &[String]) {
+        self.generated_markers = compile_regex_list(generated_markers, "generated file marker");
+    }
+
+    /// Replaces the list of regex for names and emails of automated accounts with `Config.bot_patterns`.
+    /// Invalid regex are logged and ignored.
+    pub fn set_bot_patterns(&mut self, bot_patterns: &[String]) {
+        self.bot_patterns = compile_regex_list(bot_patterns, "bot pattern");
+    }
+
+    /// Sets the muncher to fall back on for files with no matching file-type rules.
+    /// An unknown muncher name is logged and ignored.
+    pub fn set_default_text_muncher(&mut self, muncher_name: &String) {
+        // check if the muncher exists to avoid a panic when it is loaded on the first use
+        if !muncher_exists(muncher_name) {
+            error!("Unknown default text muncher {}", muncher_name);
+            return;
+        }
+
+        debug!("Default text muncher: {}", muncher_name);
+        self.default_text_muncher = Some(muncher_name.clone());
+    }
+
     /// Return the right muncher for the file extension extracted from the full path.
+    /// Path overrides take precedence over the file extension. File names and extensions listed in munchers
+    /// are checked if there is no file-type match. The default text muncher, if set, is returned for files
+    /// with no other match.
     pub fn get_muncher(&mut self, file_path: &String) -> Option<&Muncher> {
         debug!("Getting a muncher for: {}", file_path);
+
+        // check if there is an explicit override for this path
+        let override_muncher_name = self
+            .muncher_path_overrides
+            .iter()
+            .find(|(path_regex, _)| path_regex.is_match(file_path))
+            .map(|(_, muncher_name)| muncher_name.clone());
+        if let Some(muncher_name) = override_muncher_name {
+            debug!("Muncher path override: {}", muncher_name);
+            return self.load_muncher(muncher_name);
+        }
+
         // try to get file extension or the file name if it has no extension like Dockerfile
         if let Some(ext) = self.file_ext_regex.find(&file_path) {
             // the file ext regex returns the ext with the separator, which is a ., but if the file has no extension it returns
@@ -101,37 +282,410 @@ impl CodeRules {
                 debug!("Matching file-type: {}", file_type.file_ext);
                 // try to find a matching muncher
                 if let Some(muncher_name) = file_type.get_muncher_name(file_path) {
-                    // load the muncher from its file on the first use
-                    if !self.munchers.contains_key(&muncher_name) {
-                        // all muncher definition files have .json ext
-                        let muncher_file_name = [&muncher_name, ".json"].concat();
-                        trace!("Loading muncher {} for the 1st time", muncher_file_name);
-
-                        let contents = EmbeddedCodeRulesMunchers::get(&muncher_file_name)
-                            .expect(format!("Missing embedded muncher contents: {}", muncher_file_name).as_str());
-                        let contents = std::str::from_utf8(contents.data.as_ref())
-                            .expect(format!("Invalid muncher contents: {}", muncher_file_name).as_str());
-
-                        // Insert None if the muncher could not be loaded so that it doesn't try to load it again
-                        self.munchers
-                            .insert(muncher_name.clone(), Muncher::new(contents, &muncher_name));
-
-                        // indicate to the caller that there were new munchers added so they can be shared with other threads
-                        if self.new_munchers.is_none() {
-                            self.new_munchers = Some(HashSet::new());
-                        }
-                        self.new_munchers.as_mut().unwrap().insert(muncher_name.clone());
-                    }
-
-                    return self.munchers.get(&muncher_name).unwrap().as_ref();
+                    return self.load_muncher(muncher_name);
                 }
             } else {
                 debug!("File-type is unknown");
             }
         }
 
+        // some munchers list file names and extensions that have no file-type rules, e.g. `Dockerfile.prod`
+        let muncher_name = muncher_for_path(file_path, self.muncher_index()).map(|m| m.muncher_name.clone());
+        if let Some(muncher_name) = muncher_name {
+            debug!("Muncher {} matched by file name or extension", muncher_name);
+            return self.load_muncher(muncher_name);
+        }
+
+        if let Some(muncher_name) = self.default_text_muncher.clone() {
+            debug!("Using the default text muncher for {}", file_path);
+            return self.load_muncher(muncher_name);
+        }
+
         debug!("No muncher found for {}", file_path);
 
         None
     }
+
+    /// Returns the muncher with `content_signatures` matching the first lines of a file, e.g. a shebang.
+    /// It is meant for files with no muncher by name. Only munchers with `content_signatures` are loaded for the check.
+    pub fn get_muncher_by_content(&mut self, first_lines: &[String]) -> Option<&Muncher> {
+        let muncher_names = self
+            .muncher_index()
+            .iter()
+            .filter(|muncher| muncher.content_signatures.is_some())
+            .map(|muncher| muncher.muncher_name.clone())
+            .collect::<Vec<String>>();
+        for muncher_name in &muncher_names {
+            self.load_muncher(muncher_name.clone());
+        }
+
+        let munchers = &self.munchers;
+        detect_muncher_by_content(
+            first_lines,
+            muncher_names
+                .iter()
+                .filter_map(|muncher_name| munchers.get(muncher_name)?.as_ref()),
+        )
+    }
+
+    /// Returns the muncher for the language of a fenced code block in Markdown, e.g. `rust` or `bash`.
+    /// Returns None for unknown languages rather than the default text muncher.
+    pub fn get_muncher_for_fence_tag(&mut self, tag: &str) -> Option<&Muncher> {
+        let default_text_muncher = self.default_text_muncher.clone();
+        let file_name = ["fenced_code.", crate::markdown::fence_tag_file_ext(tag)].concat();
+        self.get_muncher(&file_name).filter(|muncher| {
+            default_text_muncher.as_ref() != Some(&muncher.muncher_name)
+                && muncher.language != Muncher::MARKDOWN_LANGUAGE
+        })
+    }
+
+    /// Returns all embedded munchers sorted by name with their rules parsed, but no regex compiled.
+    /// They are parsed on the first call and are only good for lookups by `file_names`, `extensions` or the presence
+    /// of other rules. The munchers picked with them are loaded with `load_muncher`.
+    fn muncher_index(&mut self) -> &Vec<Muncher> {
+        if self.muncher_index.is_none() {
+            let mut munchers = EmbeddedCodeRulesMunchers::iter()
+                .filter_map(|file_name| Some(file_name.strip_suffix(".json")?.to_owned()))
+                .filter_map(|muncher_name| {
+                    // invalid munchers are logged when they are loaded
+                    let contents = compose_muncher_contents(&muncher_name, get_embedded_muncher_contents).ok()?;
+                    let mut muncher = serde_json::from_str::<Muncher>(&contents).ok()?;
+                    muncher.muncher_name = muncher_name;
+                    Some(muncher)
+                })
+                .collect::<Vec<Muncher>>();
+            munchers.sort_unstable_by(|a, b| a.muncher_name.cmp(&b.muncher_name));
+            debug!("Munchers in the index: {}", munchers.len());
+            self.muncher_index = Some(munchers);
+        }
+
+        self.muncher_index
+            .as_ref()
+            .expect("The muncher index is set above. It's a bug.")
+    }
+
+    /// Loads munchers saved by `save_muncher_cache` from `dir` to skip parsing their rules on the first use.
+    /// Cached munchers with rules that differ from the embedded ones are ignored.
+    pub fn load_muncher_cache(&mut self, dir: &Path) {
+        self.cached_munchers = Muncher::load_cached(dir);
+        debug!("Munchers in cache: {}", self.cached_munchers.len());
+    }
+
+    /// Saves all munchers loaded so far and the unused ones from `load_muncher_cache` into a cache file in `dir`.
+    pub fn save_muncher_cache(&self, dir: &Path) -> Result<(), MuncherCacheError> {
+        Muncher::write_cache(dir, self.munchers.values().flatten().chain(self.cached_munchers.values()))
+    }
+
+    /// Returns a muncher by its name, loading it from the cache or the embedded rules on the first use.
+    fn load_muncher(&mut self, muncher_name: String) -> Option<&Muncher> {
+        // load the muncher from its file on the first use
+        if !self.munchers.contains_key(&muncher_name) {
+            trace!("Loading muncher {} for the 1st time", muncher_name);
+
+            // munchers extending each other in a loop are invalid and should fail fast rather than hang the resolver
+            let muncher = match compose_muncher_contents(&muncher_name, get_embedded_muncher_contents) {
+                Ok(contents) => {
+                    let contents_hash = crate::utils::hash_sha1_u64(contents.as_bytes());
+                    match self
+                        .cached_munchers
+                        .remove(&muncher_name)
+                        .filter(|cached| cached.contents_hash == contents_hash)
+                    {
+                        Some(cached) => {
+                            trace!("Muncher {} loaded from cache", muncher_name);
+                            Some(cached)
+                        }
+                        None => Muncher::new(&contents, &muncher_name),
+                    }
+                }
+                Err(e) => {
+                    error!("Cannot load muncher {} due to {}", muncher_name, e);
+                    None
+                }
+            };
+
+            // Insert None if the muncher could not be loaded so that it doesn't try to load it again
+            self.munchers.insert(muncher_name.clone(), muncher);
+
+            // indicate to the caller that there were new munchers added so they can be shared with other threads
+            if self.new_munchers.is_none() {
+                self.new_munchers = Some(HashSet::new());
+            }
+            self.new_munchers.as_mut().unwrap().insert(muncher_name.clone());
+        }
+
+        self.munchers.get(&muncher_name).unwrap().as_ref()
+    }
+}
+
+/// Returns true if there is an embedded muncher file for `muncher_name`, e.g. `rust.rs`.
+pub(crate) fn muncher_exists(muncher_name: &str) -> bool {
+    EmbeddedCodeRulesMunchers::get(&[muncher_name, ".json"].concat()).is_some()
+}
+
+/// Returns the contents of an embedded muncher file.
+/// Returns an error if the muncher does not exist or is not a valid UTF-8.
+fn get_embedded_muncher_contents(muncher_name: &str) -> Result<String, String> {
+    // all muncher definition files have .json ext
+    let muncher_file_name = [muncher_name, ".json"].concat();
+
+    let contents = match EmbeddedCodeRulesMunchers::get(&muncher_file_name) {
+        Some(v) => v,
+        None => return Err(format!("missing muncher {}", muncher_name)),
+    };
+    match std::str::from_utf8(contents.data.as_ref()) {
+        Ok(v) => Ok(v.to_owned()),
+        Err(e) => Err(format!("invalid muncher {}: {}", muncher_name, e)),
+    }
+}
+
+/// Walks the chain of base munchers starting from `muncher_name` using `get_extends` to look up the base of each muncher.
+/// Returns the chain starting with `muncher_name` and ending with the base-most muncher.
+/// Returns an error naming the cycle if a muncher extends itself directly or indirectly, e.g. `a -> b -> a`.
+pub(crate) fn resolve_extends_chain<F>(muncher_name: &str, mut get_extends: F) -> Result<Vec<String>, String>
+where
+    F: FnMut(&str) -> Result<Option<String>, String>,
+{
+    let mut chain = vec![muncher_name.to_owned()];
+    let mut visited: HashSet<String> = HashSet::new();
+    visited.insert(muncher_name.to_owned());
+
+    while let Some(base_name) = get_extends(chain.last().unwrap())? {
+        if visited.contains(&base_name) {
+            chain.push(base_name);
+            return Err(format!("circular muncher extends: {}", chain.join(" -> ")));
+        }
+        visited.insert(base_name.clone());
+        chain.push(base_name);
+    }
+
+    Ok(chain)
+}
+
+/// Returns the definition of `muncher_name` with the definitions of all its base munchers merged in, starting from the
+/// base-most one. Lists and maps of a muncher extend those of its base and all other fields override them.
+/// The contents are returned as-is if the muncher has no base, so that its hash does not depend on the formatting.
+/// Returns an error if any of the munchers is missing or invalid or if they extend each other in a loop.
+pub(crate) fn compose_muncher_contents<G>(muncher_name: &str, get_contents: G) -> Result<String, String>
+where
+    G: Fn(&str) -> Result<String, String>,
+{
+    // each definition is read and parsed once and kept for the merge after its base is looked up
+    let mut definitions: BTreeMap<String, (String, Value)> = BTreeMap::new();
+    let chain = resolve_extends_chain(muncher_name, |name| {
+        let contents = get_contents(name)?;
+        let definition =
+            serde_json::from_str::<Value>(&contents).map_err(|e| format!("invalid muncher {}: {}", name, e))?;
+        let extends = match definition.get("extends") {
+            None | Some(Value::Null) => None,
+            Some(Value::String(v)) => Some(v.clone()),
+            Some(_) => return Err(format!("invalid muncher {}: extends must be a string", name)),
+        };
+        definitions.insert(name.to_owned(), (contents, definition));
+        Ok(extends)
+    })?;
+    if chain.len() == 1 {
+        return Ok(definitions
+            .remove(muncher_name)
+            .expect("the chain starts with the muncher")
+            .0);
+    }
+    debug!("Muncher extends chain: {}", chain.join(" -> "));
+
+    let mut composed = Value::Object(Map::new());
+    for name in chain.iter().rev() {
+        let (_, definition) = definitions.remove(name).expect("every muncher in the chain is parsed");
+        merge_muncher_definitions(&mut composed, definition);
+    }
+
+    Ok(composed.to_string())
+}
+
+/// Merges `child` muncher definition into `base` in place. Arrays are appended to with the items `base` does not have,
+/// objects are merged key by key and any other values are replaced.
+fn merge_muncher_definitions(base: &mut Value, child: Value) {
+    match (base, child) {
+        (Value::Object(base), Value::Object(child)) => {
+            for (key, value) in child {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_muncher_definitions(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(child)) => {
+            for value in child {
+                if !base.contains(&value) {
+                    base.push(value);
+                }
+            }
+        }
+        (base, child) => *base = child,
+    }
+}
+
+/// Compiles every regex in the list. Invalid regex are logged with `what` they are for and skipped.
+fn compile_regex_list(patterns: &[String], what: &str) -> Vec<Regex> {
+    patterns
+        .iter()
+        .filter_map(|pattern| match Regex::new(pattern) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!("Invalid {} {} due to {}", what, pattern, e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_glob_to_regex() {
+    let regex = Regex::new(&glob_to_regex("config/*.txt")).unwrap();
+    assert!(regex.is_match("config/app.txt"));
+    assert!(!regex.is_match("config/sub/app.txt"));
+    assert!(!regex.is_match("myconfig/app.txt"));
+
+    let regex = Regex::new(&glob_to_regex("**/*.txt")).unwrap();
+    assert!(regex.is_match("app.txt"));
+    assert!(regex.is_match("config/sub/app.txt"));
+}
+
+#[test]
+fn test_resolve_extends_chain() {
+    let extends = |name: &str| -> Result<Option<String>, String> {
+        match name {
+            "objc" => Ok(Some("cpp".to_owned())),
+            "cpp" => Ok(Some("c".to_owned())),
+            "loop_a" => Ok(Some("loop_b".to_owned())),
+            "loop_b" => Ok(Some("loop_a".to_owned())),
+            _ => Ok(None),
+        }
+    };
+
+    assert_eq!(resolve_extends_chain("objc", extends).unwrap(), vec!["objc", "cpp", "c"]);
+    assert_eq!(
+        resolve_extends_chain("loop_a", extends).unwrap_err(),
+        "circular muncher extends: loop_a -> loop_b -> loop_a"
+    );
+}
+
+#[test]
+fn test_get_muncher_by_content() {
+    let mut code_rules = CodeRules::new();
+    assert!(code_rules.get_muncher(&"deploy".to_owned()).is_none());
+
+    let first_lines = vec!["#!/usr/bin/env bash".to_owned(), "set -e".to_owned()];
+    assert_eq!(
+        code_rules
+            .get_muncher_by_content(&first_lines)
+            .map(|m| m.muncher_name.as_str()),
+        Some("shell")
+    );
+    assert!(code_rules.get_muncher_by_content(&["plain text".to_owned()]).is_none());
+
+    // only munchers with content rules are compiled for the check
+    assert!(code_rules
+        .munchers
+        .values()
+        .flatten()
+        .all(|muncher| muncher.content_signatures.is_some()));
+}
+
+#[test]
+fn test_compose_muncher_contents() {
+    let definitions: BTreeMap<&str, &str> = [
+        ("c", r#"{"language": "C", "keywords": ["\\bstruct\\b"], "line_comments": ["^\\s*//"]}"#),
+        (
+            "cpp",
+            r#"{"language": "C++", "extends": "c", "keywords": ["\\bclass\\b", "\\bstruct\\b"]}"#,
+        ),
+        ("loop_a", r#"{"language": "A", "extends": "loop_b"}"#),
+        ("loop_b", r#"{"language": "B", "extends": "loop_a"}"#),
+    ]
+    .iter()
+    .cloned()
+    .collect();
+    let reads = std::cell::Cell::new(0);
+    let get_contents = |name: &str| {
+        reads.set(reads.get() + 1);
+        definitions
+            .get(name)
+            .map(|v| v.to_string())
+            .ok_or_else(|| format!("missing muncher {}", name))
+    };
+
+    // the child adds its keywords to those of the base and overrides the language
+    let contents = compose_muncher_contents("cpp", get_contents).unwrap();
+    assert_eq!(reads.get(), 2, "each muncher in the chain is read once");
+    let muncher = Muncher::new(&contents, &"cpp".to_owned()).unwrap();
+    assert_eq!(muncher.language, "C++");
+    assert_eq!(muncher.extends.as_deref(), Some("c"));
+    assert_eq!(muncher.keywords.as_ref().unwrap().len(), 2);
+    assert_eq!(muncher.line_comments.as_ref().unwrap().len(), 1);
+
+    // the hash reflects the inherited rules
+    let own_rules_only = Muncher::new(&get_contents("cpp").unwrap(), &"cpp".to_owned()).unwrap();
+    assert_ne!(muncher.muncher_hash, own_rules_only.muncher_hash);
+
+    // a muncher with no base is unchanged
+    assert_eq!(compose_muncher_contents("c", get_contents).unwrap(), get_contents("c").unwrap());
+
+    assert_eq!(
+        compose_muncher_contents("loop_a", get_contents).unwrap_err(),
+        "circular muncher extends: loop_a -> loop_b -> loop_a"
+    );
+}
+
+#[test]
+fn test_get_muncher_by_file_name() {
+    let mut code_rules = CodeRules::new();
+    for file_name in ["Dockerfile", "deploy/Dockerfile.prod", "Containerfile"].iter() {
+        assert_eq!(
+            code_rules
+                .get_muncher(&file_name.to_string())
+                .map(|m| m.muncher_name.as_str()),
+            Some("docker"),
+            "{}",
+            file_name
+        );
+    }
+
+    // only the matching muncher is compiled
+    assert_eq!(code_rules.munchers.keys().collect::<Vec<&String>>(), vec!["docker"]);
+}
+
+#[test]
+fn test_muncher_cache() {
+    let dir = std::env::temp_dir().join(format!("stm_code_rules_muncher_cache_{}", uuid::Uuid::new_v4()));
+    let mut code_rules = CodeRules::new();
+    let muncher_hash = code_rules.get_muncher(&"main.rs".to_owned()).unwrap().muncher_hash;
+    code_rules.save_muncher_cache(&dir).unwrap();
+
+    // the cached muncher is used instead of parsing the embedded rules
+    let mut code_rules = CodeRules::new();
+    code_rules.load_muncher_cache(&dir);
+    assert!(code_rules.cached_munchers.contains_key("rust.rs"));
+    assert_eq!(code_rules.get_muncher(&"main.rs".to_owned()).unwrap().muncher_hash, muncher_hash);
+    assert!(!code_rules.cached_munchers.contains_key("rust.rs"));
+
+    // a muncher cached with different rules is ignored
+    let mut code_rules = CodeRules::new();
+    code_rules.load_muncher_cache(&dir);
+    code_rules.cached_munchers.get_mut("rust.rs").unwrap().contents_hash += 1;
+    code_rules.cached_munchers.get_mut("rust.rs").unwrap().keywords = None;
+    let muncher = code_rules.get_muncher(&"main.rs".to_owned()).unwrap();
+    assert_eq!(muncher.muncher_hash, muncher_hash);
+    assert!(muncher.keywords.is_some());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_canonical_language() {
+    assert_eq!(CodeRules::canonical_language("JS"), Some("JavaScript"));
+    assert_eq!(CodeRules::canonical_language("JavaScript"), None);
+    // the names are case-sensitive as in the munchers
+    assert_eq!(CodeRules::canonical_language("js"), None);
 }
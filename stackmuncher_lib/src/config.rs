@@ -1,4 +1,6 @@
 use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
 
 #[derive(Debug)]
 pub struct Config {
@@ -18,6 +20,68 @@ pub struct Config {
     /// List of contributors to generate reports for. Defaults to Git user, author and committer .email values.
     /// Can be overridden by CLI params. The first value in the list is the preferred user contact.
     pub git_identities: Vec<String>,
+    /// A list of `(glob, muncher_name)` pairs to force a particular muncher for matching paths regardless
+    /// of the file extension, e.g. `("config/*.txt", "ini")`. They are checked before the extension-based rules.
+    pub muncher_path_overrides: Vec<(String, String)>,
+    /// Control characters to remove from every line before it is classified, e.g. form feeds in older C code
+    /// that break keyword matching. Set it to `Config::COMMON_STRAY_CONTROL_CHARS` to enable. Defaults to None (off).
+    pub strip_control_chars: Option<Vec<char>>,
+    /// The max time to spend on munching files of a single repo. Files that were not processed by then are
+    /// left unprocessed and the report is flagged as `timed_out`. Defaults to None (no limit).
+    pub max_processing_duration: Option<Duration>,
+    /// The name of the muncher for text files that have no specific muncher, e.g. `text`. They only get their
+    /// total and blank lines counted. Binary and empty files are still skipped. Defaults to None (off).
+    pub default_text_muncher: Option<String>,
+    /// A folder for caching munched files by blob SHA1, muncher hash and settings to avoid reprocessing unchanged files
+    /// across runs and repos. Defaults to None (off).
+    pub blob_cache_dir: Option<PathBuf>,
+    /// A folder for caching parsed munchers across runs, see `CodeRules::load_muncher_cache`. Defaults to None (off).
+    pub muncher_cache_dir: Option<PathBuf>,
+    /// Record the line number where each ref first appeared in every file in `Tech.ref_first_seen` of `per_file_tech`.
+    /// It makes reports considerably larger. Defaults to false.
+    pub track_ref_first_seen: bool,
+    /// Files larger than this are not munched and are listed in `Report.unprocessed_file_reasons`.
+    /// Defaults to `Config::DEFAULT_MAX_FILE_SIZE_BYTES`. Set it to None to munch files of any size.
+    pub max_file_size_bytes: Option<u64>,
+    /// Files with the average line length over this number of characters are most likely minified JS or CSS and are
+    /// listed in `Report.unprocessed_file_reasons` instead of being munched. Only applies to munchers with
+    /// `check_minified`, which defaults to programming languages.
+    /// Defaults to `Config::DEFAULT_MAX_AVG_LINE_LENGTH`. Set it to None to munch files with lines of any length.
+    pub max_avg_line_length: Option<usize>,
+    /// The max number of files munched at the same time on the tokio thread pool. The results are the same
+    /// regardless of the number. Defaults to `Config::default_max_concurrent_files()`, one file per CPU.
+    pub max_concurrent_files: usize,
+    /// Files with no muncher matching their name are checked against `content_signatures` of all munchers,
+    /// e.g. a script with a shebang and no extension. It requires reading those files. Defaults to true.
+    pub detect_munchers_by_content: bool,
+    /// Gitignore-style patterns for files that should not be processed in addition to the repo's `.gitignore`
+    /// and `.stmignore`, e.g. `vendor/` or `*.generated.cs`. They take precedence over negations in those files.
+    pub ignore_patterns: Vec<String>,
+    /// Gitignore-style patterns for test files, e.g. `*_test.*`. Their `per_file_tech` records are flagged with `is_test`
+    /// and their code lines are added up in `Tech.test_code_lines`. Defaults to `Config::DEFAULT_TEST_PATH_PATTERNS`.
+    pub test_path_patterns: Vec<String>,
+    /// Regex for headers of generated files, e.g. `// Code generated by protoc-gen-go. DO NOT EDIT.` The files are
+    /// munched as usual, but their `per_file_tech` records are flagged with `is_generated`.
+    /// Defaults to `Config::DEFAULT_GENERATED_MARKERS`.
+    pub generated_markers: Vec<String>,
+    /// Regex for names and emails of automated accounts, e.g. `dependabot[bot]`. They are removed from the list of
+    /// contributors of the project report. Defaults to `Config::DEFAULT_BOT_PATTERNS`.
+    pub bot_patterns: Vec<String>,
+    /// The path to a `.mailmap` file for mapping multiple names and emails of the same developer to a single
+    /// contributor identity. A relative path starts at `project_dir`. Defaults to None (`.mailmap` in the project root).
+    pub mailmap_path: Option<PathBuf>,
+    /// Read files from the working tree on disk instead of committed blobs and include untracked files that are not
+    /// ignored by `.gitignore`, e.g. to preview the report before committing. Cached reports are not reused.
+    /// Defaults to false.
+    pub include_working_tree: bool,
+    /// Code in fenced blocks of Markdown files, e.g. ```` ```rust ````, is munched with the muncher for the language
+    /// of the fence and gets its own `per_file_tech` record. Blocks with unknown languages are left as Markdown.
+    /// Defaults to false.
+    pub munch_fenced_code_blocks: bool,
+    /// Hash every code line with the whitespace collapsed to count `Tech.duplicated_lines`, i.e. code lines seen
+    /// in another file before, e.g. copy-pasted boilerplate. Cached reports and blobs are not reused.
+    /// Defaults to false.
+    pub detect_duplicate_lines: bool,
 }
 
 impl Config {
@@ -28,6 +92,56 @@ impl Config {
     pub const CONTRIBUTOR_REPORT_SANITIZED_FILE_NAME: &'static str = "submission";
     pub const REPORT_FILE_EXTENSION: &'static str = ".json";
     pub const GIT_FOLDER_NAME: &'static str = ".git";
+    /// Control characters that are often found in source files without being a part of the code:
+    /// form feed, vertical tab and NUL.
+    pub const COMMON_STRAY_CONTROL_CHARS: [char; 3] = ['\x0C', '\x0B', '\0'];
+    /// Source files larger than 5MB are most likely generated or data files.
+    pub const DEFAULT_MAX_FILE_SIZE_BYTES: u64 = 5 * 1024 * 1024;
+    /// Hand-written code rarely averages more than 100 characters per line, minified files have lines of thousands.
+    pub const DEFAULT_MAX_AVG_LINE_LENGTH: usize = 500;
+    /// Common locations and names of test files across languages.
+    pub const DEFAULT_TEST_PATH_PATTERNS: [&'static str; 4] = ["**/tests/**", "*_test.*", "*.spec.*", "*.test.*"];
+
+    /// Returns `DEFAULT_TEST_PATH_PATTERNS` as a Vec for `test_path_patterns`.
+    pub fn default_test_path_patterns() -> Vec<String> {
+        Config::DEFAULT_TEST_PATH_PATTERNS
+            .iter()
+            .map(|v| v.to_string())
+            .collect()
+    }
+
+    /// Markers of generated files used by Go, protobuf, Facebook tools and .NET, checked in the file header.
+    pub const DEFAULT_GENERATED_MARKERS: [&'static str; 3] = [
+        r"Code generated .* DO NOT EDIT",
+        r"@generated\b",
+        r"(?i)<auto-generated",
+    ];
+
+    /// Returns `DEFAULT_GENERATED_MARKERS` as a Vec for `generated_markers`.
+    pub fn default_generated_markers() -> Vec<String> {
+        Config::DEFAULT_GENERATED_MARKERS
+            .iter()
+            .map(|v| v.to_string())
+            .collect()
+    }
+
+    /// GitHub Apps commit as `name[bot]`, other common bots use their own accounts.
+    pub const DEFAULT_BOT_PATTERNS: [&'static str; 4] = [
+        r"(?i)\[bot\]",
+        r"(?i)^dependabot",
+        r"(?i)^renovate(-bot)?(@|$)",
+        r"(?i)^snyk-bot(@|$)",
+    ];
+
+    /// Returns `DEFAULT_BOT_PATTERNS` as a Vec for `bot_patterns`.
+    pub fn default_bot_patterns() -> Vec<String> {
+        Config::DEFAULT_BOT_PATTERNS.iter().map(|v| v.to_string()).collect()
+    }
+
+    /// Returns the number of CPUs available to the process for `max_concurrent_files` or 1 if it cannot be determined.
+    pub fn default_max_concurrent_files() -> usize {
+        std::thread::available_parallelism().map(|v| v.get()).unwrap_or(1)
+    }
 
     /// Returns a minimal version of Self with no validation and default values.
     /// It compiles some regex and should be cached
@@ -39,6 +153,25 @@ impl Config {
             user_name,
             repo_name,
             git_identities: Vec::new(),
+            muncher_path_overrides: Vec::new(),
+            strip_control_chars: None,
+            max_processing_duration: None,
+            default_text_muncher: None,
+            blob_cache_dir: None,
+            muncher_cache_dir: None,
+            track_ref_first_seen: false,
+            max_file_size_bytes: Some(Config::DEFAULT_MAX_FILE_SIZE_BYTES),
+            max_avg_line_length: Some(Config::DEFAULT_MAX_AVG_LINE_LENGTH),
+            max_concurrent_files: Config::default_max_concurrent_files(),
+            detect_munchers_by_content: true,
+            ignore_patterns: Vec::new(),
+            test_path_patterns: Config::default_test_path_patterns(),
+            generated_markers: Config::default_generated_markers(),
+            bot_patterns: Config::default_bot_patterns(),
+            mailmap_path: None,
+            include_working_tree: false,
+            munch_fenced_code_blocks: false,
+            detect_duplicate_lines: false,
         }
     }
 
@@ -53,6 +186,127 @@ impl Config {
             user_name: String::new(),
             repo_name: String::new(),
             git_identities: Vec::new(),
+            muncher_path_overrides: Vec::new(),
+            strip_control_chars: None,
+            max_processing_duration: None,
+            default_text_muncher: None,
+            blob_cache_dir: None,
+            muncher_cache_dir: None,
+            track_ref_first_seen: false,
+            max_file_size_bytes: Some(Config::DEFAULT_MAX_FILE_SIZE_BYTES),
+            max_avg_line_length: Some(Config::DEFAULT_MAX_AVG_LINE_LENGTH),
+            max_concurrent_files: Config::default_max_concurrent_files(),
+            detect_munchers_by_content: true,
+            ignore_patterns: Vec::new(),
+            test_path_patterns: Config::default_test_path_patterns(),
+            generated_markers: Config::default_generated_markers(),
+            bot_patterns: Config::default_bot_patterns(),
+            mailmap_path: None,
+            include_working_tree: false,
+            munch_fenced_code_blocks: false,
+            detect_duplicate_lines: false,
+        }
+    }
+
+    /// Checks the config for problems that would otherwise fail much later, e.g. during processing.
+    /// Returns all the problems found, not just the first one.
+    /// The munchers are embedded in the library, so only the muncher names used in the config are checked.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        if !self.project_dir.is_dir() {
+            errors.push(ConfigError::ProjectDirNotFound(self.project_dir.clone()));
+        }
+
+        // reports are named `user/repo`, so both parts must be present if any of them is
+        if !self.user_name.is_empty() || !self.repo_name.is_empty() {
+            if self.user_name.is_empty() {
+                errors.push(ConfigError::EmptyUserName);
+            }
+            if self.repo_name.is_empty() {
+                errors.push(ConfigError::EmptyRepoName);
+            }
+        }
+
+        if let Some(muncher_name) = &self.default_text_muncher {
+            if !crate::code_rules::muncher_exists(muncher_name) {
+                errors.push(ConfigError::UnknownMuncher(muncher_name.clone()));
+            }
+        }
+        for (_, muncher_name) in &self.muncher_path_overrides {
+            if !crate::code_rules::muncher_exists(muncher_name) {
+                errors.push(ConfigError::UnknownMuncher(muncher_name.clone()));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
     }
 }
+
+/// Problems with the config found by `Config::validate`.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// `Config.project_dir` does not exist or is not a directory
+    #[error("Project directory {} does not exist", .0.to_string_lossy())]
+    ProjectDirNotFound(PathBuf),
+    /// `Config.repo_name` is set, but `Config.user_name` is empty
+    #[error("GitHub user name is empty")]
+    EmptyUserName,
+    /// `Config.user_name` is set, but `Config.repo_name` is empty
+    #[error("GitHub repo name is empty")]
+    EmptyRepoName,
+    /// There is no embedded muncher with this name, e.g. in `Config.default_text_muncher`
+    #[error("Unknown muncher {0}")]
+    UnknownMuncher(String),
+}
+
+#[test]
+fn test_validate_collects_all_errors() {
+    let mut config = Config::new("rimutaka".to_owned(), String::new());
+    config.project_dir = std::env::temp_dir().join(format!("stm_missing_{}", uuid::Uuid::new_v4()));
+    config.default_text_muncher = Some("no_such_muncher".to_owned());
+
+    assert_eq!(
+        config.validate(),
+        Err(vec![
+            ConfigError::ProjectDirNotFound(config.project_dir.clone()),
+            ConfigError::EmptyRepoName,
+            ConfigError::UnknownMuncher("no_such_muncher".to_owned()),
+        ])
+    );
+
+    config.project_dir = std::env::temp_dir();
+    config.repo_name = "stm".to_owned();
+    config.default_text_muncher = Some("text".to_owned());
+    config.muncher_path_overrides = vec![("config/*.txt".to_owned(), "shell".to_owned())];
+    assert_eq!(config.validate(), Ok(()));
+}
+
+#[test]
+fn test_validate_missing_project_dir() {
+    let mut config = Config::new_with_defaults(&tracing::Level::INFO);
+    config.project_dir = PathBuf::from("no/such/project/dir");
+
+    // unnamed projects are fine
+    assert_eq!(config.validate(), Err(vec![ConfigError::ProjectDirNotFound(config.project_dir.clone())]));
+}
+
+#[test]
+fn test_default_test_path_patterns() {
+    let mut test_paths = crate::ignore::IgnoreList::default();
+    test_paths.add_patterns(Config::DEFAULT_TEST_PATH_PATTERNS.iter().cloned());
+
+    assert!(test_paths.is_ignored("src/foo_test.rs"));
+    assert!(test_paths.is_ignored("tests/integration.rs"));
+    assert!(test_paths.is_ignored("crates/lib/tests/data/fixture.json"));
+    assert!(test_paths.is_ignored("web/app.spec.ts"));
+    assert!(test_paths.is_ignored("web/app.test.js"));
+
+    assert!(!test_paths.is_ignored("src/foo.rs"));
+    assert!(!test_paths.is_ignored("src/latest.rs"));
+    assert!(!test_paths.is_ignored("src/contest/main.rs"));
+}
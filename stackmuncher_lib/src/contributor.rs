@@ -1,4 +1,6 @@
 use super::git::GitLogEntry;
+use crate::mailmap::Mailmap;
+use crate::utils::sha256::hash_str_to_sha256_as_base58;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
@@ -13,6 +15,7 @@ pub struct Contributor {
     pub git_id: String,
     /// A list of possible identities as name/email pairs for extracting contact details and de-duplication.
     /// E.g. `Author: rimutaka <max@onebro.me> would be `rimutaka`/`max@onebro.me`.
+    #[serde(serialize_with = "crate::report::sorted::set")]
     pub name_email_pairs: HashSet<(String, String)>,
     /// The full SHA1 of the very last commit by this contributor. This bit should be retained for matching repositories on STM server.
     pub last_commit_sha1: String,
@@ -25,7 +28,19 @@ pub struct Contributor {
     /// in the future, so it's easier to have a separate counter.
     #[serde(default)]
     pub commit_count: u64,
+    /// The total number of lines inserted by this contributor across all commits. Binary files are not counted.
+    #[serde(default)]
+    pub lines_added: u64,
+    /// The total number of lines deleted by this contributor across all commits. Binary files are not counted.
+    #[serde(default)]
+    pub lines_removed: u64,
+    /// Number of commits by this contributor per conventional commit type, e.g. `feat`, `fix`, `docs`.
+    /// Commits that do not follow the convention are not counted.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[serde(serialize_with = "crate::report::sorted::option_map")]
+    pub commit_type_counts: Option<HashMap<String, usize>>,
     /// The list of files touched by this contributor as FileName/CommitSHA1 tuple.
+    #[serde(serialize_with = "crate::report::sorted::set")]
     pub touched_files: HashSet<ContributorFile>,
     /// A list of pointers at contributor commits in recent project commits member of Report.
     #[serde(skip_serializing_if = "Vec::is_empty", default = "Vec::new")]
@@ -60,6 +75,18 @@ impl PartialEq for ContributorFile {
     }
 }
 
+impl Ord for ContributorFile {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.name.cmp(&other.name)
+    }
+}
+
+impl PartialOrd for ContributorFile {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Contributor {
     /// De-dupes and normalizes the list of contributors from the provided commit history.
     ///
@@ -67,68 +94,99 @@ impl Contributor {
     /// the name or email. E.g. rimutaka/max@onebro.me or maxv/max@onebro.me. They can be merged and de-duped
     /// to some extent, but the process is prone to errors. E.g. common user names such as `admin` or `ubuntu`
     /// can be pointing at completely different people.
-    pub(crate) fn from_commit_history(commits: Vec<GitLogEntry>) -> Vec<Contributor> {
+    ///
+    /// Aliases listed in `mailmap` are merged into the contributor with the canonical identity.
+    pub(crate) fn from_commit_history(commits: Vec<GitLogEntry>, mailmap: &Mailmap) -> Vec<Contributor> {
         // the output collector: a map of Contributors with the contributor git identity as the key
         // each contributor has a hashmap with file as the key and commit/date/timestamp tuple that gets converted into an Vec for touched_files property
         let mut contributors: HashMap<String, (Contributor, HashMap<String, (String, String, i64)>)> = HashMap::new();
 
         for (commit_idx, commit) in commits.into_iter().enumerate() {
-            // skip commits with no author details
-            if commit.author_name_email.0.is_empty() && commit.author_name_email.1.is_empty() {
-                continue;
-            }
+            // co-authors from `Co-authored-by:` trailers get the same credit for the commit as the author
+            let mut author_name_emails = Vec::with_capacity(commit.co_author_name_emails.len() + 1);
+            author_name_emails.push(commit.author_name_email.clone());
+            author_name_emails.extend(commit.co_author_name_emails.iter().cloned());
 
-            // choose the preferred identity for this contributor
-            let git_identity = Self::git_identity_from_name_email_pair(&commit.author_name_email);
+            // the author may also be listed as a co-author
+            let mut commit_git_identities: HashSet<String> = HashSet::new();
 
-            // check if the contributor is already in the output collector
-            if let Some((contributor, touched_files)) = contributors.get_mut(&git_identity) {
-                // this is a known contributor - merge with the existing one
-                contributor
-                    .name_email_pairs
-                    .insert((commit.author_name_email.0, commit.author_name_email.1));
+            // e.g. `feat` or `fix`, it is the same for all authors of the commit
+            let commit_type = commit.conventional_commit_type();
 
-                // only the latest version of the file is of interest
-                for file in commit.files {
-                    if !touched_files.contains_key(&file) {
-                        touched_files.insert(file, (commit.sha1.clone(), commit.date.clone(), commit.date_epoch));
-                    }
+            // every author of the commit is credited with all the lines
+            let (lines_added, lines_removed) = commit
+                .line_changes
+                .values()
+                .fold((0, 0), |(added, removed), (inserted, deleted)| (added + inserted, removed + deleted));
+
+            for author_name_email in author_name_emails {
+                // skip identities with no details
+                if author_name_email.0.is_empty() && author_name_email.1.is_empty() {
+                    continue;
                 }
 
-                // add the commit to the list of contributor commits
-                // the cast should be safe because the max number of commits within a project is well within u64 bounds
-                contributor.commits.push(commit_idx as u64);
-            } else {
-                // it's a new contributor - add as-is
-
-                // add the identities as name/email pairs
-                let mut name_email_pairs: HashSet<(String, String)> = HashSet::new();
-                name_email_pairs.insert((commit.author_name_email.0, commit.author_name_email.1));
-
-                // collect the list of touched files with the commit SHA1
-                let mut touched_files: HashMap<String, (String, String, i64)> = HashMap::new();
-                for file in commit.files {
-                    if !touched_files.contains_key(&file) {
-                        touched_files.insert(file, (commit.sha1.clone(), commit.date.clone(), commit.date_epoch));
-                    }
+                // choose the preferred identity for this contributor
+                let git_identity = Self::git_identity_from_name_email_pair(&author_name_email, mailmap);
+                if !commit_git_identities.insert(git_identity.clone()) {
+                    continue;
                 }
 
-                // add the commit to the list of contributor commits
-                let contr_commits_list = vec![commit_idx as u64];
-
-                // init the contributor
-                let contributor = Contributor {
-                    git_id: git_identity.clone(),
-                    name_email_pairs,
-                    last_commit_sha1: commit.sha1,
-                    last_commit_epoch: commit.date_epoch,
-                    last_commit_date: commit.date,
-                    touched_files: HashSet::new(),
-                    commits: contr_commits_list,
-                    commit_count: 1,
-                };
-
-                contributors.insert(git_identity, (contributor, touched_files));
+                // check if the contributor is already in the output collector
+                if let Some((contributor, touched_files)) = contributors.get_mut(&git_identity) {
+                    // this is a known contributor - merge with the existing one
+                    contributor.name_email_pairs.insert(author_name_email);
+
+                    // only the latest version of the file is of interest
+                    for file in &commit.files {
+                        if !touched_files.contains_key(file) {
+                            touched_files
+                                .insert(file.clone(), (commit.sha1.clone(), commit.date.clone(), commit.date_epoch));
+                        }
+                    }
+
+                    // add the commit to the list of contributor commits
+                    // the cast should be safe because the max number of commits within a project is well within u64 bounds
+                    contributor.commits.push(commit_idx as u64);
+                    contributor.lines_added += lines_added;
+                    contributor.lines_removed += lines_removed;
+                    contributor.add_commit_type(commit_type.as_ref());
+                } else {
+                    // it's a new contributor - add as-is
+
+                    // add the identities as name/email pairs
+                    let mut name_email_pairs: HashSet<(String, String)> = HashSet::new();
+                    name_email_pairs.insert(author_name_email);
+
+                    // collect the list of touched files with the commit SHA1
+                    let mut touched_files: HashMap<String, (String, String, i64)> = HashMap::new();
+                    for file in &commit.files {
+                        if !touched_files.contains_key(file) {
+                            touched_files
+                                .insert(file.clone(), (commit.sha1.clone(), commit.date.clone(), commit.date_epoch));
+                        }
+                    }
+
+                    // add the commit to the list of contributor commits
+                    let contr_commits_list = vec![commit_idx as u64];
+
+                    // init the contributor
+                    let mut contributor = Contributor {
+                        git_id: git_identity.clone(),
+                        name_email_pairs,
+                        last_commit_sha1: commit.sha1.clone(),
+                        last_commit_epoch: commit.date_epoch,
+                        last_commit_date: commit.date.clone(),
+                        touched_files: HashSet::new(),
+                        commits: contr_commits_list,
+                        commit_count: 1,
+                        lines_added,
+                        lines_removed,
+                        commit_type_counts: None,
+                    };
+                    contributor.add_commit_type(commit_type.as_ref());
+
+                    contributors.insert(git_identity, (contributor, touched_files));
+                }
             }
         }
 
@@ -153,11 +211,35 @@ impl Contributor {
             output_collector.push(contributor);
         }
 
+        // the order of the hashmap is random and it would make the same report serialize differently every time
+        output_collector.sort_by(|a, b| a.git_id.cmp(&b.git_id));
+
         output_collector
     }
 
-    /// Converts name email pairs, e.g. rimutaka|max@onebro.me into a git identity in a consistent way across the project
-    pub(crate) fn git_identity_from_name_email_pair(author_name_email: &(String, String)) -> String {
+    /// Adds a commit of `commit_type` to `commit_type_counts`. Commits with no type are ignored.
+    fn add_commit_type(&mut self, commit_type: Option<&String>) {
+        if let Some(commit_type) = commit_type {
+            *self
+                .commit_type_counts
+                .get_or_insert_with(HashMap::new)
+                .entry(commit_type.clone())
+                .or_insert(0) += 1;
+        }
+    }
+
+    /// Returns a salted SHA256 hash of `git_id` encoded as base58 for identifying the contributor in public reports
+    /// without revealing the name or email. The identity is trimmed and lower-cased before hashing, so the hash
+    /// is the same for `Max@onebro.me` and `max@onebro.me`. The same `salt` must be used for all reports that
+    /// need to be matched, but it should not be shared.
+    pub fn anonymized_hash(&self, salt: &str) -> String {
+        hash_str_to_sha256_as_base58(&[salt, self.git_id.trim().to_lowercase().as_str()].concat())
+    }
+
+    /// Converts name email pairs, e.g. rimutaka|max@onebro.me into a git identity in a consistent way across the project.
+    /// The pair is mapped to the canonical name and email from `mailmap` first, if there is a match.
+    pub(crate) fn git_identity_from_name_email_pair(author_name_email: &(String, String), mailmap: &Mailmap) -> String {
+        let author_name_email = mailmap.canonical(author_name_email);
         if !author_name_email.1.is_empty() {
             author_name_email.1.clone()
         } else {
@@ -165,3 +247,125 @@ impl Contributor {
         }
     }
 }
+
+#[test]
+fn test_anonymized_hash() {
+    let contributor = |git_id: &str| Contributor {
+        git_id: git_id.to_string(),
+        name_email_pairs: HashSet::new(),
+        last_commit_sha1: String::new(),
+        last_commit_epoch: 0,
+        last_commit_date: String::new(),
+        commit_count: 0,
+        lines_added: 0,
+        lines_removed: 0,
+        commit_type_counts: None,
+        touched_files: HashSet::new(),
+        commits: Vec::new(),
+    };
+
+    let hash = contributor("max@onebro.me").anonymized_hash("salt");
+    // pinned to detect accidental changes that would break matching with previously published reports
+    assert_eq!(hash, "xJ3ikox9vuAKr6sZ9r1999kXXesfBV79w7tk5gJW893");
+    assert_eq!(contributor(" Max@OneBro.me ").anonymized_hash("salt"), hash);
+    assert_ne!(contributor("max@onebro.me").anonymized_hash("pepper"), hash);
+    assert_ne!(contributor("rimutaka@onebro.me").anonymized_hash("salt"), hash);
+}
+
+#[test]
+fn test_co_authors_are_contributors() {
+    let mut commit = GitLogEntry::new();
+    commit.sha1 = "d5e742de653954bfae88f0e5f6c8f0a7a5f6c437".to_owned();
+    commit.date_epoch = 1627380297;
+    commit.author_name_email = ("Alice".to_owned(), "alice@example.com".to_owned());
+    commit.msg = "\nAdd a parser (#42)\n\nCo-authored-by: Bob <bob@example.com>\nco-authored-by: Carol <carol@example.com>\nCo-authored-by: Alice <alice@example.com>".to_owned();
+    commit.co_author_name_emails = crate::git::co_authors_from_msg(&commit.msg);
+    commit.files.insert("src/parser.rs".to_owned());
+
+    // the author listed as a co-author is not counted twice
+    assert_eq!(commit.co_author_name_emails.len(), 3);
+
+    let contributors = Contributor::from_commit_history(vec![commit], &Mailmap::default());
+    let git_ids = contributors.iter().map(|c| c.git_id.as_str()).collect::<Vec<&str>>();
+    assert_eq!(git_ids, vec!["alice@example.com", "bob@example.com", "carol@example.com"]);
+    for contributor in &contributors {
+        assert_eq!(contributor.commit_count, 1);
+        assert_eq!(contributor.touched_files.len(), 1);
+        assert_eq!(contributor.last_commit_epoch, 1627380297);
+    }
+}
+
+#[test]
+fn test_line_changes_per_contributor() {
+    let commit = |author: &str, line_changes: &[(&str, (u64, u64))]| {
+        let mut commit = GitLogEntry::new();
+        commit.sha1 = "d5e742de653954bfae88f0e5f6c8f0a7a5f6c437".to_owned();
+        commit.author_name_email = (author.to_owned(), [author, "@example.com"].concat());
+        for (file_name, changes) in line_changes {
+            commit.files.insert(file_name.to_string());
+            commit.line_changes.insert(file_name.to_string(), *changes);
+        }
+        commit
+    };
+    let mut binary_commit = commit("alice", &[("src/main.rs", (1, 1))]);
+    binary_commit.files.insert("assets/logo.png".to_owned());
+
+    let contributors = Contributor::from_commit_history(
+        vec![
+            commit("alice", &[("src/main.rs", (10, 2)), ("src/lib.rs", (5, 0))]),
+            commit("bob", &[("README.md", (3, 7))]),
+            binary_commit,
+        ],
+        &Mailmap::default(),
+    );
+
+    assert_eq!(contributors[0].git_id, "alice@example.com");
+    assert_eq!((contributors[0].lines_added, contributors[0].lines_removed), (16, 3));
+    assert_eq!((contributors[1].lines_added, contributors[1].lines_removed), (3, 7));
+}
+
+#[test]
+fn test_commit_types_per_contributor() {
+    let commit = |author: &str, subject: &str| {
+        let mut commit = GitLogEntry::new();
+        commit.sha1 = "d5e742de653954bfae88f0e5f6c8f0a7a5f6c437".to_owned();
+        commit.author_name_email = (author.to_owned(), [author, "@example.com"].concat());
+        // `git log` indents the message and starts it with a blank line
+        commit.msg = ["\n    ", subject, "\n\n    More details"].concat();
+        commit
+    };
+
+    let contributors = Contributor::from_commit_history(
+        vec![
+            commit("alice", "feat(cli): add --dryrun flag"),
+            commit("alice", "fix: crash on empty repos"),
+            commit("alice", "Update README"),
+            commit("alice", "feat!: drop the old report format"),
+            commit("bob", "docs: fix a typo"),
+            commit("bob", "WIP"),
+            commit("carol", "Merge branch 'main'"),
+            commit("carol", "refactor stuff"),
+        ],
+        &Mailmap::default(),
+    );
+
+    let commit_types = |idx: usize| {
+        let mut commit_types = contributors[idx]
+            .commit_type_counts
+            .iter()
+            .flatten()
+            .map(|(commit_type, count)| (commit_type.as_str(), *count))
+            .collect::<Vec<(&str, usize)>>();
+        commit_types.sort();
+        commit_types
+    };
+    assert_eq!(commit_types(0), vec![("feat", 2), ("fix", 1)]);
+    assert_eq!(commit_types(1), vec![("docs", 1)]);
+    // free-form messages only
+    assert_eq!(contributors[2].commit_count, 2);
+    assert!(contributors[2].commit_type_counts.is_none());
+    assert!(serde_json::to_value(&contributors[2])
+        .unwrap()
+        .get("commit_type_counts")
+        .is_none());
+}
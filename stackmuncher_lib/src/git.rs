@@ -19,6 +19,8 @@ pub struct GitBlob {
     pub commit_date_epoch: i64,
     /// Date of the commit the blob belongs to
     pub commit_date_iso: String,
+    /// Size of the blob in bytes if it is known, e.g. from `git ls-tree -l`
+    pub size: Option<u64>,
 }
 
 pub type BlobSHA1 = String;
@@ -27,6 +29,10 @@ pub type BlobSHA1 = String;
 /// E.g. `037498fba1ca5b3662963c848158b7b678adbbf3    .gitignore`.
 pub type ListOfBlobs = HashMap<FilePath, GitBlob>;
 
+/// GIT stores symlinks as blobs with this file mode. The contents of the blob is the path of the target,
+/// which is not code and should not be processed.
+const SYMLINK_MODE: &str = "120000";
+
 /// A a structured representation of `git log` output. E.g.
 /// ```
 /// commit f527864cc944d52887d7cc26e79781ac1b01abc2
@@ -35,11 +41,11 @@ pub type ListOfBlobs = HashMap<FilePath, GitBlob>;
 ///
 ///     Switched from analyzing local files to GIT blobs
 ///
-/// stackmuncher/src/git.rs
-/// stackmuncher/src/lib.rs
-/// stackmuncher/src/processors/mod.rs
-/// stackmuncher/src/report.rs
-/// stmapp/src/main.rs
+/// 52      31      stackmuncher/src/git.rs
+/// 14      2       stackmuncher/src/lib.rs
+/// 8       8       stackmuncher/src/processors/mod.rs
+/// 3       0       stackmuncher/src/report.rs
+/// -       -       stmapp/logo.png
 /// ```
 #[derive(Clone)]
 pub struct GitLogEntry {
@@ -48,7 +54,11 @@ pub struct GitLogEntry {
     pub date: String,
     pub msg: String,
     pub author_name_email: (String, String),
+    /// Name/email pairs from `Co-authored-by:` trailers in the commit message, e.g. on squashed PRs or pair work.
+    pub co_author_name_emails: Vec<(String, String)>,
     pub files: HashSet<String>,
+    /// Lines inserted and deleted per file from `--numstat`. Binary files have no line counts and are only listed in `files`.
+    pub line_changes: HashMap<String, (u64, u64)>,
 }
 
 impl GitLogEntry {
@@ -60,7 +70,9 @@ impl GitLogEntry {
             date: String::new(),
             msg: String::new(),
             author_name_email: (String::new(), String::new()),
+            co_author_name_emails: Vec::new(),
             files: HashSet::new(),
+            line_changes: HashMap::new(),
         }
     }
 
@@ -79,8 +91,73 @@ impl GitLogEntry {
         error!("Invalid SHA1: {}", &self.sha1);
         None
     }
+
+    /// Returns the conventional commit type from the commit subject, e.g. `feat` for `feat(cli): add --dryrun flag`.
+    /// Returns None if the subject does not follow the convention or the type is not one of `CONVENTIONAL_COMMIT_TYPES`.
+    pub fn conventional_commit_type(&self) -> Option<String> {
+        // the message starts with a blank line and is indented, so the subject is the first non-blank line
+        let subject = self.msg.lines().map(|line| line.trim()).find(|line| !line.is_empty())?;
+
+        // the type is everything up to the optional (scope), optional ! and the mandatory :
+        let (prefix, _) = subject.split_once(':')?;
+        let prefix = prefix.trim_end_matches('!');
+        let commit_type = match prefix.split_once('(') {
+            Some((commit_type, scope)) if scope.ends_with(')') => commit_type,
+            Some(_) => return None,
+            None => prefix,
+        };
+        let commit_type = commit_type.to_lowercase();
+
+        if CONVENTIONAL_COMMIT_TYPES.contains(&commit_type.as_str()) {
+            Some(commit_type)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns name/email pairs from `Co-authored-by: Jane Doe <jane@example.com>` trailers in the commit message.
+/// The trailer key is case-insensitive. Trailers with no name or email are skipped.
+pub(crate) fn co_authors_from_msg(msg: &str) -> Vec<(String, String)> {
+    msg.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let (key, value) = line.split_once(':')?;
+            if !key.trim().eq_ignore_ascii_case(CO_AUTHORED_BY_TRAILER) {
+                return None;
+            }
+            let name_email = split_name_email(value.trim());
+            if name_email.0.is_empty() && name_email.1.is_empty() {
+                None
+            } else {
+                Some(name_email)
+            }
+        })
+        .collect()
+}
+
+/// Splits `Lorenzo Baboollie <lorenzo@xamsie.be>` into the name and the email. The name is blank for `<lorenzo@xamsie.be>`.
+/// Returns the whole value as the name with a blank email if there is no `<email>` part.
+pub(crate) fn split_name_email(value: &str) -> (String, String) {
+    if value.ends_with(">") {
+        if let Some(idx) = value.rfind('<') {
+            let (name, email) = value.split_at(idx);
+            let email = email.trim().trim_end_matches(">").trim_start_matches("<").trim();
+            return (name.trim().to_owned(), email.to_owned());
+        };
+    }
+
+    (value.to_owned(), String::new())
 }
 
+/// The key of the commit message trailer for additional authors of the commit.
+pub const CO_AUTHORED_BY_TRAILER: &str = "Co-authored-by";
+
+/// Commit types recognized in conventional commit messages, e.g. `feat: add a new flag` or `fix(parser)!: handle BOM`.
+pub const CONVENTIONAL_COMMIT_TYPES: [&str; 11] = [
+    "feat", "fix", "chore", "docs", "style", "refactor", "perf", "test", "build", "ci", "revert",
+];
+
 /// Executes a git command in the specified dir with a possible Error as a normal outcome.
 /// E.g. some `git config` commands may return an error because there is no such setting, but we don't want to
 /// log it as an error because it is an expected outcome. This function returns an error only if no errors are expected or there is an error message attached.
@@ -144,17 +221,18 @@ pub async fn check_git_version(dir: &Path) -> Result<String, ()> {
     Ok(version)
 }
 
-/// Populates blob's sha1 property at the point of the given commit.
+/// Populates blob's sha1 and size properties at the point of the given commit.
 /// Only one `git ls-tree` call is used to get the data.
-/// * `blobs` param: Must be a ListOfBlobs with commit details populated per file. This function only adds the blob SHA1.
+/// * `blobs` param: Must be a ListOfBlobs with commit details populated per file. This function only adds the blob SHA1
+///   and size.
 /// The commit details can be taken from `git log` or contributor section of the report.
 /// * `commit_sha1` param: either specify a commit SHA1 or None for HEAD.
 ///
 /// The raw git output looks like this:
 /// ```
-/// 100644 blob a28b99eae8417ac31293a332ef1a125b8772032d    Cargo.toml
-/// 100644 blob f288702d2fa16d3cdf0035b15a9fcbc552cd88e7    LICENSE
-/// 100644 blob 9da69050aa4d1f6488a258a221217a4dd9e73b71    assets/file-types/cs.json
+/// 100644 blob a28b99eae8417ac31293a332ef1a125b8772032d     925    Cargo.toml
+/// 100644 blob f288702d2fa16d3cdf0035b15a9fcbc552cd88e7   34523    LICENSE
+/// 100644 blob 9da69050aa4d1f6488a258a221217a4dd9e73b71    1029    assets/file-types/cs.json
 /// ```
 pub(crate) async fn populate_blob_sha1(
     dir: &Path,
@@ -167,9 +245,19 @@ pub(crate) async fn populate_blob_sha1(
         None => "HEAD".into(),
     };
 
-    let all_objects =
-        execute_git_command(vec!["ls-tree".into(), "-r".into(), "--full-tree".into(), commit_sha1.clone()], dir, false)
-            .await?;
+    // -l adds the blob size to check it against the limit without loading the blob
+    let all_objects = execute_git_command(
+        vec![
+            "ls-tree".into(),
+            "-r".into(),
+            "-l".into(),
+            "--full-tree".into(),
+            commit_sha1.clone(),
+        ],
+        dir,
+        false,
+    )
+    .await?;
     let all_objects = String::from_utf8_lossy(&all_objects);
 
     trace!("{:?}", blobs);
@@ -178,17 +266,21 @@ pub(crate) async fn populate_blob_sha1(
         .lines()
         .filter_map(|v| {
             trace! {"get_all_tree_files: {}", v};
-            if &v[7..11] == "blob" {
-                let file_name = v[53..].to_owned();
+            // split into `mode type sha1 size` and the file name
+            let (meta, file_name) = v.split_once('\t')?;
+            let meta = meta.split_whitespace().collect::<Vec<&str>>();
+            if meta.len() == 4 && meta[1] == "blob" && meta[0] != SYMLINK_MODE {
+                let file_name = file_name.to_owned();
                 // cloning everything here seems to be inefficient
                 if let Some(blob) = blobs.get(&file_name) {
                     Some((
                         file_name,
                         GitBlob {
-                            sha1: v[12..52].to_owned(),
+                            sha1: meta[2].to_owned(),
                             commit_sha1: blob.commit_sha1.clone(),
                             commit_date_epoch: blob.commit_date_epoch.clone(),
                             commit_date_iso: blob.commit_date_iso.clone(),
+                            size: meta[3].parse::<u64>().ok(),
                         },
                     ))
                 } else {
@@ -235,20 +327,34 @@ pub(crate) async fn get_all_tree_files(
         .filter_map(|v| {
             trace! {"get_all_tree_files: {}", v};
             if &v[7..11] == "blob" {
-                Some(v[53..].to_owned())
+                Some((v[..6].to_owned(), v[53..].to_owned()))
             } else {
                 None
             }
         })
-        .collect::<HashSet<String>>();
+        .collect::<Vec<(String, String)>>();
     let tree_all = files.len();
 
+    // remove symlinks
+    let files = files
+        .into_iter()
+        .filter_map(|(mode, file_path)| {
+            if mode == SYMLINK_MODE {
+                trace!("Ignored symlink {}", file_path);
+                None
+            } else {
+                Some(file_path)
+            }
+        })
+        .collect::<HashSet<String>>();
+    let symlink_count = tree_all - files.len();
+
     // remove encoded files
     let files = files
         .into_iter()
         .filter_map(|file_path| octal_to_unicode_string(file_path))
         .collect::<HashSet<String>>();
-    let encoded_count = tree_all - files.len();
+    let encoded_count = tree_all - symlink_count - files.len();
 
     // remove ignored files
     let files = files
@@ -263,19 +369,227 @@ pub(crate) async fn get_all_tree_files(
         .collect::<HashSet<String>>();
 
     info!(
-        "Objects in the GIT tree: {}, encoded paths: {}, ignored: {}, processing: {}",
+        "Objects in the GIT tree: {}, symlinks: {}, encoded paths: {}, ignored: {}, processing: {}",
         tree_all,
+        symlink_count,
         encoded_count,
-        tree_all - files.len(),
+        tree_all - symlink_count - encoded_count - files.len(),
         files.len(),
     );
 
     Ok(files)
 }
 
+/// Where project files such as `.gitignore`, `.stmignore` or `.mailmap` are read from. It should match where the
+/// processed files come from, e.g. the files committed at `HEAD` for the files in the `HEAD` tree.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RootFilesSource<'a> {
+    /// Files on disk, e.g. with `Config.include_working_tree`
+    WorkingTree,
+    /// Files staged in the git index
+    Index,
+    /// Files committed at a revision, e.g. `HEAD`
+    Commit(&'a str),
+}
+
+/// Returns the contents of `file_names` in the root of the project taken from `source` with the file name as the key.
+/// Files that do not exist are not included, e.g. an optional `.stmignore`. Read errors are logged and the file is skipped.
+pub(crate) async fn get_root_file_contents(
+    dir: &Path,
+    source: RootFilesSource<'_>,
+    file_names: &[&str],
+) -> HashMap<String, Vec<u8>> {
+    let mut contents: HashMap<String, Vec<u8>> = HashMap::with_capacity(file_names.len());
+
+    // committed and staged files may differ from the ones on disk
+    let blob_sha1s = match source {
+        RootFilesSource::WorkingTree => {
+            for file_name in file_names {
+                let file_path = dir.join(file_name);
+                if !file_path.is_file() {
+                    continue;
+                }
+                match std::fs::read(&file_path) {
+                    Ok(v) => {
+                        contents.insert(file_name.to_string(), v);
+                    }
+                    Err(e) => warn!("Cannot read {} due to {}", file_name, e),
+                }
+            }
+            return contents;
+        }
+        RootFilesSource::Index => get_root_blob_sha1s(dir, None, file_names).await,
+        RootFilesSource::Commit(rev) => get_root_blob_sha1s(dir, Some(rev), file_names).await,
+    }
+    .unwrap_or_default();
+
+    for (file_name, blob_sha1) in blob_sha1s {
+        match get_blob_contents(dir, &blob_sha1).await {
+            Ok(v) => {
+                contents.insert(file_name, v);
+            }
+            Err(_) => warn!("Cannot read {} from blob {}", file_name, blob_sha1),
+        }
+    }
+
+    contents
+}
+
+/// Returns blob SHA1s of `file_names` in the root of the tree at `rev`, e.g. `HEAD`, or in the git index if `rev` is None.
+/// Files that do not exist are not included, e.g. an optional `.stmignore`.
+async fn get_root_blob_sha1s(
+    dir: &Path,
+    rev: Option<&str>,
+    file_names: &[&str],
+) -> Result<HashMap<String, BlobSHA1>, ()> {
+    let mut git_args: Vec<String> = match rev {
+        Some(rev) => vec!["ls-tree".into(), "--full-tree".into(), rev.into()],
+        None => vec!["ls-files".into(), "--stage".into()],
+    };
+    git_args.push("--".into());
+    git_args.extend(file_names.iter().map(|file_name| file_name.to_string()));
+    let all_objects = execute_git_command(git_args, dir, false).await?;
+
+    // `mode blob sha1` from ls-tree or `mode sha1 stage` from ls-files, followed by a tab and the file name
+    Ok(String::from_utf8_lossy(&all_objects)
+        .lines()
+        .filter_map(|v| {
+            let (meta, file_path) = v.split_once('\t')?;
+            let blob_sha1 = meta.split(' ').find(|v| v.len() == 40)?;
+            Some((file_path.to_owned(), blob_sha1.to_owned()))
+        })
+        .collect())
+}
+
+/// Get the list of files staged in the git index with their blob SHA1s. Commit details are left blank because
+/// the staged files are not committed yet. Files with merge conflicts are skipped.
+/// The raw git output looks like this:
+/// ```
+/// 100644 a28b99eae8417ac31293a332ef1a125b8772032d 0    Cargo.toml
+/// 100644 f288702d2fa16d3cdf0035b15a9fcbc552cd88e7 0    LICENSE
+/// ```
+pub(crate) async fn get_index_blobs(dir: &Path, ignore_paths: &Vec<Regex>) -> Result<ListOfBlobs, ()> {
+    let all_objects = execute_git_command(vec!["ls-files".into(), "--stage".into()], dir, false).await?;
+    let all_objects = String::from_utf8_lossy(&all_objects);
+
+    let blobs = all_objects
+        .lines()
+        .filter_map(|v| {
+            trace! {"get_index_blobs: {}", v};
+            // split into `mode sha1 stage` and the file name
+            let (meta, file_path) = v.split_once('\t')?;
+            let meta = meta.split(' ').collect::<Vec<&str>>();
+            // stages other than 0 are for unresolved merge conflicts
+            if meta.len() != 3 || meta[1].len() != 40 || meta[2] != "0" {
+                return None;
+            }
+            if meta[0] == SYMLINK_MODE {
+                trace!("Ignored symlink {}", file_path);
+                return None;
+            }
+            let file_path = octal_to_unicode_string(file_path.to_owned())?;
+            if is_in_ignore_list(ignore_paths, &file_path) {
+                return None;
+            }
+
+            Some((
+                file_path,
+                GitBlob {
+                    sha1: meta[1].to_owned(),
+                    commit_sha1: String::new(),
+                    commit_date_epoch: 0,
+                    commit_date_iso: String::new(),
+                    size: None,
+                },
+            ))
+        })
+        .collect::<ListOfBlobs>();
+
+    info!("Staged files in the GIT index: {}", blobs.len());
+
+    Ok(blobs)
+}
+
+/// Get the list of files present in the working tree: tracked files plus untracked files that are not excluded by
+/// `.gitignore` at any level of the directory hierarchy, `.git/info/exclude` or the user's global excludes file.
+/// Git applies the ignore rules itself, so the result is consistent with what `git status` considers a part
+/// of the project. Tracked files that were deleted from the working tree are not included.
+pub async fn get_working_tree_files(dir: &Path, ignore_paths: &Vec<Regex>) -> Result<HashSet<String>, ()> {
+    let all_files = execute_git_command(
+        vec![
+            "ls-files".into(),
+            "--cached".into(),
+            "--others".into(),
+            "--exclude-standard".into(),
+        ],
+        dir,
+        false,
+    )
+    .await?;
+    let all_files = String::from_utf8_lossy(&all_files);
+
+    let files = all_files
+        .lines()
+        .filter_map(|v| {
+            trace! {"get_working_tree_files: {}", v};
+            let file_path = octal_to_unicode_string(v.to_owned())?;
+            // --cached lists files deleted from the working tree until the deletion is staged
+            if is_in_ignore_list(ignore_paths, &file_path) || !dir.join(&file_path).is_file() {
+                return None;
+            }
+            // is_file() follows symlinks
+            if dir.join(&file_path).symlink_metadata().ok()?.file_type().is_symlink() {
+                trace!("Ignored symlink {}", file_path);
+                return None;
+            }
+            Some(file_path)
+        })
+        .collect::<HashSet<String>>();
+
+    info!("Files in the working tree: {}", files.len());
+
+    Ok(files)
+}
+
+/// Splits a line of `git log --numstat` output into the file path and the number of inserted and deleted lines,
+/// e.g. `12\t3\tsrc/main.rs`. Binary files have `-` instead of the numbers and get None. Renamed files are returned
+/// under the new name, e.g. `src/{old.rs => new.rs}` as `src/new.rs`. A line in any other format is returned as the path.
+fn parse_numstat_line(line: &str) -> (String, Option<(u64, u64)>) {
+    let mut parts = line.splitn(3, '\t');
+    let (inserted, deleted, file_path) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(inserted), Some(deleted), Some(file_path)) => (inserted, deleted, file_path),
+        _ => return (line.to_owned(), None),
+    };
+
+    let line_changes = match (inserted.parse::<u64>(), deleted.parse::<u64>()) {
+        (Ok(inserted), Ok(deleted)) => Some((inserted, deleted)),
+        _ => None,
+    };
+
+    (renamed_file_path(file_path), line_changes)
+}
+
+/// Returns the new name of a file from a rename in numstat format, e.g. `src/{old.rs => new.rs}` or `old.rs => new.rs`.
+/// Any other path is returned as-is.
+fn renamed_file_path(file_path: &str) -> String {
+    if let (Some(start), Some(end)) = (file_path.find('{'), file_path.rfind('}')) {
+        if let Some((_, new)) = file_path[start + 1..end].split_once(" => ") {
+            // `a/{b => }/c` moves the file one level up and leaves a double slash
+            return [&file_path[..start], new, &file_path[end + 1..]]
+                .concat()
+                .replace("//", "/");
+        }
+    }
+
+    match file_path.split_once(" => ") {
+        Some((_, new)) => new.to_owned(),
+        None => file_path.to_owned(),
+    }
+}
+
 /// Checks if the file name was encoded by GIT using octal sequences for non-ASCII glyphs and attempt a conversion to a normal UTF-8 string.
 /// E.g. `"LINQ\343\202\265\343\203\263\343\203\227\343\203\253.cs/.vs/LINQ\343\202\265\343\203\263\343\203\227\343\203\253.cs/v16/.suo"`
-/// Returns None if the string cannot be converted.
+/// Returns None if the string cannot be converted, e.g. the name is not valid UTF-8.
 fn octal_to_unicode_string(file_path: String) -> Option<String> {
     // return as-is if not encoded
     if !file_path.starts_with("\"") {
@@ -287,17 +601,66 @@ fn octal_to_unicode_string(file_path: String) -> Option<String> {
         return None;
     }
 
-    // the decoding should take place here
-    // TODO: see https://github.com/stackmuncher/stm_app/issues/42
-
     debug!("Encoded file name in git output: {}", file_path);
 
-    None
+    // the name is a C-style string with octal sequences for every non-ASCII byte, e.g. `\343`
+    let mut bytes: Vec<u8> = Vec::with_capacity(file_path.len());
+    let mut encoded = file_path[1..file_path.len() - 1].bytes();
+    while let Some(byte) = encoded.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        let byte = match encoded.next() {
+            Some(digit @ b'0'..=b'3') => {
+                // always 3 digits for a single byte
+                let mut value = digit - b'0';
+                for _ in 0..2 {
+                    match encoded.next() {
+                        Some(digit @ b'0'..=b'7') => value = value * 8 + (digit - b'0'),
+                        _ => {
+                            error!("Invalid octal sequence in file name: {}", file_path);
+                            return None;
+                        }
+                    }
+                }
+                value
+            }
+            Some(b'a') => 7,
+            Some(b'b') => 8,
+            Some(b't') => b'\t',
+            Some(b'n') => b'\n',
+            Some(b'v') => 11,
+            Some(b'f') => 12,
+            Some(b'r') => b'\r',
+            Some(byte @ b'"') | Some(byte @ b'\\') => byte,
+            _ => {
+                error!("Invalid escape sequence in file name: {}", file_path);
+                return None;
+            }
+        };
+        bytes.push(byte);
+    }
+
+    match String::from_utf8(bytes) {
+        Ok(v) => Some(v),
+        Err(_) => {
+            error!("Non-UTF-8 file name in git output: {}", file_path);
+            None
+        }
+    }
 }
 
 /// Returns TRUE if the file matches any of the ignore regex rules from `ignore_paths` module.
 #[inline]
 fn is_in_ignore_list(ignore_paths: &Vec<Regex>, file_path: &str) -> bool {
+    // package manifests are only ignored for their location, not their extension, e.g. `requirements.txt`
+    let file_path = if crate::manifest::is_manifest(file_path) {
+        file_path.rfind('/').map(|idx| &file_path[..=idx]).unwrap_or_default()
+    } else {
+        file_path
+    };
+
     // check if the path is in the ignore list
     for ignore_regex in ignore_paths {
         if ignore_regex.is_match(file_path) {
@@ -329,7 +692,7 @@ pub async fn get_log(
     let mut git_args = vec![
         "log".into(),
         "--no-decorate".into(),
-        "--name-only".into(),
+        "--numstat".into(),
         "--encoding=utf-8".into(),
     ];
     if let Some(author) = contributor_git_identity {
@@ -386,19 +749,14 @@ pub async fn get_log(
             }
             trace!("Author: {}", author);
             // try to split the author details into name and email
-            if author.ends_with(">") {
-                if let Some(idx) = author.rfind(" <") {
-                    let (author_n, author_e) = author.split_at(idx);
-                    let author_n = author_n.trim();
-                    let author_e = author_e.trim().trim_end_matches(">").trim_start_matches("<");
-                    debug!("Author split: {}|{}", author_n, author_e);
-                    current_log_entry.author_name_email = (author_n.to_owned(), author_e.to_owned());
-                    continue;
-                };
+            let (author_n, author_e) = split_name_email(author);
+            if author_e.is_empty() {
+                // name/email split failed - the entire line is used as the name
+                warn!("Split failed on {}", line);
+            } else {
+                debug!("Author split: {}|{}", author_n, author_e);
             }
-            // name/email split failed - add the entire line
-            current_log_entry.author_name_email = (author.to_owned(), String::new());
-            warn!("Split failed on {}", line);
+            current_log_entry.author_name_email = (author_n, author_e);
         } else if line.starts_with("Date: ") {
             // Date:   Tue Dec 22 17:43:07 2020 +0000
             if line.len() < 9 {
@@ -430,13 +788,27 @@ pub async fn get_log(
                 continue;
             }
             current_log_entry.msg = [current_log_entry.msg, line[3..].to_owned()].join("\n");
+            // trailers are at the end of the message body, but can be picked up from any line
+            current_log_entry
+                .co_author_name_emails
+                .extend(co_authors_from_msg(line));
         } else {
             // the only remaining type of data should be the list of files
-            // they are not tagged or indented - the entire line is the file name with the relative path
-            // file names are displayed only with --name-only option
-            if !is_in_ignore_list(ignore_paths, line) {
+            // they are not tagged or indented - the line is the number of inserted and deleted lines followed by
+            // the file name with the relative path, e.g. `12\t3\tsrc/main.rs`, as displayed with --numstat option
+            let (file_path, line_changes) = parse_numstat_line(line);
+            // non-ASCII names are quoted and encoded by git, e.g. `"donn\303\251es.rs"`, and are decoded once here
+            // so that `files` and `line_changes` have the same keys
+            let file_path = match octal_to_unicode_string(file_path) {
+                Some(v) => v,
+                None => continue,
+            };
+            if !is_in_ignore_list(ignore_paths, &file_path) {
                 trace!("Added as a file");
-                current_log_entry.files.insert(line.into());
+                if let Some(line_changes) = line_changes {
+                    current_log_entry.line_changes.insert(file_path.clone(), line_changes);
+                }
+                current_log_entry.files.insert(file_path);
             } else {
                 trace!("Ignored");
             }
@@ -448,32 +820,24 @@ pub async fn get_log(
 
     debug!("Found {} commits of interest", log_entries.len());
 
-    // remove encoded files
+    // remove commits with no files left after ignoring and decoding
     let log_entries = log_entries
         .into_iter()
-        .filter_map(|mut log_entry| {
-            log_entry.files = log_entry
-                .files
-                .into_iter()
-                .filter_map(|file_path| octal_to_unicode_string(file_path))
-                .collect::<HashSet<String>>();
-            if log_entry.files.is_empty() {
-                None
-            } else {
-                Some(log_entry)
-            }
-        })
+        .filter(|log_entry| !log_entry.files.is_empty())
         .collect::<Vec<GitLogEntry>>();
 
     Ok(log_entries)
 }
 
-/// Extracts all contributor commits from the full log. `git_identities` should be lowercase.
+/// Extracts all contributor commits from the full log, including commits they co-authored. `git_identities` should be lowercase.
 pub fn get_contributor_commits_from_log(git_log: &Vec<GitLogEntry>, git_identities: &Vec<String>) -> Vec<GitLogEntry> {
     git_log
         .iter()
         .filter_map(|entry| {
-            if git_identities.contains(&entry.author_name_email.1.to_lowercase()) {
+            if std::iter::once(&entry.author_name_email)
+                .chain(entry.co_author_name_emails.iter())
+                .any(|(_, email)| git_identities.contains(&email.to_lowercase()))
+            {
                 Some(entry.clone())
             } else {
                 None
@@ -549,6 +913,7 @@ pub(crate) fn log_entries_to_list_of_blobs(git_log: &Vec<GitLogEntry>) -> ListOf
                     commit_sha1: log_entry.sha1.clone(),
                     commit_date_epoch: log_entry.date_epoch,
                     commit_date_iso: log_entry.date.clone(),
+                    size: None,
                 };
                 blobs.insert(file.clone(), blob);
             }
@@ -557,3 +922,128 @@ pub(crate) fn log_entries_to_list_of_blobs(git_log: &Vec<GitLogEntry>) -> ListOf
     debug!("list_of_files_with_commits_from_git_log collected {} files from git log", blobs.len());
     blobs
 }
+
+#[test]
+fn test_parse_numstat_block() {
+    let numstat = "12\t3\tsrc/main.rs\n-\t-\tassets/logo.png\n";
+    let mut lines = numstat.lines().map(parse_numstat_line);
+
+    assert_eq!(lines.next(), Some(("src/main.rs".to_owned(), Some((12, 3)))));
+    // binary files have no line counts
+    assert_eq!(lines.next(), Some(("assets/logo.png".to_owned(), None)));
+    assert_eq!(lines.next(), None);
+
+    assert_eq!(
+        parse_numstat_line("0\t0\tsrc/{old.rs => new.rs}"),
+        ("src/new.rs".to_owned(), Some((0, 0)))
+    );
+    assert_eq!(renamed_file_path("src/{lib => }/mod.rs"), "src/mod.rs");
+    assert_eq!(renamed_file_path("old.rs => new.rs"), "new.rs");
+}
+
+#[test]
+fn test_conventional_commit_type() {
+    let commit_type = |msg: &str| {
+        let mut log_entry = GitLogEntry::new();
+        log_entry.msg = msg.to_owned();
+        log_entry.conventional_commit_type()
+    };
+
+    // git log indents the message and starts it with a blank line
+    assert_eq!(commit_type("\n    feat: add --dryrun flag\n\n    body"), Some("feat".to_owned()));
+    assert_eq!(commit_type("fix(parser)!: handle BOM"), Some("fix".to_owned()));
+    assert_eq!(commit_type("Docs: update the readme"), Some("docs".to_owned()));
+    assert_eq!(commit_type("Merge branch 'main'"), None);
+    assert_eq!(commit_type("wip: not a known type"), None);
+    assert_eq!(commit_type("fix(parser: unbalanced scope"), None);
+    // only the subject is looked at
+    assert_eq!(commit_type("Update deps\n\nfix: something"), None);
+    assert_eq!(commit_type(""), None);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_symlinks_are_not_processed() {
+    let repo = crate::utils::TestRepo::new("symlinks");
+    let project_dir = repo.dir.clone();
+
+    repo.write("main.rs", "fn main() {\n    let x = 1;\n}\n");
+    // the blob of a symlink contains the target path, which would be munched as a line of code
+    std::os::unix::fs::symlink("main.rs", project_dir.join("link.rs")).unwrap();
+    repo.commit_all("init");
+    assert!(repo.git(&["ls-tree", "-r", "HEAD"]).contains("120000 blob"));
+
+    let tree_files = get_all_tree_files(&project_dir, None, &Vec::new()).await.unwrap();
+    assert_eq!(tree_files, vec!["main.rs".to_owned()].into_iter().collect::<HashSet<String>>());
+    assert!(!get_index_blobs(&project_dir, &Vec::new())
+        .await
+        .unwrap()
+        .contains_key("link.rs"));
+    assert!(!get_working_tree_files(&project_dir, &Vec::new())
+        .await
+        .unwrap()
+        .contains("link.rs"));
+
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    let report = crate::report::Report::process_project(&mut code_rules, &project_dir, &None, None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(report
+        .per_file_tech
+        .iter()
+        .all(|tech| tech.file_name.as_deref() != Some("link.rs")));
+    // only the lines of main.rs without the closing bracket
+    assert_eq!(report.tech().iter().map(|tech| tech.code_lines).sum::<u64>(), 2);
+}
+
+#[tokio::test]
+async fn test_get_working_tree_files() {
+    let repo = crate::utils::TestRepo::new("working_tree_files");
+    repo.write(".gitignore", "target/\n*.log\n");
+    repo.write("docs/.gitignore", "drafts/\n");
+    repo.write("main.rs", "fn main() {}\n");
+    repo.write("deleted.rs", "fn deleted() {}\n");
+    repo.commit_all("init");
+
+    // untracked files are listed unless git ignores them at any level
+    repo.write("new.rs", "fn new() {}\n");
+    repo.write("build.log", "done\n");
+    repo.write("target/debug/app.d", "app\n");
+    repo.write("docs/index.md", "# Docs\n");
+    repo.write("docs/drafts/wip.md", "# WIP\n");
+    std::fs::remove_file(repo.dir.join("deleted.rs")).unwrap();
+
+    let files = get_working_tree_files(&repo.dir, &Vec::new()).await.unwrap();
+    let mut files = files.into_iter().collect::<Vec<String>>();
+    files.sort();
+    assert_eq!(files, vec![".gitignore", "docs/.gitignore", "docs/index.md", "main.rs", "new.rs"]);
+}
+
+#[tokio::test]
+async fn test_get_log_with_non_ascii_file_names() {
+    let repo = crate::utils::TestRepo::new("non_ascii_file_names");
+    repo.write("données.rs", "fn main() {}\n");
+    repo.write("ascii.rs", "fn ascii() {}\n");
+    repo.commit_all("init");
+
+    let git_log = get_log(&repo.dir, None, &Vec::new()).await.unwrap();
+    assert_eq!(git_log.len(), 1);
+    assert!(git_log[0].files.contains("données.rs"));
+    // line counts are keyed by the same decoded name
+    assert_eq!(git_log[0].line_changes.get("données.rs"), Some(&(1, 0)));
+
+    let tree_files = get_all_tree_files(&repo.dir, None, &Vec::new()).await.unwrap();
+    assert!(tree_files.contains("données.rs"));
+
+    assert_eq!(
+        octal_to_unicode_string(r#""LINQ\343\202\265.cs""#.to_owned()),
+        Some("LINQサ.cs".to_owned())
+    );
+    assert_eq!(
+        octal_to_unicode_string(r#""tab\tand \"quotes\".rs""#.to_owned()),
+        Some("tab\tand \"quotes\".rs".to_owned())
+    );
+    // not valid UTF-8
+    assert_eq!(octal_to_unicode_string(r#""\377.rs""#.to_owned()), None);
+}
@@ -0,0 +1,272 @@
+use crate::git::{get_root_file_contents, RootFilesSource};
+use regex::Regex;
+use std::path::Path;
+use tracing::{debug, error};
+
+/// A list of gitignore-style rules for excluding files from processing, e.g. `node_modules/`, `*.min.js`
+/// or `!keep.min.js`. The last matching rule wins, same as in `.gitignore`.
+#[derive(Debug, Default)]
+pub(crate) struct IgnoreList {
+    rules: Vec<IgnoreRule>,
+}
+
+/// A single compiled line of an ignore file.
+#[derive(Debug)]
+struct IgnoreRule {
+    /// Matches the full path relative to the project root
+    regex: Regex,
+    /// `!pattern` re-includes paths excluded by the rules before it
+    negated: bool,
+    /// `pattern/` matches directories only
+    dir_only: bool,
+}
+
+impl IgnoreList {
+    /// The name of an optional ignore file with the same syntax as `.gitignore` for paths that should be
+    /// committed, but not munched.
+    pub const STMIGNORE_FILE_NAME: &'static str = ".stmignore";
+    pub const GITIGNORE_FILE_NAME: &'static str = ".gitignore";
+
+    /// Returns a list of rules from `.gitignore` and `.stmignore` in the root of the project at `source` followed by
+    /// `extra_patterns`. Missing ignore files are not an error.
+    pub(crate) async fn for_project(
+        project_dir: &Path,
+        source: RootFilesSource<'_>,
+        extra_patterns: &[String],
+    ) -> Self {
+        let mut ignore_list = IgnoreList::default();
+        let ignore_file_names = [Self::GITIGNORE_FILE_NAME, Self::STMIGNORE_FILE_NAME];
+        let ignore_files = get_root_file_contents(project_dir, source, &ignore_file_names).await;
+
+        // .stmignore rules go after .gitignore
+        for ignore_file_name in ignore_file_names.iter() {
+            if let Some(contents) = ignore_files.get(*ignore_file_name) {
+                ignore_list.add_patterns(String::from_utf8_lossy(contents).lines());
+            }
+        }
+
+        // hard-coded excludes go last to override any negations in the repo files
+        ignore_list.add_patterns(extra_patterns.iter().map(|pattern| pattern.as_str()));
+        debug!("Ignore rules: {}", ignore_list.rules.len());
+
+        ignore_list
+    }
+
+    /// Adds the lines of an ignore file to the list. Blank lines and comments are skipped.
+    /// Invalid patterns are logged and skipped.
+    pub(crate) fn add_patterns<'a, I>(&mut self, lines: I)
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        for line in lines {
+            if let Some(rule) = IgnoreRule::new(line) {
+                self.rules.push(rule);
+            }
+        }
+    }
+
+    /// Returns true if the file at `file_path` relative to the project root should be ignored.
+    /// A file inside an ignored directory cannot be re-included by a negated rule, same as in git.
+    pub(crate) fn is_ignored(&self, file_path: &str) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+
+        // check every parent directory first, e.g. `a` and `a/b` for `a/b/c.txt`
+        for (idx, _) in file_path.match_indices('/') {
+            if self.matches(&file_path[..idx], true) {
+                return true;
+            }
+        }
+
+        self.matches(file_path, false)
+    }
+
+    /// Returns true if the last rule matching the path is not negated.
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.regex.is_match(path))
+            .map(|rule| !rule.negated)
+            .unwrap_or(false)
+    }
+}
+
+impl IgnoreRule {
+    /// Compiles a single line of an ignore file. Returns None for blank lines, comments and invalid patterns.
+    fn new(line: &str) -> Option<Self> {
+        // trailing spaces are ignored unless escaped with a backslash
+        let mut pattern = line.trim_end();
+        if pattern.ends_with('\\') && line.len() > pattern.len() {
+            pattern = &line[..pattern.len() + 1];
+        }
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return None;
+        }
+
+        // a leading `\` escapes `!` or `#` that would make it a negation or a comment
+        let negated = pattern.starts_with('!');
+        if negated || pattern.starts_with("\\!") || pattern.starts_with("\\#") {
+            pattern = &pattern[1..];
+        }
+
+        let dir_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+
+        match Regex::new(&pattern_to_regex(pattern)) {
+            Ok(regex) => Some(IgnoreRule {
+                regex,
+                negated,
+                dir_only,
+            }),
+            Err(e) => {
+                error!("Invalid ignore pattern {} due to {}", line, e);
+                None
+            }
+        }
+    }
+}
+
+/// Converts a gitignore pattern with no leading `!` or trailing `/` into a regex matching the full path.
+/// A pattern with no `/` matches the name at any level, e.g. `target` matches `target` and `a/target`.
+/// A pattern with a `/` is relative to the project root, e.g. `/target` or `docs/*.md`.
+fn pattern_to_regex(pattern: &str) -> String {
+    if pattern.contains('/') {
+        glob_to_regex(pattern.trim_start_matches('/'))
+    } else {
+        glob_to_regex(&["**/", pattern].concat())
+    }
+}
+
+/// Converts a path glob into an anchored regex string, e.g. `config/*.txt` -> `^config/[^/]*\.txt$`.
+/// * `**` matches any number of path segments
+/// * `*` matches anything within a single path segment
+/// * `?` matches a single character other than `/`
+/// * `[abc]` or `[!0-9]` matches a single character from the class or not from it
+/// * `\` escapes the next character, e.g. `\*` matches `*`
+pub(crate) fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => {
+                if chars.peek() == Some(&'*') {
+                    chars.next();
+                    // `**/` also matches zero directories, e.g. `**/*.txt` matches `a.txt`
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        regex.push_str("(?:.*/)?");
+                    } else {
+                        regex.push_str(".*");
+                    }
+                } else {
+                    regex.push_str("[^/]*");
+                }
+            }
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                // a character class, e.g. `[abc]` or `[!0-9]`, is copied as is if it is closed
+                let class = chars.clone().take_while(|c| *c != ']').collect::<String>();
+                if chars.clone().nth(class.chars().count()) == Some(']') && !class.is_empty() {
+                    for _ in 0..=class.chars().count() {
+                        chars.next();
+                    }
+                    let class = match class.strip_prefix('!') {
+                        Some(v) => ["^", v].concat(),
+                        None => class,
+                    };
+                    regex.push('[');
+                    regex.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                    regex.push(']');
+                } else {
+                    regex.push_str("\\[");
+                }
+            }
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    regex.push_str(&regex::escape(&escaped.to_string()));
+                }
+            }
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+#[test]
+fn test_nested_node_modules_are_ignored() {
+    let mut ignore_list = IgnoreList::default();
+    ignore_list.add_patterns(["# dependencies", "node_modules/", "/target", ""].iter().cloned());
+
+    assert!(ignore_list.is_ignored("node_modules/lodash/index.js"));
+    assert!(ignore_list.is_ignored("web/app/node_modules/react/index.js"));
+    assert!(ignore_list.is_ignored("target/debug/main.rs"));
+
+    // `node_modules/` is for directories only and `/target` is anchored to the root
+    assert!(!ignore_list.is_ignored("docs/node_modules"));
+    assert!(!ignore_list.is_ignored("src/target/main.rs"));
+    assert!(!ignore_list.is_ignored("web/app/index.js"));
+}
+
+#[test]
+fn test_negated_pattern_re_includes_files() {
+    let mut ignore_list = IgnoreList::default();
+    ignore_list.add_patterns(
+        ["*.min.js", "!vendor.min.js", "build/", "!build/keep.js"]
+            .iter()
+            .cloned(),
+    );
+
+    assert!(ignore_list.is_ignored("static/app.min.js"));
+    assert!(!ignore_list.is_ignored("static/vendor.min.js"));
+    assert!(!ignore_list.is_ignored("static/app.js"));
+
+    // a file inside an ignored directory stays ignored, same as in git
+    assert!(ignore_list.is_ignored("build/keep.js"));
+}
+
+#[test]
+fn test_pattern_to_regex() {
+    let regex = Regex::new(&pattern_to_regex("docs/**/*.md")).unwrap();
+    assert!(regex.is_match("docs/a.md"));
+    assert!(regex.is_match("docs/a/b/c.md"));
+    assert!(!regex.is_match("src/docs/a.md"));
+
+    let regex = Regex::new(&pattern_to_regex("*.[oa]")).unwrap();
+    assert!(regex.is_match("lib/x.o"));
+    assert!(regex.is_match("x.a"));
+    assert!(!regex.is_match("x.c"));
+
+    let regex = Regex::new(&pattern_to_regex("log[!0-9]")).unwrap();
+    assert!(regex.is_match("logs"));
+    assert!(!regex.is_match("log1"));
+}
+
+#[tokio::test]
+async fn test_ignore_files_from_the_commit() {
+    let repo = crate::utils::TestRepo::new("ignore_files");
+    repo.write(".stmignore", "docs/\n");
+    repo.write("docs/index.md", "# Docs\n");
+    repo.commit_all("Add docs");
+    // the changes on disk are not a part of the commit
+    repo.write(".stmignore", "build/\n");
+
+    let ignore_list = IgnoreList::for_project(&repo.dir, RootFilesSource::Commit("HEAD"), &[]).await;
+    assert!(ignore_list.is_ignored("docs/index.md"));
+    assert!(!ignore_list.is_ignored("build/main.js"));
+
+    let ignore_list = IgnoreList::for_project(&repo.dir, RootFilesSource::WorkingTree, &[]).await;
+    assert!(!ignore_list.is_ignored("docs/index.md"));
+    assert!(ignore_list.is_ignored("build/main.js"));
+
+    repo.git(&["add", ".stmignore"]);
+    let ignore_list = IgnoreList::for_project(&repo.dir, RootFilesSource::Index, &[]).await;
+    assert!(ignore_list.is_ignored("build/main.js"));
+}
@@ -1,22 +1,35 @@
 use chrono::TimeZone;
 use contributor::Contributor;
 use git::{log_entries_to_list_of_blobs, GitBlob, GitLogEntry, ListOfBlobs};
+use mailmap::Mailmap;
+use muncher::Muncher;
 use report::Report;
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::sync::Arc;
 use tracing::{debug, info, trace, warn};
 
+pub mod blob_cache;
 pub mod code_rules;
 pub mod config;
 pub mod contributor;
 pub mod file_type;
 pub mod git;
+mod ignore;
 mod ignore_paths;
+mod mailmap;
+pub mod manifest;
+mod markdown;
 pub mod muncher;
+pub mod muncher_cache;
+pub mod notebook;
 pub mod processors;
 pub mod report;
 pub mod utils;
 
+/// A callback for reporting progress of long processing runs as `(processed, total)` number of files.
+pub type ProgressCallback = dyn Fn(usize, usize) + Send + Sync;
+
 impl Report {
     /// Processes the entire repo with or without a previous report. If the report is present and the munchers
     /// have not changed the relevant sections are copied from the old report. Use this function when:
@@ -32,6 +45,18 @@ impl Report {
         project_dir: &Path,
         old_report: &Option<report::Report>,
         git_log: Option<Vec<GitLogEntry>>,
+    ) -> Result<Option<report::Report>, ()> {
+        Report::process_project_with_progress(code_rules, project_dir, old_report, git_log, None).await
+    }
+
+    /// The same as `process_project`, but calls `progress` after every file that had to be munched with the number of
+    /// files processed so far and the total number of files to munch. Files copied from `old_report` are not counted.
+    pub async fn process_project_with_progress(
+        code_rules: &mut code_rules::CodeRules,
+        project_dir: &Path,
+        old_report: &Option<report::Report>,
+        git_log: Option<Vec<GitLogEntry>>,
+        progress: Option<&ProgressCallback>,
     ) -> Result<Option<report::Report>, ()> {
         let report = report::Report::new();
 
@@ -41,8 +66,22 @@ impl Report {
             None => git::get_log(project_dir, None, &code_rules.ignore_paths).await?,
         };
 
-        // get the list of files in the tree at HEAD
-        let all_head_files = git::get_all_tree_files(project_dir, None, &code_rules.ignore_paths).await?;
+        // get the list of files in the tree at HEAD without the files excluded by .gitignore, .stmignore and config
+        // or the files on disk if uncommitted changes should be included
+        let (mut all_head_files, ignore_files_source) = if code_rules.include_working_tree {
+            (
+                git::get_working_tree_files(project_dir, &code_rules.ignore_paths).await?,
+                git::RootFilesSource::WorkingTree,
+            )
+        } else {
+            (
+                git::get_all_tree_files(project_dir, None, &code_rules.ignore_paths).await?,
+                git::RootFilesSource::Commit("HEAD"),
+            )
+        };
+        let ignore_list =
+            ignore::IgnoreList::for_project(project_dir, ignore_files_source, &code_rules.ignore_patterns).await;
+        all_head_files.retain(|file_name| !ignore_list.is_ignored(file_name));
         if all_head_files.len() as u64 > Report::MAX_FILES_PER_REPO {
             warn!("Repo ignored. Too many files: {}", all_head_files.len());
             return Err(());
@@ -67,8 +106,25 @@ impl Report {
             })
             .collect::<ListOfBlobs>();
 
+        // files that were never committed have no blobs and are read from disk with blank commit details
+        let untracked_files = all_head_files
+            .iter()
+            .filter(|file_name| !all_project_blobs.contains_key(*file_name))
+            .cloned()
+            .collect::<Vec<String>>();
+
+        // the cached report is for committed blobs and may not match the files on disk
+        // duplicated lines can only be counted if all files are munched again
+        let old_report = if code_rules.include_working_tree || code_rules.detect_duplicate_lines {
+            &None
+        } else {
+            old_report
+        };
+
         let report = report.set_single_commit_flag(&git_log, &old_report);
-        let report = report.add_commits_history(git_log).await;
+        let mailmap = Mailmap::for_project(project_dir, ignore_files_source, code_rules.mailmap_path.as_ref()).await;
+        let mut report = report.add_commits_history(git_log, &mailmap).await;
+        report.remove_bots(&code_rules.bot_patterns);
 
         // check if there were any contents or muncher changes since the last commit
         // this is the cheapest check we can do to determine if there were an changes that need to be reprocessed
@@ -109,11 +165,28 @@ impl Report {
         // group contributor files by commit to get the blob IDs with min number of git requests later
 
         // populate blob sha1 from head commit for blobs that need to be munched
-        let blobs_to_munch = git::populate_blob_sha1(project_dir, blobs_to_munch, None).await?;
+        let mut blobs_to_munch = git::populate_blob_sha1(project_dir, blobs_to_munch, None).await?;
+        if code_rules.include_working_tree {
+            for file_name in untracked_files {
+                if code_rules.get_muncher(&file_name).is_some() {
+                    blobs_to_munch.insert(
+                        file_name,
+                        GitBlob {
+                            sha1: String::new(),
+                            commit_sha1: String::new(),
+                            commit_date_epoch: 0,
+                            commit_date_iso: String::new(),
+                            size: None,
+                        },
+                    );
+                }
+            }
+            debug!("Blobs to munch with untracked files: {}", blobs_to_munch.len());
+        }
 
         // generate the report
         let report = report
-            .process_project_files(code_rules, project_dir, &blobs_to_munch, Some(&all_head_files))
+            .process_project_files(code_rules, project_dir, &blobs_to_munch, Some(&all_head_files), progress)
             .await?;
 
         // update lists of files (unprocessed and project tree)
@@ -125,6 +198,57 @@ impl Report {
         Ok(Some(report))
     }
 
+    /// Processes files staged in the git index, e.g. for a pre-commit hook, and returns a report marked as a staged snapshot.
+    /// The report has no commit info because the files are not committed yet.
+    pub async fn process_staged(
+        code_rules: &mut code_rules::CodeRules,
+        project_dir: &Path,
+    ) -> Result<report::Report, ()> {
+        let mut report = report::Report::new();
+        report.is_staged_snapshot = true;
+
+        // blob SHA1s come straight from the index
+        let ignore_list =
+            ignore::IgnoreList::for_project(project_dir, git::RootFilesSource::Index, &code_rules.ignore_patterns)
+                .await;
+        let mut staged_blobs = git::get_index_blobs(project_dir, &code_rules.ignore_paths).await?;
+        staged_blobs.retain(|file_name, _| !ignore_list.is_ignored(file_name));
+        let all_staged_files = staged_blobs.keys().cloned().collect::<HashSet<String>>();
+
+        // remove blobs that have no munchers - there is no point in getting the contents
+        let blobs_to_munch = staged_blobs
+            .into_iter()
+            .filter_map(|(file_name, blob)| {
+                if code_rules.get_muncher(&file_name).is_some() {
+                    Some((file_name, blob))
+                } else {
+                    None
+                }
+            })
+            .collect::<ListOfBlobs>();
+        debug!("Staged blobs to munch: {}", blobs_to_munch.len());
+
+        let mut report = report
+            .process_project_files(code_rules, project_dir, &blobs_to_munch, Some(&all_staged_files), None)
+            .await?;
+
+        // per-file records get blank commit info from the blobs, which is not the same as no commit info
+        report.per_file_tech = report
+            .per_file_tech
+            .drain()
+            .map(|mut tech| {
+                tech.commit_sha1 = None;
+                tech.commit_date_epoch = None;
+                tech.commit_date_iso = None;
+                tech
+            })
+            .collect::<HashSet<report::Tech>>();
+
+        let report = report.update_project_file_lists(all_staged_files);
+
+        Ok(report.with_summary())
+    }
+
     /// Processes specified files from the repo and returns a report with Tech and Tech per file sections.
     /// * `project_dir` - needed for git
     /// * `blobs_to_process` - list of blobs that need to be processed, must have SHA1 set
@@ -134,34 +258,303 @@ impl Report {
         project_dir: &Path,
         blobs_to_process: &ListOfBlobs,
         all_tree_files: Option<&HashSet<String>>,
+        progress: Option<&ProgressCallback>,
     ) -> Result<report::Report, ()> {
         info!("Processing individual project files from {}", project_dir.to_string_lossy());
 
         // result collectors
         let mut report = self;
 
-        // loop through all the files supplied by the caller and process them one by one
-        for (file_name, blob) in blobs_to_process {
+        // git always uses `/`, but a caller may pass Windows paths
+        // they are normalized once here for the records and the lists of unprocessed files to have the same names
+        let blobs_to_process = blobs_to_process
+            .iter()
+            .map(|(file_name, blob)| (utils::normalize_file_name(file_name), blob.clone()))
+            .collect::<ListOfBlobs>();
+
+        // a copy is needed because code_rules is borrowed mutably for getting munchers inside the loop
+        let default_text_muncher = code_rules.default_text_muncher.clone();
+        // the cache is keyed by blob SHA1, which does not change with the files on disk
+        // and it has no line hashes for counting duplicated lines
+        let blob_cache = if code_rules.include_working_tree || code_rules.detect_duplicate_lines {
+            None
+        } else {
+            code_rules.blob_cache.clone()
+        };
+        let max_concurrent_files = code_rules.max_concurrent_files.max(1);
+        let settings = processors::ProcessFileSettings {
+            strip_control_chars: code_rules.strip_control_chars.clone(),
+            track_ref_first_seen: code_rules.track_ref_first_seen,
+            hash_code_lines: code_rules.detect_duplicate_lines,
+            max_file_size_bytes: code_rules.max_file_size_bytes,
+            max_avg_line_length: code_rules.max_avg_line_length,
+            generated_markers: code_rules.generated_markers.clone(),
+            include_working_tree: code_rules.include_working_tree,
+        };
+        // cached results are only valid for the same settings and tree
+        let settings_hash = settings.results_hash(all_tree_files);
+
+        // test files use the same pattern syntax as .gitignore
+        let mut test_paths = ignore::IgnoreList::default();
+        test_paths.add_patterns(code_rules.test_path_patterns.iter().map(|pattern| pattern.as_str()));
+
+        // concurrent tasks need their own copies of shared data
+        let all_tree_files = all_tree_files.map(|v| Arc::new(v.clone()));
+        let mut munchers: HashMap<String, Arc<Muncher>> = HashMap::new();
+
+        // a pathological repo should not tie up the process indefinitely
+        let deadline = code_rules
+            .max_processing_duration
+            .map(|v| std::time::Instant::now() + v);
+
+        // code lines seen in the files merged so far, see `Tech.duplicated_lines`
+        let mut seen_line_hashes: HashSet<u64> = HashSet::new();
+
+        // identical blobs at multiple paths, e.g. vendored copies, are munched once and the results are copied
+        // the files on disk may not match their blobs, so only committed files can be matched this way
+        let mut paths_per_blob: HashMap<&String, usize> = HashMap::new();
+        if !code_rules.include_working_tree {
+            for blob in blobs_to_process.values().filter(|blob| !blob.sha1.is_empty()) {
+                *paths_per_blob.entry(&blob.sha1).or_insert(0) += 1;
+            }
+        }
+        // results for blobs with multiple paths keyed on the blob SHA1 and the muncher hash
+        let mut munched_blobs: HashMap<(String, u64), Result<Vec<report::Tech>, processors::ProcessError>> =
+            HashMap::new();
+
+        // loop through all the files supplied by the caller to fetch the right muncher and check the cache first
+        // the rest is munched concurrently, up to `max_concurrent_files` at a time
+        // the files are sorted by name for duplicated lines to be attributed to the same files every time
+        let mut blobs = blobs_to_process.iter().collect::<Vec<(&String, &GitBlob)>>();
+        blobs.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        let mut planned_files = Vec::with_capacity(blobs.len());
+        let mut files_to_process = Vec::new();
+        let mut planned_blob_keys: HashSet<(String, u64)> = HashSet::new();
+        for (idx, (file_name, blob)) in blobs.iter().enumerate() {
+            if deadline.is_some_and(|deadline| std::time::Instant::now() > deadline) {
+                warn!("Processing time limit reached. {} files left unprocessed.", blobs.len() - idx);
+                report.timed_out = true;
+                for (file_name, _) in &blobs[idx..] {
+                    report.add_unprocessed_file(file_name);
+                }
+                break;
+            }
+
             debug!("Blob {}/{}", file_name, blob.sha1);
-            // fetch the right muncher
-            if let Some(muncher) = code_rules.get_muncher(file_name) {
-                // process the file with the rules from the muncher
-                if let Ok(tech) = processors::process_file(
+            let mut share_muncher = |muncher: &Muncher| {
+                munchers
+                    .entry(muncher.muncher_name.clone())
+                    .or_insert_with(|| Arc::new(muncher.clone()))
+                    .clone()
+            };
+            let mut muncher = code_rules.get_muncher(file_name).map(&mut share_muncher);
+
+            // the contents read to pick the munchers are kept for processing to avoid reading the file again
+            let mut contents = None;
+
+            // files with no muncher by name may still be recognized by their contents, e.g. scripts with a shebang
+            if code_rules.detect_munchers_by_content
+                && muncher
+                    .as_ref()
+                    .is_none_or(|muncher| default_text_muncher.as_ref() == Some(&muncher.muncher_name))
+            {
+                if let Some((file, first_lines)) = processors::get_first_lines(
+                    file_name,
+                    blob,
+                    project_dir,
+                    code_rules.include_working_tree || blob.sha1.is_empty(),
+                    code_rules.max_file_size_bytes,
+                )
+                .await
+                {
+                    if let Some(detected) = code_rules.get_muncher_by_content(&first_lines).map(&mut share_muncher) {
+                        debug!("Muncher {} detected by content of {}", detected.muncher_name, file_name);
+                        muncher = Some(detected);
+                    }
+                    contents = Some(file);
+                }
+            }
+
+            // notebooks are munched with the rules for the language of their code cells, if known
+            if notebook::is_notebook(file_name) {
+                if let Some((notebook, ext)) = processors::read_notebook(
+                    file_name,
+                    blob,
+                    project_dir,
+                    code_rules.include_working_tree || blob.sha1.is_empty(),
+                    code_rules.max_file_size_bytes,
+                )
+                .await
+                {
+                    if let Some(detected) = ext
+                        .and_then(|ext| code_rules.get_muncher(&[file_name.as_str(), ext.as_str()].concat()))
+                        .filter(|muncher| default_text_muncher.as_ref() != Some(&muncher.muncher_name))
+                        .map(&mut share_muncher)
+                    {
+                        debug!("Muncher {} detected by notebook language of {}", detected.muncher_name, file_name);
+                        muncher = Some(detected);
+                    }
+                    contents = Some(notebook);
+                }
+            }
+
+            // code in fenced blocks of Markdown files is munched with the rules for the language of the fence
+            let mut fence_munchers = HashMap::new();
+            if code_rules.munch_fenced_code_blocks
+                && muncher
+                    .as_ref()
+                    .is_some_and(|muncher| muncher.language == Muncher::MARKDOWN_LANGUAGE)
+            {
+                if let Some((file, tags)) = processors::get_fence_tags(
                     file_name,
-                    &blob.sha1,
-                    muncher,
+                    blob,
                     project_dir,
-                    &blob.commit_sha1,
-                    blob.commit_date_epoch,
-                    &blob.commit_date_iso,
-                    all_tree_files,
+                    code_rules.include_working_tree || blob.sha1.is_empty(),
+                    code_rules.max_file_size_bytes,
                 )
                 .await
                 {
-                    report.per_file_tech.insert(tech.clone());
-                    report.merge_tech_record(tech.reset_file_and_commit_info());
+                    for tag in tags {
+                        if let Some(fence_muncher) = code_rules.get_muncher_for_fence_tag(&tag).map(&mut share_muncher)
+                        {
+                            fence_munchers.insert(tag, fence_muncher);
+                        }
+                    }
+                    contents = Some(file);
+                }
+            }
+            // the cache is keyed by the hash of a single muncher
+            let is_cacheable = fence_munchers.is_empty();
+
+            // the same blob at another path may be munched in this run already
+            // the rules may differ between the paths, e.g. for a file with no extension
+            let blob_key = muncher
+                .as_ref()
+                .filter(|_| paths_per_blob.get(&blob.sha1).is_some_and(|paths| *paths > 1))
+                .map(|muncher| (blob.sha1.clone(), muncher.muncher_hash));
+            let is_blob_copy = match blob_key.as_ref() {
+                Some(key) => !planned_blob_keys.insert(key.clone()),
+                None => false,
+            };
+
+            // an identical blob may have been munched with the same rules before
+            let cached_techs = muncher
+                .as_ref()
+                .filter(|_| is_cacheable && !is_blob_copy)
+                .and_then(|muncher| {
+                    blob_cache
+                        .as_ref()
+                        .and_then(|cache| cache.get(file_name, blob, muncher.muncher_hash, settings_hash))
+                });
+
+            if let (Some(muncher), None, false) = (muncher.as_ref(), cached_techs.as_ref(), is_blob_copy) {
+                files_to_process.push(processors::FileToProcess {
+                    file_name: (*file_name).clone(),
+                    blob: (*blob).clone(),
+                    muncher: muncher.clone(),
+                    fence_munchers,
+                    contents,
+                });
+            }
+            planned_files.push((*file_name, *blob, muncher, cached_techs, is_cacheable, blob_key, is_blob_copy));
+        }
+
+        // process the files with the rules from their munchers
+        code_rules.munched_files += files_to_process.len();
+        let mut processed_techs = processors::spawn_process_files(
+            files_to_process,
+            project_dir,
+            all_tree_files,
+            &settings,
+            max_concurrent_files,
+            deadline,
+        )
+        .into_iter();
+
+        // the results are merged in the same order regardless of how many files were processed at a time
+        // the files processed ahead of the merge wait for it with their results in the tasks
+        let planned_len = planned_files.len();
+        for (idx, (file_name, blob, muncher, cached_techs, is_cacheable, blob_key, is_blob_copy)) in
+            planned_files.into_iter().enumerate()
+        {
+            if let Some(muncher) = muncher {
+                let techs = if is_blob_copy {
+                    // the first path of the blob comes before its copies, so the result is already there
+                    debug!("Reusing munched blob {} for {}", blob.sha1, file_name);
+                    blob_key
+                        .as_ref()
+                        .and_then(|key| munched_blobs.get(key))
+                        .cloned()
+                        .unwrap_or_else(|| Err(processors::ProcessError::Task("missing result".to_owned())))
+                        .map(|techs| {
+                            techs
+                                .into_iter()
+                                .map(|tech| tech.with_file_and_commit_info(file_name, blob))
+                                .collect::<Vec<report::Tech>>()
+                        })
+                } else {
+                    let techs = match cached_techs {
+                        Some(v) => Ok(v),
+                        None => {
+                            let techs = match processed_techs.next() {
+                                Some(task) => processors::join_processed_file(task).await,
+                                None => Err(processors::ProcessError::Task("missing result".to_owned())),
+                            };
+                            if let (Some(cache), Ok(techs), true) = (blob_cache.as_ref(), techs.as_ref(), is_cacheable)
+                            {
+                                cache.insert(&blob.sha1, muncher.muncher_hash, settings_hash, techs);
+                            }
+                            techs
+                        }
+                    };
+                    if let Some(blob_key) = blob_key {
+                        munched_blobs.insert(blob_key, techs.clone());
+                    }
+                    techs
+                };
+
+                match techs {
+                    Err(processors::ProcessError::TimedOut) => {
+                        debug!("Processing time limit reached before {}", file_name);
+                        report.timed_out = true;
+                        report.add_unprocessed_file(file_name);
+                    }
+                    Err(reason) => {
+                        warn!("{} was not processed: {}", file_name, reason);
+                        report.add_unprocessed_file_with_reason(file_name, reason.to_string());
+                    }
+                    Ok(techs) => {
+                        // the fallback muncher gets all sorts of files, but only text with some content is worth keeping
+                        if techs.iter().all(|tech| tech.total_lines == 0)
+                            && default_text_muncher.as_ref() == Some(&muncher.muncher_name)
+                        {
+                            debug!("Skipping empty or binary file {}", file_name);
+                        } else {
+                            // files with interleaved languages have a record per language
+                            let is_test = test_paths.is_ignored(file_name);
+                            let mut file_line_hashes = Vec::new();
+                            for mut tech in techs {
+                                tech.set_is_test(is_test);
+                                // lines repeated within the same file are not duplicates
+                                if let Some(code_line_hashes) = tech.code_line_hashes.take() {
+                                    tech.duplicated_lines = code_line_hashes
+                                        .iter()
+                                        .filter(|hash| seen_line_hashes.contains(*hash))
+                                        .count() as u64;
+                                    file_line_hashes.extend(code_line_hashes);
+                                }
+                                report.per_file_tech.insert(tech.clone());
+                                report.merge_tech_record(tech.reset_file_and_commit_info());
+                            }
+                            seen_line_hashes.extend(file_line_hashes);
+                        }
+                    }
                 }
             }
+
+            if let Some(progress) = progress {
+                progress(idx + 1, planned_len);
+            }
         }
 
         info!("Analysis finished");
@@ -195,13 +588,15 @@ impl Report {
             .iter()
             .filter_map(|tech| tech.file_name.clone())
             .collect();
+        // a file may have several records, one per language, so only the files present before copying are skipped
+        let existing_per_file_techs = copied_per_file_techs.clone();
 
         // loop thru all the cached per-file techs
         for tech in &other_report.per_file_tech {
             // unwrap the file name - there should always be one
             if let Some(file_tech_file_name) = tech.file_name.clone() {
                 // skip the file if it's already present in the target report
-                if copied_per_file_techs.contains(&file_tech_file_name) {
+                if existing_per_file_techs.contains(&file_tech_file_name) {
                     continue;
                 }
                 // check if there is a corresponding blob for that file name
@@ -265,6 +660,7 @@ impl Report {
                         commit_sha1: file.commit.clone(),
                         commit_date_epoch: file.date_epoch,
                         commit_date_iso: file.date_iso.clone(),
+                        size: None,
                     },
                 )
             })
@@ -357,7 +753,7 @@ impl Report {
 
         // generate the report
         let mut report = report
-            .process_project_files(code_rules, project_dir, &blobs_to_munch, all_tree_files)
+            .process_project_files(code_rules, project_dir, &blobs_to_munch, all_tree_files, None)
             .await?;
 
         // count all file extensions from contributor files
@@ -420,6 +816,119 @@ impl Report {
         Ok(report)
     }
 
+    /// Processes only the files changed in the HEAD commit and returns a mini-report scoped to that commit and its author.
+    /// Per-file tech records are re-used from the project report where possible.
+    /// * `git_log` - the full or partial log with the HEAD commit as the first entry
+    pub async fn process_head_commit(
+        &self,
+        code_rules: &mut code_rules::CodeRules,
+        project_dir: &Path,
+        git_log: &[GitLogEntry],
+        all_tree_files: Option<&HashSet<String>>,
+    ) -> Result<report::Report, ()> {
+        let project_report = self;
+
+        // merge commits are not included in the log, so the first entry may be an older commit
+        let head_commit = match git_log.first() {
+            Some(v) => v,
+            None => {
+                warn!("Empty git log - no HEAD commit to process");
+                return Err(());
+            }
+        };
+        if project_report.report_commit_sha1.as_ref() != Some(&head_commit.sha1) {
+            warn!(
+                "HEAD commit {} does not match the project report commit {:?}",
+                head_commit.sha1, project_report.report_commit_sha1
+            );
+        }
+        debug!("Processing HEAD commit: {}", head_commit.sha1);
+
+        // only files that are still in the tree can be processed, deleted files have no blobs
+        let head_blobs = head_commit
+            .files
+            .iter()
+            .filter_map(|file_name| {
+                if all_tree_files.is_some_and(|files| !files.contains(file_name)) {
+                    return None;
+                }
+                Some((
+                    file_name.clone(),
+                    GitBlob {
+                        sha1: String::new(),
+                        commit_sha1: head_commit.sha1.clone(),
+                        commit_date_epoch: head_commit.date_epoch,
+                        commit_date_iso: head_commit.date.clone(),
+                        size: None,
+                    },
+                ))
+            })
+            .collect::<ListOfBlobs>();
+
+        // copy per-file tech records the project report already has for the HEAD commit
+        let report = report::Report::new();
+        let (report, reused_per_file_tech) =
+            report.copy_cached_data_from_another_report(code_rules, Some(project_report), &head_blobs);
+
+        // the rest has to be munched
+        let blobs_to_munch = head_blobs
+            .into_iter()
+            .filter_map(|(file_name, blob)| {
+                if reused_per_file_tech.contains(&file_name) || code_rules.get_muncher(&file_name).is_none() {
+                    None
+                } else {
+                    Some((file_name, blob))
+                }
+            })
+            .collect::<ListOfBlobs>();
+        debug!(
+            "HEAD commit blobs to munch: {}, reused: {}",
+            blobs_to_munch.len(),
+            reused_per_file_tech.len()
+        );
+
+        let blobs_to_munch =
+            git::populate_blob_sha1(project_dir, blobs_to_munch, Some(head_commit.sha1.clone())).await?;
+        let mut report = report
+            .process_project_files(code_rules, project_dir, &blobs_to_munch, all_tree_files, None)
+            .await?;
+
+        // count all file extensions from the commit files
+        for file_name in &head_commit.files {
+            report.add_file_type(file_name);
+        }
+
+        // the commit and its author, whose identity was already mapped via .mailmap in the project report
+        let author = project_report
+            .contributors
+            .iter()
+            .flatten()
+            .find(|contributor| contributor.name_email_pairs.contains(&head_commit.author_name_email))
+            .map(|contributor| contributor.git_id.clone())
+            .unwrap_or_else(|| {
+                Contributor::git_identity_from_name_email_pair(&head_commit.author_name_email, &Mailmap::default())
+            });
+        report.report_commit_sha1 = Some(head_commit.sha1.clone());
+        report.last_commit_author = Some(author.clone());
+        report.git_ids_included.insert(author);
+        report.last_contributor_commit_sha1 = Some(head_commit.sha1.clone());
+        report.last_contributor_commit_date_iso = Some(head_commit.date.clone());
+        report.last_contributor_commit_date_epoch = Some(head_commit.date_epoch);
+        report.commit_count_contributor = Some(1);
+
+        // copy some meta from the project report
+        report.log_hash = project_report.log_hash.clone();
+        report.is_single_commit = project_report.is_single_commit;
+        report.contributor_count = project_report.contributor_count;
+        report.loc_project = project_report.loc_project;
+        report.libs_project = project_report.libs_project;
+        report.commit_count_project = project_report.commit_count_project;
+        report.date_head = project_report.date_head.clone();
+        report.date_init = project_report.date_init.clone();
+
+        Ok(report)
+    }
+
     /// Returns `true` if either content (blobs) or relevant munchers (their hashes) have changed since the old report
     /// was generated. Otherwise returns true.
     fn has_content_or_muncher_changes(
@@ -443,6 +952,12 @@ impl Report {
             return true;
         };
 
+        // files left unprocessed after running out of time have to be munched even if nothing changed
+        if old_report.timed_out {
+            warn!("Reprocessing a report that timed out");
+            return true;
+        }
+
         let report_commit_sha1 = self.report_commit_sha1.clone().unwrap_or_default();
         let old_report_commit_sha1 = old_report.report_commit_sha1.clone().unwrap_or_default();
 
@@ -457,7 +972,7 @@ impl Report {
         // collects hashes of munchers that should be ignored for this project because they have
         // not changed since the last processing of the repo
         let mut old_munchers: std::collections::HashSet<u64> = std::collections::HashSet::new();
-        for tech in &old_report.tech {
+        for tech in old_report.tech() {
             if tech.muncher_hash > 0 {
                 old_munchers.insert(tech.muncher_hash);
             }
@@ -568,3 +1083,305 @@ impl Report {
         report
     }
 }
+
+#[tokio::test]
+async fn test_process_head_commit() {
+    let repo = utils::TestRepo::new("head_commit");
+    repo.write("main.rs", "fn main() {\n    let x = 1;\n}\n");
+    repo.write("README.md", "# Demo\n");
+    repo.commit_all("init");
+    repo.write("lib.rs", "pub fn lib() {}\n");
+    repo.write("README.md", "# Demo\n\nMore docs.\n");
+    repo.git(&["add", "-A"]);
+    repo.git(&[
+        "-c",
+        "user.name=Other",
+        "-c",
+        "user.email=other@example.com",
+        "commit",
+        "-qm",
+        "lib",
+    ]);
+
+    let mut code_rules = code_rules::CodeRules::new();
+    let git_log = git::get_log(&repo.dir, None, &code_rules.ignore_paths).await.unwrap();
+    let project_report = Report::process_project(&mut code_rules, &repo.dir, &None, Some(git_log.clone()))
+        .await
+        .unwrap()
+        .unwrap();
+    let report = project_report
+        .process_head_commit(&mut code_rules, &repo.dir, &git_log, project_report.tree_files.as_ref())
+        .await
+        .unwrap();
+
+    // only the files changed in the HEAD commit are included
+    let mut file_names = report
+        .per_file_tech
+        .iter()
+        .filter_map(|tech| tech.file_name.clone())
+        .collect::<Vec<String>>();
+    file_names.sort();
+    assert_eq!(file_names, vec!["README.md", "lib.rs"]);
+    assert!(report
+        .tech()
+        .iter()
+        .all(|tech| tech.language != "Rust" || tech.code_lines == 1));
+
+    // the report is scoped to the author of the HEAD commit
+    assert_eq!(report.last_commit_author.as_deref(), Some("other@example.com"));
+    assert_eq!(report.commit_count_contributor, Some(1));
+    assert_eq!(report.report_commit_sha1, project_report.report_commit_sha1);
+
+    // there is no HEAD commit in an empty log
+    assert!(project_report
+        .process_head_commit(&mut code_rules, &repo.dir, &[], None)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_process_staged() {
+    let repo = utils::TestRepo::new("staged");
+    repo.write("main.rs", "fn main() {\n    let x = 1;\n}\n");
+    repo.commit_all("init");
+    repo.write("main.rs", "fn main() {\n    let x = 1;\n    let y = 2;\n}\n");
+    repo.git(&["add", "main.rs"]);
+    // changes that are not staged are not included
+    repo.write("main.rs", "fn main() {}\n");
+    repo.write("lib.rs", "pub fn lib() {}\n");
+
+    let mut code_rules = code_rules::CodeRules::new();
+    let report = Report::process_staged(&mut code_rules, &repo.dir).await.unwrap();
+
+    assert!(report.is_staged_snapshot);
+    assert!(report.date_head.is_none());
+    assert_eq!(report.per_file_tech.len(), 1);
+    let tech = report.per_file_tech.iter().next().unwrap();
+    assert_eq!(tech.file_name.as_deref(), Some("main.rs"));
+    assert!(tech.commit_sha1.is_none());
+    assert_eq!(report.tech().iter().map(|tech| tech.code_lines).sum::<u64>(), 3);
+}
+
+#[tokio::test]
+async fn test_process_project_with_progress() {
+    let repo = utils::TestRepo::new("progress");
+    repo.write("main.rs", "fn main() {}\n");
+    repo.write("lib.rs", "pub fn lib() {}\n");
+    repo.write("src/util.rs", "pub fn util() {}\n");
+    repo.commit_all("init");
+
+    let calls = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let progress_calls = calls.clone();
+    let progress = move |processed: usize, total: usize| progress_calls.lock().unwrap().push((processed, total));
+
+    let mut code_rules = code_rules::CodeRules::new();
+    Report::process_project_with_progress(&mut code_rules, &repo.dir, &None, None, Some(&progress))
+        .await
+        .unwrap()
+        .unwrap();
+
+    // the callback fires once per file
+    assert_eq!(*calls.lock().unwrap(), vec![(1, 3), (2, 3), (3, 3)]);
+}
+
+#[tokio::test]
+async fn test_process_project_timed_out() {
+    let repo = utils::TestRepo::new("timed_out");
+    repo.write("main.rs", "fn main() {}\n");
+    repo.write("lib.rs", "pub fn lib() {}\n");
+    repo.commit_all("init");
+
+    // no time to munch anything
+    let mut code_rules = code_rules::CodeRules::new();
+    code_rules.max_processing_duration = Some(std::time::Duration::from_secs(0));
+    let report = Report::process_project(&mut code_rules, &repo.dir, &None, None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(report.timed_out);
+    assert!(report.per_file_tech.is_empty());
+    assert!(report.unprocessed_file_names.contains("main.rs"));
+
+    // the same commit is processed again instead of keeping the incomplete report
+    let mut code_rules = code_rules::CodeRules::new();
+    let report = Report::process_project(&mut code_rules, &repo.dir, &Some(report), None)
+        .await
+        .unwrap()
+        .expect("A timed out report must be reprocessed");
+    assert!(!report.timed_out);
+    assert_eq!(report.per_file_tech.len(), 2);
+}
+
+#[tokio::test]
+async fn test_process_project_manifests() {
+    let repo = utils::TestRepo::new("manifests");
+    repo.write("Cargo.toml", "[package]\nname = \"demo\"\n\n[dependencies]\nserde = \"1.0\"\n");
+    repo.write("web/package.json", r#"{ "name": "web", "dependencies": { "react": "^18.2.0" } }"#);
+    repo.write("ml/requirements.txt", "# models\nnumpy>=1.24\n");
+    repo.write("go.mod", "module example.com/demo\n\nrequire github.com/pkg/errors v0.9.1\n");
+    // manifests of vendored dependencies stay ignored
+    repo.write(
+        "node_modules/react/package.json",
+        r#"{ "name": "react", "dependencies": { "loose-envify": "^1.1.0" } }"#,
+    );
+    repo.commit_all("init");
+
+    let mut code_rules = code_rules::CodeRules::new();
+    let report = Report::process_project(&mut code_rules, &repo.dir, &None, None)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let pkgs = |file_name: &str| {
+        report
+            .per_file_tech
+            .iter()
+            .find(|tech| tech.file_name.as_deref() == Some(file_name))
+            .and_then(|tech| tech.pkgs.as_ref())
+            .map(|pkgs| pkgs.iter().map(|pkg| pkg.k.clone()).collect::<Vec<String>>())
+            .unwrap_or_default()
+    };
+    assert_eq!(pkgs("Cargo.toml"), vec!["serde"]);
+    assert_eq!(pkgs("web/package.json"), vec!["react"]);
+    assert_eq!(pkgs("ml/requirements.txt"), vec!["numpy"]);
+    assert_eq!(pkgs("go.mod"), vec!["github.com/pkg/errors"]);
+    assert!(pkgs("node_modules/react/package.json").is_empty());
+}
+
+#[tokio::test]
+async fn test_process_project_notebooks() {
+    let repo = utils::TestRepo::new("notebooks");
+    let notebook = |metadata: &str| {
+        format!(
+            r##"{{
+ "cells": [
+  {{ "cell_type": "markdown", "metadata": {{}}, "source": ["# Analysis"] }},
+  {{ "cell_type": "code", "metadata": {{}}, "outputs": [], "source": ["import pandas as pd\n", "print(pd)"] }}
+ ],
+ "metadata": {{ {} }},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}}"##,
+            metadata
+        )
+    };
+    repo.write(
+        "python.ipynb",
+        notebook(r#""language_info": { "name": "python", "file_extension": ".py" }"#),
+    );
+    repo.write("unknown.ipynb", notebook(""));
+    repo.commit_all("init");
+
+    let mut code_rules = code_rules::CodeRules::new();
+    let report = Report::process_project(&mut code_rules, &repo.dir, &None, None)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let tech = |file_name: &str| {
+        report
+            .per_file_tech
+            .iter()
+            .find(|tech| tech.file_name.as_deref() == Some(file_name))
+            .unwrap()
+            .clone()
+    };
+    // only the code cells are munched with the rules of the notebook language
+    let python = tech("python.ipynb");
+    assert_eq!((python.language.as_str(), python.total_lines, python.code_lines), ("Python", 2, 2));
+    // the JSON is munched as-is with the notebook rules if the language is not known
+    let unknown = tech("unknown.ipynb");
+    assert_eq!((unknown.language.as_str(), unknown.total_lines), ("Jupyter", 9));
+}
+
+#[tokio::test]
+async fn test_blob_cache_with_different_settings() {
+    let repo = utils::TestRepo::new("blob_cache_settings");
+    repo.write("main.py", "\nimport requests\n");
+    repo.commit_all("init");
+    let cache_dir = repo.dir.join(".stm_cache");
+
+    let process = |track_ref_first_seen: bool| {
+        let repo_dir = repo.dir.clone();
+        let cache_dir = cache_dir.clone();
+        async move {
+            let mut code_rules = code_rules::CodeRules::new();
+            code_rules.blob_cache = Some(blob_cache::BlobCache::new(&cache_dir));
+            code_rules.track_ref_first_seen = track_ref_first_seen;
+            Report::process_project(&mut code_rules, &repo_dir, &None, None)
+                .await
+                .unwrap()
+                .unwrap()
+        }
+    };
+
+    let report = process(false).await;
+    assert!(report.per_file_tech.iter().all(|tech| tech.ref_first_seen.is_none()));
+
+    // the cached result of the 1st run has no line numbers
+    let report = process(true).await;
+    let tech = report.per_file_tech.iter().next().unwrap();
+    assert_eq!(tech.ref_first_seen.as_ref().and_then(|refs| refs.get("requests")), Some(&2));
+}
+
+#[tokio::test]
+async fn test_process_project_files_with_windows_paths() {
+    let repo = utils::TestRepo::new("windows_paths");
+    repo.write("src/main.rs", "fn main() {}\n");
+    repo.write("src/data.xyz", "?\n");
+
+    // untracked files with no blobs are read from disk
+    let blob = GitBlob {
+        sha1: String::new(),
+        commit_sha1: String::new(),
+        commit_date_epoch: 0,
+        commit_date_iso: String::new(),
+        size: None,
+    };
+    let blobs = ["src\\main.rs", "src\\data.xyz"]
+        .iter()
+        .map(|file_name| (file_name.to_string(), blob.clone()))
+        .collect::<ListOfBlobs>();
+
+    let mut code_rules = code_rules::CodeRules::new();
+    let report = Report::new()
+        .process_project_files(&mut code_rules, &repo.dir, &blobs, None, None)
+        .await
+        .unwrap();
+    let file_names = report
+        .per_file_tech
+        .iter()
+        .filter_map(|tech| tech.file_name.as_deref())
+        .collect::<Vec<&str>>();
+    assert_eq!(file_names, vec!["src/main.rs"]);
+
+    // the processed file is not listed as unprocessed under its Windows name
+    let report = report.update_project_file_lists(blobs.keys().cloned().collect());
+    let unprocessed = report.unprocessed_file_names.iter().collect::<Vec<&String>>();
+    assert_eq!(unprocessed, vec!["src/data.xyz"]);
+}
+
+#[tokio::test]
+async fn test_max_file_size() {
+    let repo = utils::TestRepo::new("max_file_size");
+    repo.write("small.rs", "fn main() {}\n");
+    repo.write("large.rs", "fn f() {}\n".repeat(10));
+    repo.commit_all("init");
+
+    let mut code_rules = code_rules::CodeRules::new();
+    code_rules.max_file_size_bytes = Some(50);
+
+    // the size of committed blobs comes from the tree listing
+    let report = Report::process_project(&mut code_rules, &repo.dir, &None, None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(report.per_file_tech.len(), 1);
+    assert!(report.unprocessed_file_reasons["large.rs"].contains("100"));
+
+    // the size of staged blobs is only known after loading them
+    repo.write("staged.rs", "fn g() {}\n".repeat(10));
+    repo.git(&["add", "staged.rs"]);
+    let report = Report::process_staged(&mut code_rules, &repo.dir).await.unwrap();
+    assert!(report.unprocessed_file_reasons["staged.rs"].contains("100"));
+}
@@ -0,0 +1,207 @@
+use crate::git::{get_root_file_contents, split_name_email, RootFilesSource};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// A list of rules from a `.mailmap` file for mapping the names and emails a developer committed under to their
+/// canonical identity. See https://git-scm.com/docs/gitmailmap for the format.
+#[derive(Debug, Default)]
+pub(crate) struct Mailmap {
+    entries: Vec<MailmapEntry>,
+}
+
+/// A single line of a mailmap file. Only the parts present in the line are Some.
+#[derive(Debug)]
+struct MailmapEntry {
+    proper_name: Option<String>,
+    proper_email: Option<String>,
+    /// The entry applies to any name if None
+    commit_name: Option<String>,
+    commit_email: String,
+}
+
+impl Mailmap {
+    pub const MAILMAP_FILE_NAME: &'static str = ".mailmap";
+
+    /// Returns the rules from `mailmap_path` on disk relative to `project_dir`, if set, or from `.mailmap` in the root of
+    /// the project taken from `source`. It is the same as `mailmap.file` and `.mailmap` in git.
+    /// A missing `.mailmap` is not an error and returns an empty list.
+    pub(crate) async fn for_project(
+        project_dir: &Path,
+        source: RootFilesSource<'_>,
+        mailmap_path: Option<&PathBuf>,
+    ) -> Self {
+        let contents = match mailmap_path {
+            Some(v) => {
+                let mailmap_file = project_dir.join(v);
+                match std::fs::read(&mailmap_file) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        warn!("Cannot read {} due to {}", mailmap_file.to_string_lossy(), e);
+                        return Self::default();
+                    }
+                }
+            }
+            None => match get_root_file_contents(project_dir, source, &[Self::MAILMAP_FILE_NAME])
+                .await
+                .remove(Self::MAILMAP_FILE_NAME)
+            {
+                Some(v) => v,
+                None => return Self::default(),
+            },
+        };
+
+        let mailmap = Self::parse(&String::from_utf8_lossy(&contents));
+        debug!("Mailmap entries: {}", mailmap.entries.len());
+        mailmap
+    }
+
+    /// Parses the contents of a mailmap file. Blank lines, comments and invalid lines are skipped.
+    pub(crate) fn parse(contents: &str) -> Self {
+        Self {
+            entries: contents.lines().filter_map(MailmapEntry::new).collect(),
+        }
+    }
+
+    /// Returns the canonical name and email for the name/email pair from a commit. Entries matching both, the name
+    /// and the email, take precedence over entries matching the email only. The last matching entry wins, same as in git.
+    /// The pair is returned as-is if there is no match.
+    pub(crate) fn canonical(&self, name_email: &(String, String)) -> (String, String) {
+        let (name, email) = name_email;
+
+        let entry = self
+            .entries
+            .iter()
+            .rev()
+            .find(|entry| {
+                entry.commit_email.eq_ignore_ascii_case(email)
+                    && entry
+                        .commit_name
+                        .as_ref()
+                        .is_some_and(|commit_name| commit_name.eq_ignore_ascii_case(name))
+            })
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .rev()
+                    .find(|entry| entry.commit_name.is_none() && entry.commit_email.eq_ignore_ascii_case(email))
+            });
+
+        match entry {
+            Some(entry) => (
+                entry.proper_name.clone().unwrap_or_else(|| name.clone()),
+                entry.proper_email.clone().unwrap_or_else(|| email.clone()),
+            ),
+            None => name_email.clone(),
+        }
+    }
+}
+
+impl MailmapEntry {
+    /// Parses one of the 4 forms of a mailmap line:
+    /// * `Proper Name <commit@email.xx>`
+    /// * `<proper@email.xx> <commit@email.xx>`
+    /// * `Proper Name <proper@email.xx> <commit@email.xx>`
+    /// * `Proper Name <proper@email.xx> Commit Name <commit@email.xx>`
+    fn new(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        // anything after the second email is a comment
+        let (first_name, first_email, rest) = next_name_email(line)?;
+
+        match next_name_email(rest) {
+            Some((commit_name, commit_email, _)) => Some(Self {
+                proper_name: first_name,
+                proper_email: Some(first_email),
+                commit_name,
+                commit_email,
+            }),
+            // a single email maps the name only and a name is required
+            None if first_name.is_some() => Some(Self {
+                proper_name: first_name,
+                proper_email: None,
+                commit_name: None,
+                commit_email: first_email,
+            }),
+            None => {
+                warn!("Invalid mailmap line: {}", line);
+                None
+            }
+        }
+    }
+}
+
+/// Splits `Some Name <some@email.xx> the rest` into the optional name, the email and the rest of the line.
+/// Returns None if there is no `<email>`.
+fn next_name_email(value: &str) -> Option<(Option<String>, String, &str)> {
+    let end = value.find('>')?;
+    let (name, email) = split_name_email(value[..=end].trim());
+    if email.is_empty() {
+        return None;
+    }
+    let name = if name.is_empty() { None } else { Some(name) };
+
+    Some((name, email, &value[end + 1..]))
+}
+
+#[test]
+fn test_mailmap_forms() {
+    let mailmap = Mailmap::parse(
+        r#"# the four forms of mailmap lines
+Max Voloshin <max@onebro.me>
+<max@onebro.me> <max@old-job.com>
+Max Voloshin <max@onebro.me> <rimutaka@users.noreply.github.com>
+Jane Doe <jane@example.com> admin <root@localhost>
+not a mailmap line
+"#,
+    );
+    let pair = |name: &str, email: &str| (name.to_owned(), email.to_owned());
+
+    // name only
+    assert_eq!(
+        mailmap.canonical(&pair("rimutaka", "max@onebro.me")),
+        pair("Max Voloshin", "max@onebro.me")
+    );
+    // email only, the email is case-insensitive
+    assert_eq!(mailmap.canonical(&pair("max", "Max@Old-Job.com")), pair("max", "max@onebro.me"));
+    // name and email
+    assert_eq!(
+        mailmap.canonical(&pair("rimutaka", "rimutaka@users.noreply.github.com")),
+        pair("Max Voloshin", "max@onebro.me")
+    );
+    // name and email matched by the commit name and email
+    assert_eq!(mailmap.canonical(&pair("admin", "root@localhost")), pair("Jane Doe", "jane@example.com"));
+    assert_eq!(mailmap.canonical(&pair("ubuntu", "root@localhost")), pair("ubuntu", "root@localhost"));
+
+    // aliases collapse into the same contributor identity
+    let git_id = |name: &str, email: &str| {
+        crate::contributor::Contributor::git_identity_from_name_email_pair(&pair(name, email), &mailmap)
+    };
+    assert_eq!(git_id("max", "max@old-job.com"), "max@onebro.me");
+    assert_eq!(git_id("rimutaka", "rimutaka@users.noreply.github.com"), "max@onebro.me");
+    assert_eq!(git_id("someone", "someone@example.com"), "someone@example.com");
+}
+
+#[tokio::test]
+async fn test_mailmap_from_the_commit() {
+    let repo = crate::utils::TestRepo::new("mailmap_from_the_commit");
+    repo.write(".mailmap", "Max Voloshin <max@onebro.me> <max@old-job.com>\n");
+    repo.commit_all("Add mailmap");
+    // an uncommitted change should only be picked up from the working tree
+    repo.write(".mailmap", "Jane Doe <jane@example.com> <max@old-job.com>\n");
+    let pair = ("max".to_owned(), "max@old-job.com".to_owned());
+
+    let mailmap = Mailmap::for_project(&repo.dir, RootFilesSource::Commit("HEAD"), None).await;
+    assert_eq!(mailmap.canonical(&pair).1, "max@onebro.me");
+
+    let mailmap = Mailmap::for_project(&repo.dir, RootFilesSource::WorkingTree, None).await;
+    assert_eq!(mailmap.canonical(&pair).1, "jane@example.com");
+
+    // a custom location is always read from disk
+    repo.write("docs/authors.mailmap", "<max@onebro.me> <max@old-job.com>\n");
+    let mailmap_path = std::path::PathBuf::from("docs/authors.mailmap");
+    let mailmap = Mailmap::for_project(&repo.dir, RootFilesSource::Commit("HEAD"), Some(&mailmap_path)).await;
+    assert_eq!(mailmap.canonical(&pair), ("max".to_owned(), "max@onebro.me".to_owned()));
+}
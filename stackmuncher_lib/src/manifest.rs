@@ -0,0 +1,284 @@
+use crate::report::kwc::{KeywordCounter, KeywordCounterSet};
+use serde_json::Value as JsonValue;
+use std::collections::HashSet;
+use std::path::Path;
+use toml::Value as TomlValue;
+use tracing::{debug, warn};
+
+pub const CARGO_TOML: &str = "Cargo.toml";
+pub const PACKAGE_JSON: &str = "package.json";
+pub const REQUIREMENTS_TXT: &str = "requirements.txt";
+pub const GO_MOD: &str = "go.mod";
+
+/// Dependency tables of Cargo.toml. They can also be nested under `[target.'cfg(...)']` or `[workspace]`.
+const CARGO_DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+/// Dependency objects of package.json
+const NPM_DEPENDENCY_OBJECTS: [&str; 4] = [
+    "dependencies",
+    "devDependencies",
+    "peerDependencies",
+    "optionalDependencies",
+];
+
+/// Returns the list of dependencies declared in a package manifest recognized by its file name, e.g. `Cargo.toml`
+/// or `package.json`. `k` is the package name, `t` is the version requirement, if any, and `c` is the number of
+/// times it was declared, e.g. as a dev and a prod dependency.
+/// Returns None if the file is not a known manifest or cannot be parsed.
+pub fn parse_manifest(path: &str, contents: &str) -> Option<Vec<KeywordCounter>> {
+    let file_name = Path::new(path).file_name()?.to_str()?;
+
+    let deps = match file_name {
+        CARGO_TOML => parse_cargo_toml(contents),
+        PACKAGE_JSON => parse_package_json(contents),
+        REQUIREMENTS_TXT => Some(parse_requirements_txt(contents)),
+        GO_MOD => Some(parse_go_mod(contents)),
+        _ => return None,
+    };
+
+    match deps {
+        Some(deps) => {
+            debug!("Found {} dependencies in {}", deps.len(), path);
+            // the same package may be declared in multiple sections
+            let mut pkgs: HashSet<KeywordCounter> = HashSet::with_capacity(deps.len());
+            for (name, version) in deps {
+                pkgs.increment_counters(new_pkg(name, version));
+            }
+            let mut pkgs = pkgs.into_iter().collect::<Vec<KeywordCounter>>();
+            pkgs.sort();
+            Some(pkgs)
+        }
+        None => {
+            warn!("Cannot parse manifest {}", path);
+            None
+        }
+    }
+}
+
+/// Returns true if the file name of `path` is one of the package manifests known to `parse_manifest`.
+pub fn is_manifest(path: &str) -> bool {
+    matches!(
+        Path::new(path).file_name().and_then(|v| v.to_str()),
+        Some(CARGO_TOML) | Some(PACKAGE_JSON) | Some(REQUIREMENTS_TXT) | Some(GO_MOD)
+    )
+}
+
+/// Returns a counter for a single declaration of the package with the version, if any, in `t`.
+fn new_pkg(name: String, version: Option<String>) -> KeywordCounter {
+    KeywordCounter {
+        k: name,
+        t: version.map(|v| {
+            let mut t = HashSet::new();
+            t.insert(v);
+            t
+        }),
+        c: 1,
+    }
+}
+
+/// Returns `(name, version)` pairs from all dependency tables, including target-specific and workspace ones.
+/// A renamed dependency is listed under its `package` name.
+fn parse_cargo_toml(contents: &str) -> Option<Vec<(String, Option<String>)>> {
+    let manifest = match contents.parse::<TomlValue>() {
+        Ok(TomlValue::Table(v)) => v,
+        Ok(_) => return None,
+        Err(e) => {
+            warn!("Invalid TOML due to {}", e);
+            return None;
+        }
+    };
+
+    // collect all tables that may have dependency tables in them
+    let mut parents = vec![&manifest];
+    if let Some(TomlValue::Table(workspace)) = manifest.get("workspace") {
+        parents.push(workspace);
+    }
+    if let Some(TomlValue::Table(targets)) = manifest.get("target") {
+        parents.extend(targets.values().filter_map(|target| target.as_table()));
+    }
+
+    let mut deps = Vec::new();
+    for parent in parents {
+        for dep_table in CARGO_DEPENDENCY_TABLES.iter() {
+            if let Some(TomlValue::Table(dep_table)) = parent.get(*dep_table) {
+                for (name, dep) in dep_table {
+                    // e.g. `serde = "1.0"` or `serde = { version = "1.0", features = ["derive"] }`
+                    let (name, version) = match dep {
+                        TomlValue::String(v) => (name.as_str(), Some(v.clone())),
+                        TomlValue::Table(v) => (
+                            v.get("package").and_then(|p| p.as_str()).unwrap_or(name.as_str()),
+                            v.get("version").and_then(|v| v.as_str()).map(|v| v.to_owned()),
+                        ),
+                        _ => (name.as_str(), None),
+                    };
+                    deps.push((name.to_owned(), version));
+                }
+            }
+        }
+    }
+
+    Some(deps)
+}
+
+/// Returns `(name, version)` pairs from all dependency objects of package.json.
+fn parse_package_json(contents: &str) -> Option<Vec<(String, Option<String>)>> {
+    let manifest = match serde_json::from_str::<JsonValue>(contents) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Invalid JSON due to {}", e);
+            return None;
+        }
+    };
+
+    let mut deps = Vec::new();
+    for dep_object in NPM_DEPENDENCY_OBJECTS.iter() {
+        if let Some(JsonValue::Object(dep_object)) = manifest.get(*dep_object) {
+            for (name, version) in dep_object {
+                deps.push((name.clone(), version.as_str().map(|v| v.to_owned())));
+            }
+        }
+    }
+
+    Some(deps)
+}
+
+/// Returns `(name, version specifier)` pairs from requirements.txt, e.g. `requests[security]>=2.8.1 ; python_version<"3"`
+/// is returned as `("requests", ">=2.8.1")`. Options like `-r other.txt` and URLs are skipped.
+fn parse_requirements_txt(contents: &str) -> Vec<(String, Option<String>)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            // drop comments and environment markers
+            let line = line.split(" #").next()?.split(';').next()?.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('-') || line.contains("://") {
+                return None;
+            }
+
+            let name_end = line
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'))
+                .unwrap_or(line.len());
+            let (name, version) = line.split_at(name_end);
+            if name.is_empty() {
+                return None;
+            }
+
+            // skip extras, e.g. `[security]`
+            let version = match version.find(']') {
+                Some(idx) if version.starts_with('[') => &version[idx + 1..],
+                _ => version,
+            };
+            let version = version.trim();
+            let version = if version.is_empty() {
+                None
+            } else {
+                Some(version.to_owned())
+            };
+
+            Some((name.to_owned(), version))
+        })
+        .collect()
+}
+
+/// Returns `(module path, version)` pairs from `require` directives of go.mod in both forms,
+/// `require example.com/mod v1.2.3` and a `require ( ... )` block.
+fn parse_go_mod(contents: &str) -> Vec<(String, Option<String>)> {
+    let mut deps = Vec::new();
+    let mut in_require_block = false;
+
+    for line in contents.lines() {
+        // `// indirect` and other comments are not needed
+        let line = line.split("//").next().unwrap_or_default().trim();
+
+        let require = if in_require_block {
+            if line == ")" {
+                in_require_block = false;
+                continue;
+            }
+            line
+        } else if let Some(v) = line.strip_prefix("require") {
+            let v = v.trim();
+            if v == "(" {
+                in_require_block = true;
+                continue;
+            }
+            v
+        } else {
+            continue;
+        };
+
+        let mut parts = require.split_whitespace();
+        if let Some(module) = parts.next() {
+            deps.push((module.to_owned(), parts.next().map(|v| v.to_owned())));
+        }
+    }
+
+    deps
+}
+
+#[test]
+fn test_parse_cargo_toml() {
+    let cargo_toml = r#"
+[package]
+name = "demo"
+version = "0.1.0"
+
+[dependencies]
+regex = "1.5"
+serde = { version = "1.0", features = ["derive"] }
+
+[dev-dependencies]
+local_utils = { path = "../utils" }
+"#;
+
+    let pkgs = parse_manifest("demo/Cargo.toml", cargo_toml).unwrap();
+    assert_eq!(pkgs.len(), 3);
+    assert_eq!(pkgs[0].k, "local_utils");
+    assert!(pkgs[0].t.is_none());
+    assert_eq!(pkgs[1].k, "regex");
+    assert!(pkgs[1].t.as_ref().unwrap().contains("1.5"));
+    assert_eq!(pkgs[2].k, "serde");
+    assert!(pkgs[2].t.as_ref().unwrap().contains("1.0"));
+    assert!(pkgs.iter().all(|pkg| pkg.c == 1));
+
+    assert!(parse_manifest("Cargo.toml", "[dependencies").is_none());
+    assert!(parse_manifest("src/main.rs", cargo_toml).is_none());
+}
+
+#[test]
+fn test_parse_package_json() {
+    let package_json = r#"{
+  "name": "demo",
+  "dependencies": { "react": "^18.2.0", "lodash": "4.17.21" },
+  "devDependencies": { "jest": "^29.0.0", "lodash": "4.17.21" }
+}"#;
+
+    let pkgs = parse_manifest("web/package.json", package_json).unwrap();
+    let names = pkgs.iter().map(|pkg| pkg.k.as_str()).collect::<Vec<&str>>();
+    assert_eq!(names, vec!["jest", "lodash", "react"]);
+    assert!(pkgs[0].t.as_ref().unwrap().contains("^29.0.0"));
+    // declared as both, a prod and a dev dependency
+    assert_eq!(pkgs[1].c, 2);
+    assert_eq!(pkgs[2].c, 1);
+}
+
+#[test]
+fn test_parse_requirements_txt_and_go_mod() {
+    let requirements_txt = "# web\n-r base.txt\nrequests[security]>=2.8.1 ; python_version < \"3\"\nflask\n";
+    let pkgs = parse_requirements_txt(requirements_txt);
+    assert_eq!(
+        pkgs,
+        vec![
+            ("requests".to_owned(), Some(">=2.8.1".to_owned())),
+            ("flask".to_owned(), None)
+        ]
+    );
+
+    let go_mod = "module example.com/demo\n\ngo 1.19\n\nrequire github.com/pkg/errors v0.9.1\n\nrequire (\n\tgolang.org/x/net v0.7.0 // indirect\n)\n";
+    let pkgs = parse_go_mod(go_mod);
+    assert_eq!(
+        pkgs,
+        vec![
+            ("github.com/pkg/errors".to_owned(), Some("v0.9.1".to_owned())),
+            ("golang.org/x/net".to_owned(), Some("v0.7.0".to_owned()))
+        ]
+    );
+}
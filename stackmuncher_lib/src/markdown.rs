@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// A fenced code block in a Markdown file, e.g. ```` ```rust ````.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FencedBlock {
+    /// The first word of the info string in lower case, e.g. `rust`. It is empty if the fence has no info string.
+    pub tag: String,
+    /// Indices of the lines between the opening and the closing fences
+    pub lines: Range<usize>,
+}
+
+/// Returns all fenced code blocks with ```` ``` ```` or `~~~` fences. A block with no closing fence runs to the end
+/// of the file, same as in CommonMark. Indented code blocks are not included because they have no language.
+pub(crate) fn fenced_blocks<I, S>(lines: I) -> Vec<FencedBlock>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut blocks = Vec::new();
+    // the fence char, the fence length, the tag and the index of the first line inside the block
+    let mut open_fence: Option<(char, usize, String, usize)> = None;
+    let mut line_count = 0;
+
+    for (line_idx, line) in lines.into_iter().enumerate() {
+        line_count = line_idx + 1;
+        let line = line.as_ref().trim_start();
+        let (fence_char, fence_len) = match fence(line) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        match open_fence.take() {
+            Some((open_char, open_len, tag, first_line)) => {
+                // the closing fence must be at least as long as the opening one and have nothing after it
+                if fence_char == open_char && fence_len >= open_len && line[fence_len..].trim().is_empty() {
+                    blocks.push(FencedBlock {
+                        tag,
+                        lines: first_line..line_idx,
+                    });
+                } else {
+                    open_fence = Some((open_char, open_len, tag, first_line));
+                }
+            }
+            None => {
+                let tag = line[fence_len..]
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    // e.g. ```{r} or ```.py
+                    .trim_matches(|c| c == '{' || c == '}' || c == '.')
+                    .to_lowercase();
+                open_fence = Some((fence_char, fence_len, tag, line_idx + 1));
+            }
+        }
+    }
+
+    if let Some((_, _, tag, first_line)) = open_fence {
+        blocks.push(FencedBlock {
+            tag,
+            lines: first_line..line_count,
+        });
+    }
+
+    blocks
+}
+
+/// Returns the fence char and the number of fence chars at the start of the line, if it is a fence.
+fn fence(line: &str) -> Option<(char, usize)> {
+    let fence_char = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let fence_len = line.chars().take_while(|c| *c == fence_char).count();
+    if fence_len < 3 {
+        return None;
+    }
+    Some((fence_char, fence_len))
+}
+
+/// Returns the set of non-empty tags of all fenced blocks.
+pub(crate) fn fence_tags(blocks: &[FencedBlock]) -> HashSet<String> {
+    blocks
+        .iter()
+        .filter(|block| !block.tag.is_empty())
+        .map(|block| block.tag.clone())
+        .collect()
+}
+
+/// Returns the file extension for the language of a fence tag to look up the muncher, e.g. `rs` for `rust`.
+/// Tags that are not a known language name are returned as-is because they are often an extension, e.g. `py`.
+pub(crate) fn fence_tag_file_ext(tag: &str) -> &str {
+    match tag {
+        "rust" => "rs",
+        "bash" | "shell" | "zsh" | "shell-script" => "sh",
+        "python" | "python3" | "py3" => "py",
+        "javascript" | "node" => "js",
+        "typescript" => "ts",
+        "csharp" | "c#" => "cs",
+        "c++" => "cpp",
+        "ruby" => "rb",
+        "golang" => "go",
+        "kotlin" => "kt",
+        "powershell" | "pwsh" => "ps1",
+        "terraform" | "hcl" => "tf",
+        "postgresql" | "postgres" => "pgsql",
+        _ => tag,
+    }
+}
+
+#[test]
+fn test_fenced_blocks() {
+    let readme =
+        "# Demo\n\n```rust\nfn main() {}\n```\n\n~~~~ Bash\n```\necho hi\n~~~~\n\n```\nplain\n```\n```{r}\nx <- 1";
+    let blocks = fenced_blocks(readme.lines());
+    assert_eq!(
+        blocks,
+        vec![
+            FencedBlock {
+                tag: "rust".to_owned(),
+                lines: 3..4
+            },
+            // a shorter fence of another char is a part of the code
+            FencedBlock {
+                tag: "bash".to_owned(),
+                lines: 7..9
+            },
+            FencedBlock {
+                tag: String::new(),
+                lines: 12..13
+            },
+            // not closed
+            FencedBlock {
+                tag: "r".to_owned(),
+                lines: 15..16
+            },
+        ]
+    );
+
+    let tags = fence_tags(&blocks);
+    assert_eq!(tags.len(), 3);
+    assert!(!tags.contains(""));
+    assert_eq!(fence_tag_file_ext("rust"), "rs");
+    assert_eq!(fence_tag_file_ext("yaml"), "yaml");
+}
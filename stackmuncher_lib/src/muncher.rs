@@ -1,26 +1,84 @@
-use regex::Regex;
-use serde::Deserialize;
-use std::hash::{Hash, Hasher};
-use tracing::{error, trace};
-
-// ===================================================================
-// IMPORTANT: update the hashing function after adding any new members
-// ===================================================================
-#[derive(Deserialize, Clone, Debug)]
+use crate::report::LanguageCategory;
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::{error, trace, warn};
+
+// ==========================================================================
+// IMPORTANT: add any new rule-bearing members to `MuncherRules` for hashing
+// ==========================================================================
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Muncher {
     #[serde(default)]
     pub muncher_name: String,
     pub language: String,
+    /// The name of the muncher this one inherits rules from. The rules are merged in by the loader, see
+    /// `compose_muncher_contents` in `code_rules`.
+    pub extends: Option<String>,
     pub keywords: Option<Vec<String>>,
+    /// Count every `keywords` match on a line if true, e.g. 2 for `if a { if b {`.
+    /// Defaults to false, i.e. a keyword is counted once per line regardless of the number of matches.
+    pub count_all_keywords: Option<bool>,
     pub bracket_only: Option<Vec<String>>,
     pub line_comments: Option<Vec<String>>,
     pub inline_comments: Option<Vec<String>>,
     pub doc_comments: Option<Vec<String>>,
     pub block_comments_start: Option<Vec<String>>,
     pub block_comments_end: Option<Vec<String>>,
+    /// Block comments can be nested, e.g. `/* /* */ */` in Rust, and only end when all of them are closed.
+    /// Defaults to false, i.e. the first `block_comments_end` ends the comment.
+    pub nested_block_comments: Option<bool>,
+    /// Start of a multi-line string, e.g. `"""` in Python. Blank lines inside the string are counted as `string_lines`.
+    pub string_block_start: Option<Vec<String>>,
+    /// End of a multi-line string, one per `string_block_start` in the same order, e.g. `'''` only ends a string
+    /// opened with `'''`. Only the part of the line after `string_block_start` is checked on the opening line.
+    pub string_block_end: Option<Vec<String>>,
+    /// Regex for single-line string literals, e.g. `"(?:[^"\\]|\\.)*"`. Matching parts of the line are blanked out
+    /// before looking for comments, so that `"http://x"` or `"/* x */"` are not mistaken for comments.
+    pub string_literals: Option<Vec<String>>,
+    /// Regex for references to other modules, e.g. `use` or `import`. Only the `(?P<name>...)` capture group is
+    /// recorded if the regex has one, e.g. `std` from `use std::collections::HashMap;`. Otherwise all capture groups
+    /// joined with a space or the whole match if there are no groups.
     pub refs: Option<Vec<String>>,
+    /// Count every `refs` match on a line if true, e.g. 2 for `use a; use b;`. Defaults to false, same as `count_all_keywords`.
+    pub count_all_refs: Option<bool>,
+    /// Regex for package manager references with the same capture group rules as `refs`.
     pub packages: Option<Vec<String>>,
-    // REMEMBER TO ADD ANY NEW MEMBERS TO HASH TRAIT!!!
+    /// Count every `packages` match on a line if true. Defaults to false, same as `count_all_keywords`.
+    pub count_all_packages: Option<bool>,
+    /// Regex to extract the author from tech-debt markers in comments, e.g. `TODO(alice)`.
+    /// The 1st capture group is the author. Defaults to `Muncher::DEFAULT_TODO_AUTHORS` if None.
+    pub todo_authors: Option<Vec<String>>,
+    /// Regex for tech-debt markers in comments, e.g. `FIXME`. Every match is counted in `Tech.todo_comments`.
+    /// Defaults to `Muncher::DEFAULT_TODO_MARKERS` if None.
+    pub todo_markers: Option<Vec<String>>,
+    /// Regex to extract copyright years from the file header, e.g. `Copyright (c) 2018-2023`. The 1st capture group
+    /// is the first year and the optional 2nd group is the last year. Defaults to `Muncher::DEFAULT_COPYRIGHT` if None.
+    pub copyright: Option<Vec<String>>,
+    /// Regex for a code line that continues on the next line, e.g. `\` at the end of the line in C or Python.
+    /// The continuation is counted as code even if it looks like a comment, a bracket or a blank line.
+    pub line_continuation: Option<Vec<String>>,
+    /// Lines matching `inline_comments` are also counted as code lines if true, e.g. `x = 1; // set x`.
+    /// Defaults to false, i.e. such lines are only counted as `inline_comments`.
+    pub inline_comments_count_as_code: Option<bool>,
+    /// Signature regex per embedded language for files where languages interleave line by line, e.g. HTML in ERB.
+    /// Code lines matching a signature are attributed to that language instead of `language`.
+    /// Languages are checked in a-z order and the first match wins.
+    pub line_languages: Option<BTreeMap<String, Vec<String>>>,
+    /// Files with the average line length over `Config.max_avg_line_length` are not munched if true, e.g. minified CSS.
+    /// Defaults to true for `LanguageCategory::Programming` languages only, so that docs with a paragraph per line
+    /// and data files are munched regardless of the line length.
+    pub check_minified: Option<bool>,
+    /// Regex for the first few lines of files that belong to this muncher regardless of their name, e.g. a shebang.
+    /// Only checked for files with no muncher matching their name. It does not change how files are munched.
+    pub content_signatures: Option<Vec<String>>,
+    /// Exact file names handled by this muncher, e.g. `Dockerfile`. Also matches names with an extra extension,
+    /// e.g. `Dockerfile.prod`. Checked case-insensitively for files with no matching file-type rules.
+    pub file_names: Option<Vec<String>>,
+    /// File extensions without the leading dot handled by this muncher, e.g. `mk` or `erb.tt`.
+    /// Checked case-insensitively for files with no matching file-type rules.
+    pub extensions: Option<Vec<String>>,
+    // Members that change how files are munched must be added to MuncherRules
 
     // Regex section is compiled once from the above properties
     #[serde(skip)]
@@ -36,6 +94,12 @@ pub struct Muncher {
     #[serde(skip)]
     pub block_comments_end_regex: Option<Vec<Regex>>,
     #[serde(skip)]
+    pub string_block_start_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub string_block_end_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub string_literals_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
     pub refs_regex: Option<Vec<Regex>>,
     #[serde(skip)]
     pub packages_regex: Option<Vec<Regex>>,
@@ -43,6 +107,35 @@ pub struct Muncher {
     pub blank_line_regex: Option<Vec<Regex>>,
     #[serde(skip)]
     pub keywords_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub todo_authors_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub todo_markers_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub copyright_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub line_continuation_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub line_languages_regex: Option<Vec<(String, Vec<Regex>)>>,
+    #[serde(skip)]
+    pub content_signatures_regex: Option<Vec<Regex>>,
+    // Sets of the line classification regex above to test a line against all regex of a category in a single pass
+    #[serde(skip)]
+    pub bracket_only_regex_set: Option<RegexSet>,
+    #[serde(skip)]
+    pub line_comments_regex_set: Option<RegexSet>,
+    #[serde(skip)]
+    pub inline_comments_regex_set: Option<RegexSet>,
+    #[serde(skip)]
+    pub doc_comments_regex_set: Option<RegexSet>,
+    #[serde(skip)]
+    pub block_comments_start_regex_set: Option<RegexSet>,
+    #[serde(skip)]
+    pub block_comments_end_regex_set: Option<RegexSet>,
+    #[serde(skip)]
+    pub blank_line_regex_set: Option<RegexSet>,
+    #[serde(skip)]
+    pub line_continuation_regex_set: Option<RegexSet>,
     /// Set to true for newly added munchers to help upstream code
     /// identify them and share with other threads
     #[serde(skip)]
@@ -50,9 +143,36 @@ pub struct Muncher {
     /// A short hash of the muncher rules to detect a change for reprocessing
     #[serde(skip)]
     pub muncher_hash: u64,
+    /// A short hash of the muncher file contents to detect a change in the rules without parsing them,
+    /// see `CodeRules::load_muncher_cache`
+    #[serde(skip)]
+    pub contents_hash: u64,
 }
 
 impl Muncher {
+    /// The max size of a single compiled regex in bytes. Munchers may come from untrusted sources and a regex
+    /// with huge repetition counts can consume a lot of memory at compile time.
+    pub const REGEX_SIZE_LIMIT: usize = 2 * (1 << 20);
+    /// The max size of the lazy DFA cache per regex in bytes.
+    pub const REGEX_DFA_SIZE_LIMIT: usize = 2 * (1 << 20);
+    /// Matches the `TODO(name)` convention used for attributing tech debt, e.g. `// TODO(alice): fix this`
+    pub const DEFAULT_TODO_AUTHORS: &'static str = r"\bTODO\(\s*([^()\s]+)\s*\)";
+    /// Matches the most common tech-debt markers, e.g. `// TODO: fix this` or `/* FIXME */`
+    pub const DEFAULT_TODO_MARKERS: &'static str = r"\b(?:TODO|FIXME|HACK)\b";
+    /// Matches `Copyright 2018`, `Copyright (c) 2018-2023`, `Copyright © 2018 - 2023` and similar
+    pub const DEFAULT_COPYRIGHT: &'static str =
+        r"(?i)\bcopyright\b\s*(?:\(c\)|©)?\s*((?:19|20)\d{2})(?:\s*[-–]\s*((?:19|20)\d{2}))?";
+    /// The number of lines at the top of the file searched for copyright years
+    pub const COPYRIGHT_HEADER_LINES: usize = 20;
+    /// The number of lines at the top of the file checked against `content_signatures`
+    pub const CONTENT_SIGNATURE_LINES: usize = 5;
+    /// The number of lines at the top of the file checked against `Config.generated_markers`
+    pub const GENERATED_MARKER_LINES: usize = 10;
+    /// The language of munchers for files with fenced code blocks, see `Config.munch_fenced_code_blocks`
+    pub const MARKDOWN_LANGUAGE: &'static str = "Markdown";
+    /// The language of the muncher for Jupyter notebooks with no known language of the code cells
+    pub const JUPYTER_LANGUAGE: &'static str = "Jupyter";
+
     /// Create a new instance from the muncher file contents.
     /// Returns None if there was a problem loading it
     pub fn new(muncher_contents: &str, muncher_name: &String) -> Option<Self> {
@@ -71,20 +191,24 @@ impl Muncher {
         conf.brand_new = true;
 
         // hash the muncher to ID the rules and avoid reprocessing
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        conf.hash(&mut hasher);
-        conf.muncher_hash = hasher.finish();
+        conf.muncher_hash = conf.rules_hash();
+        conf.contents_hash = crate::utils::hash_sha1_u64(muncher_contents.as_bytes());
 
         // compile all regex strings
         if conf.compile_all_regex().is_err() {
             return None;
         }
 
+        // let the muncher author know about rules that can never match
+        for lint_warning in conf.lint() {
+            warn!("{}", lint_warning);
+        }
+
         Some(conf)
     }
 
     /// Compiles regex strings.
-    fn compile_all_regex(&mut self) -> Result<(), ()> {
+    pub(crate) fn compile_all_regex(&mut self) -> Result<(), ()> {
         trace!("Compiling regex for {}", self.muncher_name);
 
         // resets to `false` if any of the regex statements failed to compile
@@ -128,6 +252,35 @@ impl Muncher {
             }
         }
 
+        if let Some(v) = self.string_block_start.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.string_block_start_regex, s);
+            }
+        }
+
+        if let Some(v) = self.string_block_end.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.string_block_end_regex, s);
+            }
+        }
+
+        // every start of a multi-line string needs its own end
+        let string_block_start_count = self.string_block_start.as_ref().map(|v| v.len()).unwrap_or_default();
+        let string_block_end_count = self.string_block_end.as_ref().map(|v| v.len()).unwrap_or_default();
+        if string_block_start_count != string_block_end_count {
+            error!(
+                "{} has {} string_block_start and {} string_block_end regex",
+                self.muncher_name, string_block_start_count, string_block_end_count
+            );
+            compilation_success = false;
+        }
+
+        if let Some(v) = self.string_literals.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.string_literals_regex, s);
+            }
+        }
+
         if let Some(v) = self.refs.as_ref() {
             for s in v {
                 compilation_success &= Muncher::add_regex_to_list(&mut self.refs_regex, s);
@@ -146,9 +299,90 @@ impl Muncher {
             }
         }
 
+        if let Some(v) = self.line_continuation.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.line_continuation_regex, s);
+            }
+        }
+
+        if let Some(line_languages) = self.line_languages.as_ref() {
+            let mut line_languages_regex: Vec<(String, Vec<Regex>)> = Vec::new();
+            for (language, signatures) in line_languages {
+                let mut signatures_regex: Option<Vec<Regex>> = None;
+                for s in signatures {
+                    compilation_success &= Muncher::add_regex_to_list(&mut signatures_regex, s);
+                }
+                if let Some(signatures_regex) = signatures_regex {
+                    line_languages_regex.push((language.clone(), signatures_regex));
+                }
+            }
+            self.line_languages_regex = Some(line_languages_regex);
+        }
+
+        // most munchers rely on the default convention for attributing TODOs
+        match self.todo_authors.as_ref() {
+            Some(v) => {
+                for s in v {
+                    compilation_success &= Muncher::add_regex_to_list(&mut self.todo_authors_regex, s);
+                }
+            }
+            None => {
+                compilation_success &= Muncher::add_regex_to_list(
+                    &mut self.todo_authors_regex,
+                    &Muncher::DEFAULT_TODO_AUTHORS.to_string(),
+                );
+            }
+        }
+
+        match self.todo_markers.as_ref() {
+            Some(v) => {
+                for s in v {
+                    compilation_success &= Muncher::add_regex_to_list(&mut self.todo_markers_regex, s);
+                }
+            }
+            None => {
+                compilation_success &= Muncher::add_regex_to_list(
+                    &mut self.todo_markers_regex,
+                    &Muncher::DEFAULT_TODO_MARKERS.to_string(),
+                );
+            }
+        }
+
+        match self.copyright.as_ref() {
+            Some(v) => {
+                for s in v {
+                    compilation_success &= Muncher::add_regex_to_list(&mut self.copyright_regex, s);
+                }
+            }
+            None => {
+                compilation_success &=
+                    Muncher::add_regex_to_list(&mut self.copyright_regex, &Muncher::DEFAULT_COPYRIGHT.to_string());
+            }
+        }
+
+        if let Some(v) = self.content_signatures.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.content_signatures_regex, s);
+            }
+        }
+
         // empty strings should have the same regex, but this may change - odd one out
         compilation_success &= Muncher::add_regex_to_list(&mut self.blank_line_regex, &r"^\s*$".to_string());
 
+        // every line is checked against these categories, so they are combined into sets
+        compilation_success &= Muncher::build_regex_set(&self.bracket_only_regex, &mut self.bracket_only_regex_set);
+        compilation_success &= Muncher::build_regex_set(&self.line_comments_regex, &mut self.line_comments_regex_set);
+        compilation_success &=
+            Muncher::build_regex_set(&self.inline_comments_regex, &mut self.inline_comments_regex_set);
+        compilation_success &= Muncher::build_regex_set(&self.doc_comments_regex, &mut self.doc_comments_regex_set);
+        compilation_success &=
+            Muncher::build_regex_set(&self.block_comments_start_regex, &mut self.block_comments_start_regex_set);
+        compilation_success &=
+            Muncher::build_regex_set(&self.block_comments_end_regex, &mut self.block_comments_end_regex_set);
+        compilation_success &= Muncher::build_regex_set(&self.blank_line_regex, &mut self.blank_line_regex_set);
+        compilation_success &=
+            Muncher::build_regex_set(&self.line_continuation_regex, &mut self.line_continuation_regex_set);
+
         // panic if there were compilation errors
         if compilation_success {
             return Ok(());
@@ -158,12 +392,108 @@ impl Muncher {
         }
     }
 
+    /// Returns a list of warnings about regex that can never match because a regex from a category checked earlier
+    /// in `process_file` matches the same lines, e.g. `line_comments` matching all `doc_comments` lines.
+    /// The check is done by matching a set of representative comment lines, so it can only detect obvious cases.
+    pub fn lint(&self) -> Vec<String> {
+        // the categories are listed in the same order as they are evaluated in `process_file`
+        let categories = [
+            ("block_comments_start", &self.block_comments_start_regex),
+            ("doc_comments", &self.doc_comments_regex),
+            ("line_comments", &self.line_comments_regex),
+            ("inline_comments", &self.inline_comments_regex),
+            ("bracket_only", &self.bracket_only_regex),
+        ];
+
+        let mut warnings: Vec<String> = Vec::new();
+
+        for (idx, (category, regex_list)) in categories.iter().enumerate() {
+            for regex in regex_list.iter().flatten() {
+                let matched_samples = LINT_SAMPLES
+                    .iter()
+                    .filter(|sample| regex.is_match(sample))
+                    .collect::<Vec<&&str>>();
+
+                // check all the categories evaluated before this one
+                for (earlier_category, earlier_regex_list) in categories.iter().take(idx) {
+                    for earlier_regex in earlier_regex_list.iter().flatten() {
+                        let is_shadowed = if matched_samples.is_empty() {
+                            // no samples to go by, so only identical patterns can be detected
+                            earlier_regex.as_str() == regex.as_str()
+                        } else {
+                            matched_samples.iter().all(|sample| earlier_regex.is_match(sample))
+                        };
+
+                        if is_shadowed {
+                            warnings.push(format!(
+                                "{}: {} regex `{}` is shadowed by {} regex `{}`",
+                                self.muncher_name, category, regex, earlier_category, earlier_regex
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        warnings
+    }
+
+    /// Returns the name of the embedded language whose signature matches the code line, if any.
+    pub fn line_language(&self, line: &str) -> Option<&String> {
+        self.line_languages_regex
+            .as_ref()?
+            .iter()
+            .find(|(_, signatures)| signatures.iter().any(|regex| regex.is_match(line)))
+            .map(|(language, _)| language)
+    }
+
+    /// Returns `check_minified` or its default for the language of the muncher.
+    pub fn checks_minified(&self) -> bool {
+        self.check_minified
+            .unwrap_or_else(|| LanguageCategory::from_language(&self.language) == LanguageCategory::Programming)
+    }
+
+    /// Combines the compiled regex from `list` into a `RegexSet` within the same size limits per regex.
+    /// The set matches a line if any of the regex in the list does. Returns FALSE if the set could not be built.
+    pub fn build_regex_set(list: &Option<Vec<Regex>>, set: &mut Option<RegexSet>) -> bool {
+        let list = match list {
+            Some(v) => v,
+            None => return true,
+        };
+
+        match RegexSetBuilder::new(list.iter().map(|regex| regex.as_str()))
+            .size_limit(Muncher::REGEX_SIZE_LIMIT * list.len())
+            .dfa_size_limit(Muncher::REGEX_DFA_SIZE_LIMIT * list.len())
+            .build()
+        {
+            Ok(v) => {
+                set.replace(v);
+                true
+            }
+            Err(e) => {
+                error!("Failed to build a regex set from {:?} with {}", list, e);
+                false
+            }
+        }
+    }
+
     /// Adds the `regex` to the supplied `list`. Creates an instance of Vec<Regex> on the first insert.
-    /// Always returns Some(). Returns FALSE on regex compilation error.
+    /// Always returns Some(). Returns FALSE on regex compilation error, including regex exceeding the size limits.
     pub fn add_regex_to_list(list: &mut Option<Vec<Regex>>, regex: &String) -> bool {
-        // try to compile the regex
-        let compiled_regex = match Regex::new(regex) {
+        // try to compile the regex within the size limits
+        let compiled_regex = match RegexBuilder::new(regex)
+            .size_limit(Muncher::REGEX_SIZE_LIMIT)
+            .dfa_size_limit(Muncher::REGEX_DFA_SIZE_LIMIT)
+            .build()
+        {
             Ok(r) => r,
+            Err(regex::Error::CompiledTooBig(limit)) => {
+                error!(
+                    "Failed to compile regex {}: the compiled size exceeds the limit of {} bytes",
+                    regex, limit
+                );
+                return false;
+            }
             Err(e) => {
                 error!("Failed to compile regex {} with {}", regex, e);
                 return false;
@@ -181,18 +511,257 @@ impl Muncher {
     }
 }
 
-impl Hash for Muncher {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.muncher_name.hash(state);
-        self.language.hash(state);
-        self.keywords.hash(state);
-        self.bracket_only.hash(state);
-        self.line_comments.hash(state);
-        self.inline_comments.hash(state);
-        self.doc_comments.hash(state);
-        self.block_comments_start.hash(state);
-        self.block_comments_end.hash(state);
-        self.refs.hash(state);
-        self.packages.hash(state);
+/// Typical comment lines from different languages used by `Muncher::lint` to detect shadowed regex.
+const LINT_SAMPLES: [&str; 14] = [
+    "/// Returns the number of lines in the file",
+    "//! A module with some docs in it",
+    "/** A doc block comment in Java or JS",
+    "/* A block comment in C-like languages",
+    "// A line comment in C-like languages",
+    "    // An indented line comment here",
+    "# A line comment in Python or Shell",
+    "## A doc comment in some languages",
+    "-- A line comment in SQL or Haskell",
+    "<!-- An HTML or XML comment -->",
+    "\"\"\" A Python docstring line",
+    "; A line comment in INI or assembler",
+    "x = 1 // An inline comment after code",
+    "x = 1 # An inline comment after code",
+];
+
+/// A canonical form of the rule-bearing members of `Muncher` used for hashing. Members that do not change
+/// the munching results must not be added here or they will invalidate cached reports for no reason.
+/// The members are listed in a-z order to make the serialized form independent of the order in `Muncher`.
+/// Optional members added after the first release are skipped if None to keep the hashes of munchers without them
+/// unchanged. Members with a default in `Muncher` are hashed with their effective patterns instead, so that changing
+/// the default invalidates the results of munchers that rely on it.
+#[derive(Serialize)]
+struct MuncherRules<'a> {
+    block_comments_end: &'a Option<Vec<String>>,
+    block_comments_start: &'a Option<Vec<String>>,
+    bracket_only: &'a Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    check_minified: Option<bool>,
+    copyright: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count_all_keywords: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count_all_packages: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count_all_refs: Option<bool>,
+    doc_comments: &'a Option<Vec<String>>,
+    inline_comments: &'a Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inline_comments_count_as_code: Option<bool>,
+    keywords: &'a Option<Vec<String>>,
+    language: &'a String,
+    line_comments: &'a Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line_continuation: Option<&'a Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line_languages: Option<&'a BTreeMap<String, Vec<String>>>,
+    muncher_name: &'a String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nested_block_comments: Option<bool>,
+    packages: &'a Option<Vec<String>>,
+    refs: &'a Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    string_block_end: Option<&'a Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    string_block_start: Option<&'a Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    string_literals: Option<&'a Vec<String>>,
+    todo_authors: Vec<&'a str>,
+    todo_markers: Vec<&'a str>,
+}
+
+impl Muncher {
+    /// Returns a short hash of the rule-bearing members serialized as JSON. Unlike `std::hash::Hash` it is stable
+    /// across Rust versions and does not change if unrelated members are added to the struct.
+    pub(crate) fn rules_hash(&self) -> u64 {
+        let rules = MuncherRules {
+            block_comments_end: &self.block_comments_end,
+            block_comments_start: &self.block_comments_start,
+            bracket_only: &self.bracket_only,
+            check_minified: self.check_minified,
+            copyright: effective_patterns(&self.copyright, Muncher::DEFAULT_COPYRIGHT),
+            count_all_keywords: self.count_all_keywords,
+            count_all_packages: self.count_all_packages,
+            count_all_refs: self.count_all_refs,
+            doc_comments: &self.doc_comments,
+            inline_comments: &self.inline_comments,
+            inline_comments_count_as_code: self.inline_comments_count_as_code,
+            keywords: &self.keywords,
+            language: &self.language,
+            line_comments: &self.line_comments,
+            line_continuation: self.line_continuation.as_ref(),
+            line_languages: self.line_languages.as_ref(),
+            muncher_name: &self.muncher_name,
+            nested_block_comments: self.nested_block_comments,
+            packages: &self.packages,
+            refs: &self.refs,
+            string_block_end: self.string_block_end.as_ref(),
+            string_block_start: self.string_block_start.as_ref(),
+            string_literals: self.string_literals.as_ref(),
+            todo_authors: effective_patterns(&self.todo_authors, Muncher::DEFAULT_TODO_AUTHORS),
+            todo_markers: effective_patterns(&self.todo_markers, Muncher::DEFAULT_TODO_MARKERS),
+        };
+        let rules = serde_json::to_vec(&rules).expect("Cannot serialize muncher rules. It's a bug.");
+
+        crate::utils::hash_sha1_u64(&rules)
     }
 }
+
+/// Returns the patterns the muncher compiles for a member with a default, i.e. `patterns` or the `default` if None.
+fn effective_patterns<'a>(patterns: &'a Option<Vec<String>>, default: &'static str) -> Vec<&'a str> {
+    match patterns {
+        Some(v) => v.iter().map(|s| s.as_str()).collect(),
+        None => vec![default],
+    }
+}
+
+/// Returns the first of `munchers` with `content_signatures` matching any of the first
+/// `Muncher::CONTENT_SIGNATURE_LINES` lines of a file, e.g. `#!/bin/bash` for a script with no extension.
+pub fn detect_muncher_by_content<'a, I>(first_lines: &[String], munchers: I) -> Option<&'a Muncher>
+where
+    I: IntoIterator<Item = &'a Muncher>,
+{
+    let first_lines = &first_lines[..first_lines.len().min(Muncher::CONTENT_SIGNATURE_LINES)];
+
+    munchers.into_iter().find(|muncher| {
+        muncher
+            .content_signatures_regex
+            .iter()
+            .flatten()
+            .any(|regex| first_lines.iter().any(|line| regex.is_match(line)))
+    })
+}
+
+/// Returns the first of `munchers` with a `file_names` entry equal to the file name of `path`, then the first one with
+/// the longest matching `extensions` entry, then the first one with a `file_names` entry equal to the part of the file
+/// name before the first dot, e.g. `Dockerfile` for `Dockerfile.prod`. The comparison is case-insensitive.
+pub fn muncher_for_path<'a, I>(path: &str, munchers: I) -> Option<&'a Muncher>
+where
+    I: IntoIterator<Item = &'a Muncher>,
+{
+    let file_name = path.rsplit(['/', '\\']).next().unwrap_or_default().to_lowercase();
+    if file_name.is_empty() {
+        return None;
+    }
+    let munchers = munchers.into_iter().collect::<Vec<&Muncher>>();
+    let has_name = |names: &Option<Vec<String>>, name: &str| names.iter().flatten().any(|v| v.to_lowercase() == name);
+
+    if let Some(muncher) = munchers
+        .iter()
+        .find(|muncher| has_name(&muncher.file_names, &file_name))
+    {
+        return Some(muncher);
+    }
+
+    // multi-part extensions are more specific, e.g. `erb.tt` vs `tt`
+    for (idx, _) in file_name.match_indices('.') {
+        let ext = &file_name[idx + 1..];
+        if let Some(muncher) = munchers.iter().find(|muncher| has_name(&muncher.extensions, ext)) {
+            return Some(muncher);
+        }
+    }
+
+    let (stem, _) = file_name.split_once('.')?;
+    munchers.into_iter().find(|muncher| has_name(&muncher.file_names, stem))
+}
+
+/// The hash must not change unless the rules change. A failure here means all cached reports will be reprocessed.
+#[test]
+fn test_rules_hash_is_pinned() {
+    let muncher = Muncher::new(r#"{"language": "Test", "keywords": ["\\bfn\\b"]}"#, &"test".to_owned()).unwrap();
+    assert_eq!(muncher.muncher_hash, 2412892282053662276);
+
+    // members with a default are hashed with the patterns in effect
+    let with_default = serde_json::json!({
+        "language": "Test",
+        "keywords": ["\\bfn\\b"],
+        "todo_markers": [Muncher::DEFAULT_TODO_MARKERS]
+    });
+    let with_default = Muncher::new(&with_default.to_string(), &"test".to_owned()).unwrap();
+    assert_eq!(with_default.muncher_hash, muncher.muncher_hash);
+}
+
+#[test]
+fn test_detect_muncher_by_content() {
+    let shell = Muncher::new(
+        r#"{"language": "Shell", "content_signatures": ["^#!\\s*/(usr/)?bin/(env\\s+)?(ba)?sh\\b"]}"#,
+        &"shell".to_owned(),
+    )
+    .unwrap();
+    let php = Muncher::new(r#"{"language": "PHP", "content_signatures": ["^<\\?php\\b"]}"#, &"php".to_owned()).unwrap();
+    let text = Muncher::new(r#"{"language": "Text"}"#, &"text".to_owned()).unwrap();
+    let munchers = vec![text, shell, php];
+
+    // a script called `deploy`
+    let deploy = vec!["#!/bin/bash".to_owned(), "set -e".to_owned()];
+    assert_eq!(detect_muncher_by_content(&deploy, &munchers).map(|m| m.language.as_str()), Some("Shell"));
+
+    // a page called `index`
+    let index = vec!["<?php".to_owned(), "echo 'hi';".to_owned()];
+    assert_eq!(detect_muncher_by_content(&index, &munchers).map(|m| m.language.as_str()), Some("PHP"));
+
+    // the signature has to be near the top of the file
+    let late = [vec![String::new(); Muncher::CONTENT_SIGNATURE_LINES], index].concat();
+    assert!(detect_muncher_by_content(&late, &munchers).is_none());
+}
+
+#[test]
+fn test_muncher_for_path() {
+    let docker = Muncher::new(r#"{"language": "Docker", "file_names": ["Dockerfile"]}"#, &"docker".to_owned()).unwrap();
+    let make = Muncher::new(
+        r#"{"language": "Make", "file_names": ["Makefile"], "extensions": ["makefile", "mk"]}"#,
+        &"make".to_owned(),
+    )
+    .unwrap();
+    let munchers = vec![docker, make];
+    let muncher_name = |path: &str| muncher_for_path(path, &munchers).map(|m| m.muncher_name.as_str());
+
+    assert_eq!(muncher_name("Dockerfile"), Some("docker"));
+    assert_eq!(muncher_name("deploy/Dockerfile.prod"), Some("docker"));
+    assert_eq!(muncher_name("build.Makefile"), Some("make"));
+    assert_eq!(muncher_name("src\\makefile"), Some("make"));
+    assert_eq!(muncher_name("rules.mk"), Some("make"));
+    assert_eq!(muncher_name("src/main.rs"), None);
+    assert_eq!(muncher_name("Dockerfiles/app.conf"), None);
+}
+
+#[test]
+fn test_oversized_regex_is_rejected() {
+    // the compiled size grows with the product of the repetition counts
+    let oversized = r"(\w+\s*){1000}{1000}".to_owned();
+    let mut list = None;
+    assert!(!Muncher::add_regex_to_list(&mut list, &oversized));
+    assert!(list.is_none());
+    let json = serde_json::json!({"language": "Test", "refs": [oversized]}).to_string();
+    assert!(Muncher::new(&json, &"test".to_owned()).is_none());
+
+    // a classic catastrophic backtracking pattern compiles and runs in linear time with this regex engine
+    assert!(Muncher::add_regex_to_list(&mut list, &r"^(a+)+$".to_owned()));
+    let adversarial = ["a".repeat(100_000), "!".to_owned()].concat();
+    assert!(!list.unwrap()[0].is_match(&adversarial));
+}
+
+#[test]
+fn test_lint() {
+    let lint = |json: &str| Muncher::new(json, &"test".to_owned()).unwrap().lint();
+
+    assert!(lint(r#"{"language": "Test", "doc_comments": ["^\\s*///"], "line_comments": ["^\\s*//"]}"#).is_empty());
+
+    // doc comments are checked first and take all the line comments
+    assert_eq!(
+        lint(r#"{"language": "Test", "doc_comments": ["^\\s*//"], "line_comments": ["^\\s*//[^/]"]}"#),
+        vec!["test: line_comments regex `^\\s*//[^/]` is shadowed by doc_comments regex `^\\s*//`"]
+    );
+
+    // a pattern that matches none of the samples is only reported if it is identical to an earlier one
+    assert_eq!(
+        lint(r#"{"language": "Test", "line_comments": ["^REM\\b"], "inline_comments": ["^REM\\b"]}"#).len(),
+        1
+    );
+    assert!(lint(r#"{"language": "Test", "line_comments": ["^REM\\b"], "inline_comments": ["^rem\\b"]}"#).is_empty());
+}
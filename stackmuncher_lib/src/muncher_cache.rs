@@ -0,0 +1,142 @@
+use crate::muncher::Muncher;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// A cache entry with the hashes of the muncher rules and file contents at the time it was cached.
+#[derive(Serialize, Deserialize)]
+struct CachedMuncher {
+    muncher_hash: u64,
+    contents_hash: u64,
+    muncher: Muncher,
+}
+
+/// Reasons for the muncher cache not being saved.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MuncherCacheError {
+    /// The munchers could not be serialized into JSON
+    #[error("Cannot serialize the muncher cache: {0}")]
+    Serialize(String),
+    /// The cache folder does not exist and could not be created
+    #[error("Cannot create muncher cache folder {0}")]
+    CreateDir(String),
+    /// The cache file could not be written
+    #[error("Cannot save muncher cache {0}")]
+    Write(String),
+}
+
+impl Muncher {
+    /// The name of the file with all cached munchers inside the cache folder.
+    pub const CACHE_FILE_NAME: &'static str = "munchers.cache.json";
+
+    /// Saves `munchers` into a single cache file in `dir` with their raw regex strings and hashes.
+    /// Compiled regex cannot be saved and are recompiled by `load_cached`.
+    pub fn write_cache<'a, I>(dir: &Path, munchers: I) -> Result<(), MuncherCacheError>
+    where
+        I: IntoIterator<Item = &'a Muncher>,
+    {
+        let cache = munchers
+            .into_iter()
+            .map(|muncher| {
+                (
+                    muncher.muncher_name.clone(),
+                    CachedMuncher {
+                        muncher_hash: muncher.muncher_hash,
+                        contents_hash: muncher.contents_hash,
+                        muncher: muncher.clone(),
+                    },
+                )
+            })
+            .collect::<BTreeMap<String, CachedMuncher>>();
+
+        let contents = serde_json::to_vec(&cache).map_err(|e| MuncherCacheError::Serialize(e.to_string()))?;
+
+        fs::create_dir_all(dir)
+            .map_err(|e| MuncherCacheError::CreateDir(format!("{} due to {}", dir.to_string_lossy(), e)))?;
+
+        let path = dir.join(Muncher::CACHE_FILE_NAME);
+        fs::write(&path, contents)
+            .map_err(|e| MuncherCacheError::Write(format!("{} due to {}", path.to_string_lossy(), e)))?;
+
+        info!("Saved {} munchers to {}", cache.len(), path.to_string_lossy());
+        Ok(())
+    }
+
+    /// Loads munchers saved by `write_cache` from `dir` keyed by muncher name. Parsing, hashing and linting of the
+    /// rules is skipped, but the regex are compiled. Entries with rules that no longer match their cached hash are
+    /// dropped. Callers should compare `contents_hash` with the hash of the current muncher file to detect stale entries.
+    /// Returns an empty map if there is no cache or it cannot be read.
+    pub fn load_cached(dir: &Path) -> BTreeMap<String, Muncher> {
+        let path = dir.join(Muncher::CACHE_FILE_NAME);
+        if !path.exists() {
+            debug!("No muncher cache at {}", path.to_string_lossy());
+            return BTreeMap::new();
+        }
+
+        let cache = match fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|v| serde_json::from_slice::<BTreeMap<String, CachedMuncher>>(&v).map_err(|e| e.to_string()))
+        {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Cannot load muncher cache {} due to {}", path.to_string_lossy(), e);
+                return BTreeMap::new();
+            }
+        };
+
+        cache
+            .into_iter()
+            .filter_map(|(muncher_name, cached)| {
+                let mut muncher = cached.muncher;
+                if muncher.rules_hash() != cached.muncher_hash {
+                    warn!("Stale muncher cache entry for {}", muncher_name);
+                    return None;
+                }
+                if muncher.compile_all_regex().is_err() {
+                    return None;
+                }
+                muncher.muncher_hash = cached.muncher_hash;
+                muncher.contents_hash = cached.contents_hash;
+                muncher.brand_new = true;
+                Some((muncher_name, muncher))
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_muncher_cache() {
+    let dir = std::env::temp_dir().join(format!("stm_muncher_cache_{}", uuid::Uuid::new_v4()));
+    let muncher_json = |keyword: &str| {
+        serde_json::json!({
+            "language": "Test",
+            "keywords": [keyword],
+            "line_comments": ["^\\s*//"],
+        })
+        .to_string()
+    };
+    let muncher = Muncher::new(&muncher_json("\\bfn\\b"), &"test".to_owned()).unwrap();
+
+    Muncher::write_cache(&dir, vec![&muncher]).unwrap();
+    let cached = Muncher::load_cached(&dir);
+    let cached_muncher = cached.get("test").unwrap();
+    assert_eq!(cached_muncher.muncher_hash, muncher.muncher_hash);
+    assert!(cached_muncher.line_comments_regex.as_ref().unwrap()[0].is_match("// comment"));
+
+    // a change in a single keyword makes the cached entry stale
+    let changed_muncher = Muncher::new(&muncher_json("\\bfunc\\b"), &"test".to_owned()).unwrap();
+    assert_ne!(changed_muncher.muncher_hash, cached_muncher.muncher_hash);
+
+    // an entry edited after it was cached is dropped
+    let path = dir.join(Muncher::CACHE_FILE_NAME);
+    let contents = fs::read_to_string(&path)
+        .unwrap()
+        .replace("\\\\bfn\\\\b", "\\\\bfunc\\\\b");
+    fs::write(&path, contents).unwrap();
+    assert!(Muncher::load_cached(&dir).is_empty());
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,151 @@
+use serde_json::Value as JsonValue;
+use tracing::{debug, warn};
+
+pub const NOTEBOOK_FILE_EXT: &str = ".ipynb";
+
+/// The code of a Jupyter notebook without the JSON wrapper.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notebook {
+    /// The extension of files in the notebook language with the leading `.`, e.g. `.py`.
+    /// It is None if the notebook has no language metadata.
+    pub file_extension: Option<String>,
+    /// The source of all code cells joined into one file with every cell starting on a new line.
+    /// Markdown and raw cells are not included.
+    pub code: String,
+}
+
+/// Returns true if the file name has the `.ipynb` extension.
+pub fn is_notebook(file_name: &str) -> bool {
+    file_name.to_lowercase().ends_with(NOTEBOOK_FILE_EXT)
+}
+
+/// Extracts the code cells and the language from the notebook JSON in nbformat 4.
+/// Returns None if the contents are not valid JSON or have no list of cells.
+pub fn parse_notebook(contents: &str) -> Option<Notebook> {
+    let notebook = match serde_json::from_str::<JsonValue>(contents) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Invalid notebook JSON due to {}", e);
+            return None;
+        }
+    };
+
+    let cells = notebook.get("cells")?.as_array()?;
+    let mut code = String::new();
+    let mut code_cells = 0;
+    for cell in cells {
+        if cell.get("cell_type").and_then(|v| v.as_str()) != Some("code") {
+            continue;
+        }
+
+        // the source is either a string or a list of lines with their line endings
+        let cell_code = match cell.get("source") {
+            Some(JsonValue::String(v)) => v.clone(),
+            Some(JsonValue::Array(v)) => v.iter().filter_map(|line| line.as_str()).collect::<String>(),
+            _ => continue,
+        };
+        if cell_code.is_empty() {
+            continue;
+        }
+
+        code.push_str(&cell_code);
+        if !cell_code.ends_with('\n') {
+            code.push('\n');
+        }
+        code_cells += 1;
+    }
+
+    let file_extension = notebook_file_extension(&notebook);
+    debug!("Notebook code cells: {}, language: {:?}", code_cells, file_extension);
+
+    Some(Notebook { file_extension, code })
+}
+
+/// Returns `metadata.language_info.file_extension` or an extension for the language name from `language_info`
+/// or `kernelspec` for notebooks that have no `file_extension`.
+fn notebook_file_extension(notebook: &JsonValue) -> Option<String> {
+    let metadata = notebook.get("metadata")?;
+    let language_info = metadata.get("language_info");
+
+    if let Some(ext) = language_info
+        .and_then(|v| v.get("file_extension"))
+        .and_then(|v| v.as_str())
+    {
+        if ext.starts_with('.') {
+            return Some(ext.to_owned());
+        }
+        return Some(format!(".{}", ext));
+    }
+
+    let language = language_info
+        .and_then(|v| v.get("name"))
+        .or_else(|| metadata.get("kernelspec").and_then(|v| v.get("language")))
+        .and_then(|v| v.as_str())?;
+
+    match language.to_lowercase().as_str() {
+        "python" | "python3" => Some(".py".to_owned()),
+        "r" => Some(".r".to_owned()),
+        "julia" => Some(".jl".to_owned()),
+        "scala" => Some(".scala".to_owned()),
+        "javascript" => Some(".js".to_owned()),
+        "typescript" => Some(".ts".to_owned()),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_parse_notebook_with_code_and_markdown_cells() {
+    let ipynb = r##"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": ["# Analysis\n", "Loads the data and prints the mean."]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "metadata": {},
+   "outputs": [],
+   "source": ["import pandas as pd\n", "\n", "# print the mean\n", "df = pd.read_csv(\"data.csv\")\n", "print(df.mean())"]
+  }
+ ],
+ "metadata": {
+  "kernelspec": { "display_name": "Python 3", "language": "python", "name": "python3" },
+  "language_info": { "name": "python", "file_extension": ".py" }
+ },
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+
+    let notebook = parse_notebook(ipynb).unwrap();
+    assert_eq!(notebook.file_extension.as_deref(), Some(".py"));
+    assert_eq!(
+        notebook.code,
+        "import pandas as pd\n\n# print the mean\ndf = pd.read_csv(\"data.csv\")\nprint(df.mean())\n"
+    );
+
+    // the notebook is munched as Python with the markdown cell left out
+    let file_name = "notebooks/analysis.ipynb".to_owned();
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    let muncher = code_rules
+        .get_muncher(&[file_name.as_str(), ".py"].concat())
+        .unwrap()
+        .clone();
+    let tech = crate::processors::analyze_bytes(ipynb.as_bytes(), &muncher, &file_name);
+    assert_eq!(tech.language, "Python");
+    assert_eq!(tech.total_lines, 5);
+    assert_eq!(tech.code_lines, 3);
+    assert_eq!(tech.blank_lines, 1);
+    assert!(tech.refs.iter().flatten().any(|kwc| kwc.k == "pandas"));
+
+    // the notebook rules are for the JSON, so it is not unwrapped for them
+    let muncher = code_rules.get_muncher(&file_name).unwrap().clone();
+    let tech = crate::processors::analyze_bytes(ipynb.as_bytes(), &muncher, &file_name);
+    assert_eq!(tech.language, "Jupyter");
+    assert_eq!(tech.total_lines, ipynb.lines().count() as u64);
+
+    assert!(parse_notebook("not json").is_none());
+    assert!(is_notebook("Analysis.IPYNB"));
+    assert!(!is_notebook("analysis.py"));
+}
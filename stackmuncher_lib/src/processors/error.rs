@@ -0,0 +1,35 @@
+use thiserror::Error;
+
+/// Reasons for a file not being processed. The message is recorded in `Report.unprocessed_file_reasons`.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ProcessError {
+    /// The blob could not be read from the repository, e.g. it does not exist
+    #[error("Cannot read blob {0}")]
+    BlobRead(String),
+    /// The file could not be read from the working tree, e.g. it was deleted
+    #[error("Cannot read file {0}")]
+    FileRead(String),
+    /// The file contents could not be decoded into text
+    #[error("Cannot decode the file: {0}")]
+    Decode(String),
+    /// The file has no contents
+    #[error("The file is empty")]
+    Empty,
+    /// The file contains NUL bytes and could not be decoded into text
+    #[error("The file is binary")]
+    Binary,
+    /// The file is larger than `Config.max_file_size_bytes`
+    #[error("File size of {size} bytes exceeds the limit of {limit} bytes")]
+    TooLarge { size: u64, limit: u64 },
+    /// The average line length is over `Config.max_avg_line_length`
+    #[error(
+        "Average line length of {avg_line_length} characters exceeds the limit of {limit}, the file is likely minified"
+    )]
+    Minified { avg_line_length: usize, limit: usize },
+    /// The processing had not started by the deadline set with `Config.max_processing_duration`
+    #[error("Processing time limit reached")]
+    TimedOut,
+    /// The processing task failed or was cancelled before returning a result
+    #[error("Processing failed with {0}")]
+    Task(String),
+}
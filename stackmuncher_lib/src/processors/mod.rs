@@ -1,37 +1,108 @@
 use super::muncher::Muncher;
-use crate::git::get_blob_contents;
+use crate::git::{get_blob_contents, GitBlob};
+use crate::manifest::parse_manifest;
+use crate::markdown;
+use crate::notebook;
 use crate::report::Tech;
 use encoding_rs as _;
-use encoding_rs::WINDOWS_1252;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
 use encoding_rs_io::{DecodeReaderBytes, DecodeReaderBytesBuilder};
-use regex::Regex;
-use std::collections::HashSet;
+use regex::{Regex, RegexSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::Read;
-use std::path::Path;
-use tracing::{debug, trace, warn};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, trace, warn};
+
+mod error;
+pub use error::ProcessError;
 
 /// Extract the file as git blob contents from the repository and perform the analysis.
+/// * **file**: the file name, the blob with the commit info for the record and the munchers, see `FileToProcess`
 /// * **all_tree_files***: needed to remove local imports that match the local file name, e.g. as in Python or Rust
+/// * **settings**: see `ProcessFileSettings`
 pub(crate) async fn process_file(
-    file_name: &String,
-    blob_sha1: &String,
-    rules: &Muncher,
+    file: &FileToProcess,
     project_dir: &Path,
-    commit_sha1: &String,
-    commit_date_epoch: i64,
-    commit_date_iso: &String,
     all_tree_files: Option<&HashSet<String>>,
-) -> Result<Tech, String> {
+    settings: &ProcessFileSettings,
+) -> Result<Vec<Tech>, ProcessError> {
+    let FileToProcess {
+        file_name,
+        blob,
+        muncher: rules,
+        fence_munchers,
+        contents,
+    } = file;
     debug!("Muncher: {}", rules.muncher_name);
 
+    // untracked files have no blobs
+    let from_disk = settings.include_working_tree || blob.sha1.is_empty();
+
     // prepare the blank structure
-    let mut tech = Tech {
+    let tech = Tech {
+        commit_sha1: Some(blob.commit_sha1.clone()),
+        commit_date_epoch: Some(blob.commit_date_epoch),
+        commit_date_iso: Some(blob.commit_date_iso.clone()),
+        ..blank_tech(rules, file_name)
+    };
+
+    // the file may have been read already to pick the muncher
+    if let Some(contents) = contents {
+        return analyze_file(contents, file_name, tech, rules, all_tree_files, settings, fence_munchers);
+    }
+
+    // exit now if the file cannot be read
+    let file = match read_file_within_limit(file_name, blob, project_dir, from_disk, settings.max_file_size_bytes).await
+    {
+        Ok(v) => v,
+        Err(e @ ProcessError::TooLarge { .. }) => return Err(e),
+        Err(e) => {
+            trace!("{} - not processing.", e);
+            return Ok(vec![tech]);
+        }
+    };
+
+    analyze_file(&file, file_name, tech, rules, all_tree_files, settings, fence_munchers)
+}
+
+/// Analyzes `content` of a file with the same decoding and classification as `process_file`, but with no git repo,
+/// e.g. for text from an editor buffer. No size, line length or generated file checks are applied.
+/// Returns the record for the language of `rules`. Lines of embedded languages, e.g. JS in HTML, are not included
+/// in it. Content that is empty or cannot be decoded gets a record with no counts.
+pub fn analyze_bytes(content: &[u8], rules: &Muncher, file_name: &str) -> Tech {
+    // the name comes from the caller and may have Windows separators
+    let file_name = crate::utils::normalize_file_name(file_name);
+    let tech = blank_tech(rules, &file_name);
+    match analyze_file(
+        content,
+        &file_name,
+        tech.clone(),
+        rules,
+        None,
+        &ProcessFileSettings::default(),
+        &HashMap::new(),
+    ) {
+        Ok(techs) => techs.into_iter().next().unwrap_or(tech),
+        Err(e) => {
+            warn!("{} was not analyzed: {}", file_name, e);
+            tech
+        }
+    }
+}
+
+/// Returns a record for `file_name` with no counts and no commit info.
+fn blank_tech(rules: &Muncher, file_name: &str) -> Tech {
+    Tech {
         language: rules.language.clone(),
         muncher_name: rules.muncher_name.clone(),
-        file_name: Some(file_name.clone()),
-        commit_sha1: Some(commit_sha1.clone()),
-        commit_date_epoch: Some(commit_date_epoch),
-        commit_date_iso: Some(commit_date_iso.clone()),
+        file_name: Some(file_name.to_owned()),
+        commit_sha1: None,
+        commit_date_epoch: None,
+        commit_date_iso: None,
         files: 1,
         total_lines: 0,
         code_lines: 0,
@@ -41,176 +112,2005 @@ pub(crate) async fn process_file(
         inline_comments: 0,
         blank_lines: 0,
         bracket_only_lines: 0,
-        keywords: HashSet::new(), // this is wasteful
-        refs: HashSet::new(),     // they should be Option<>
+        shebang_lines: 0,
+        string_lines: 0,
+        todo_comments: 0,
+        is_test: false,
+        test_code_lines: 0,
+        is_generated: false,
+        generated_code_lines: 0,
+        duplicated_lines: 0,
+        code_line_hashes: None,
+        keywords: None,
+        refs: None,
         refs_kw: None,
-        pkgs: HashSet::new(), // they should be Option<>
+        pkgs: None,
         pkgs_kw: None,
+        todo_authors: None,
+        line_ranges: None,
+        copyright_years: None,
+        detected_encoding: None,
+        ref_first_seen: None,
         muncher_hash: rules.muncher_hash,
         history: None,
-    };
+    }
+}
 
+/// Decodes the file contents and classifies the lines. It is the part of `process_file` that does not need git.
+/// * **tech**: a blank record with the file and commit info to fill in
+/// * **settings**: the file size limit and the source of the file do not apply here
+fn analyze_file(
+    file: &[u8],
+    file_name: &str,
+    mut tech: Tech,
+    rules: &Muncher,
+    all_tree_files: Option<&HashSet<String>>,
+    settings: &ProcessFileSettings,
+    fence_munchers: &HashMap<String, Arc<Muncher>>,
+) -> Result<Vec<Tech>, ProcessError> {
+    let strip_control_chars = settings.strip_control_chars.as_ref();
     // get file contents as UTF
-    let lines = match get_file_lines(file_name, blob_sha1, project_dir, false).await {
+    let (contents, encoding) = match decode_file(file, file_name, false, strip_control_chars) {
         Ok(v) => v,
-        Err(_) => {
-            // try ANSI if that fails
-            match get_file_lines(file_name, blob_sha1, project_dir, true).await {
-                Err(_) => {
+        // try ANSI if that fails
+        Err(ProcessError::Decode(_)) | Err(ProcessError::Binary) => {
+            match decode_file(file, file_name, true, strip_control_chars) {
+                Err(e) => {
                     // exit now if the file is either empty or binary
-                    trace!("Empty or binary file - not processing.");
-                    return Ok(tech);
+                    trace!("{} - not processing.", e);
+                    return Ok(vec![tech]);
                 }
                 Ok(v) => v,
             }
         }
+        Err(e) => {
+            trace!("{} - not processing.", e);
+            return Ok(vec![tech]);
+        }
     };
-    if lines.len() == 0 {
+    debug!("Decoded {} as {}", file_name, encoding);
+    tech.detected_encoding = Some(encoding.to_owned());
+
+    // notebooks are JSON documents with the code in cells and only the code is worth munching with the rules of
+    // the notebook language, the notebook rules work on the JSON
+    let contents = if notebook::is_notebook(file_name) && rules.language != Muncher::JUPYTER_LANGUAGE {
+        match notebook::parse_notebook(&contents) {
+            Some(v) => v.code,
+            None => contents,
+        }
+    } else {
+        contents
+    };
+    if contents.is_empty() {
         // no point processing an empty file further
         trace!("The file is empty - not processing.");
-        return Ok(tech);
+        return Ok(vec![tech]);
+    }
+
+    // minified files have very few very long lines that are not worth munching
+    if let Some(max_avg_line_length) = settings.max_avg_line_length.filter(|_| rules.checks_minified()) {
+        let avg_line_length = avg_line_length(split_lines(&contents));
+        if avg_line_length > max_avg_line_length {
+            return Err(ProcessError::Minified {
+                avg_line_length,
+                limit: max_avg_line_length,
+            });
+        }
+    }
+
+    // generated files are munched as usual, but flagged for consumers to exclude them
+    let is_generated = is_generated(&contents, &settings.generated_markers);
+
+    // embedded languages start with the same file and commit info
+    let blank_tech = tech.clone();
+
+    // code in fenced blocks is taken out of Markdown and gets a record per language
+    let (contents, fenced_techs) = if fence_munchers.is_empty() {
+        (contents, Vec::new())
+    } else {
+        munch_fenced_code(contents, fence_munchers, &blank_tech)
+    };
+
+    // `count_lines` records the first occurrence of refs and code line hashes only if there are collectors for them
+    if settings.track_ref_first_seen {
+        tech.ref_first_seen = Some(HashMap::new());
+    }
+    if settings.hash_code_lines {
+        tech.code_line_hashes = Some(Vec::new());
     }
 
     // get total lines
-    tech.total_lines = lines.len() as u64;
+    tech.total_lines = split_lines(&contents).count() as u64;
+
+    // the lines are borrowed from the contents one at a time to avoid an allocation per line
+    let line_languages = count_lines(&mut tech, split_lines(&contents), rules);
+
+    // remove refs names that match local file names
+    tech = tech.remove_local_imports(all_tree_files);
+
+    // declared dependencies are more reliable than regex matches on the lines of a package manifest
+    if let Some(pkgs) = parse_manifest(file_name, &contents) {
+        tech.pkgs = if pkgs.is_empty() {
+            None
+        } else {
+            Some(pkgs.into_iter().collect())
+        };
+    }
+
+    // lines of embedded languages are taken out of the host language and get a record per language
+    let mut techs = Vec::with_capacity(line_languages.len() + 1);
+    for (language, line_ranges) in line_languages {
+        let code_lines = lines_in_ranges(&line_ranges);
+        tech.total_lines -= code_lines;
+        techs.push(Tech {
+            language,
+            // the file is counted once under the host language
+            files: 0,
+            total_lines: code_lines,
+            code_lines,
+            line_ranges: Some(line_ranges),
+            ..blank_tech.clone()
+        });
+    }
+    techs.insert(0, tech);
+    techs.extend(fenced_techs);
+    for tech in techs.iter_mut() {
+        tech.set_is_generated(is_generated);
+    }
+
+    Ok(techs)
+}
+
+/// Lines of fenced code blocks by the muncher name with the muncher and the line ranges of the blocks in the file.
+type FencedLines<'a> = BTreeMap<&'a String, (&'a Arc<Muncher>, Vec<&'a str>, Vec<(usize, usize)>)>;
 
-    // set to true when the line is inside a block comment
-    let mut inside_block_comment = false;
+/// Munches the lines of fenced code blocks with the muncher for their fence tag, e.g. ```` ```rust ````.
+/// Returns the contents without those lines and a record per fence muncher with `blank_tech` file and commit info.
+/// Blocks with no muncher in `fence_munchers` stay in the contents.
+fn munch_fenced_code(
+    contents: String,
+    fence_munchers: &HashMap<String, Arc<Muncher>>,
+    blank_tech: &Tech,
+) -> (String, Vec<Tech>) {
+    let lines = split_lines(&contents).collect::<Vec<&str>>();
+
+    // blocks of the same language are munched together
+    let mut fenced_lines: FencedLines = BTreeMap::new();
+    let mut is_fenced = vec![false; lines.len()];
+    for block in markdown::fenced_blocks(&lines) {
+        if let Some(muncher) = fence_munchers.get(&block.tag) {
+            let (_, block_lines, line_ranges) = fenced_lines
+                .entry(&muncher.muncher_name)
+                .or_insert_with(|| (muncher, Vec::new(), Vec::new()));
+            for line_idx in block.lines {
+                block_lines.push(lines[line_idx]);
+                add_line_to_ranges(line_ranges, line_idx + 1);
+                is_fenced[line_idx] = true;
+            }
+        }
+    }
+    if fenced_lines.is_empty() {
+        return (contents, Vec::new());
+    }
+
+    let techs = fenced_lines
+        .into_values()
+        .map(|(muncher, block_lines, line_ranges)| {
+            let mut tech = Tech {
+                language: muncher.language.clone(),
+                muncher_name: muncher.muncher_name.clone(),
+                muncher_hash: muncher.muncher_hash,
+                // the file is counted once under Markdown
+                files: 0,
+                total_lines: block_lines.len() as u64,
+                line_ranges: Some(line_ranges),
+                ..blank_tech.clone()
+            };
+            // languages embedded in the code block are not split any further
+            let line_languages = count_lines(&mut tech, block_lines, muncher);
+            tech.code_lines += line_languages
+                .values()
+                .map(|line_ranges| lines_in_ranges(line_ranges))
+                .sum::<u64>();
+            tech
+        })
+        .collect::<Vec<Tech>>();
+    debug!("Fenced code languages: {}", techs.len());
+
+    let contents = lines
+        .into_iter()
+        .zip(is_fenced)
+        .filter_map(|(line, is_fenced)| if is_fenced { None } else { Some(line) })
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    (contents, techs)
+}
+
+/// A file for `spawn_process_files` with the blob and the muncher it should be processed with.
+pub(crate) struct FileToProcess {
+    pub file_name: String,
+    pub blob: GitBlob,
+    pub muncher: Arc<Muncher>,
+    /// Munchers for fenced code blocks in Markdown by the fence tag, e.g. `rust`, see `munch_fenced_code`
+    pub fence_munchers: HashMap<String, Arc<Muncher>>,
+    /// The raw contents if the file was read already to pick the muncher, e.g. for notebooks
+    pub contents: Option<Vec<u8>>,
+}
+
+/// Settings that apply to all files in `spawn_process_files` and `process_file`.
+#[derive(Clone, Default)]
+pub(crate) struct ProcessFileSettings {
+    pub strip_control_chars: Option<Vec<char>>,
+    pub track_ref_first_seen: bool,
+    /// Collect `Tech.code_line_hashes` for counting duplicated lines across files
+    pub hash_code_lines: bool,
+    pub max_file_size_bytes: Option<u64>,
+    pub max_avg_line_length: Option<usize>,
+    pub generated_markers: Vec<Regex>,
+    /// Read the files from disk under `project_dir` instead of the blobs, e.g. to include uncommitted changes.
+    /// Files with no blob SHA1 are always read from disk.
+    pub include_working_tree: bool,
+}
+
+impl ProcessFileSettings {
+    /// Returns a short hash of the settings that affect the munched results and of the list of files in the tree,
+    /// which is used to tell local imports apart from 3rd party ones. See `BlobCache`.
+    pub(crate) fn results_hash(&self, all_tree_files: Option<&HashSet<String>>) -> u64 {
+        let mut all_tree_files = all_tree_files.map(|files| files.iter().collect::<Vec<&String>>());
+        if let Some(files) = all_tree_files.as_mut() {
+            files.sort_unstable();
+        }
+
+        let settings = serde_json::json!({
+            "strip_control_chars": self.strip_control_chars,
+            "track_ref_first_seen": self.track_ref_first_seen,
+            "hash_code_lines": self.hash_code_lines,
+            "max_file_size_bytes": self.max_file_size_bytes,
+            "max_avg_line_length": self.max_avg_line_length,
+            "generated_markers": self.generated_markers.iter().map(|regex| regex.as_str()).collect::<Vec<&str>>(),
+            "include_working_tree": self.include_working_tree,
+            "all_tree_files": all_tree_files,
+        });
+        let settings = serde_json::to_vec(&settings).expect("Cannot serialize file settings. It's a bug.");
+
+        crate::utils::hash_sha1_u64(&settings)
+    }
+}
+
+/// Spawns `process_file` for all `files` on the tokio thread pool with no more than `max_concurrency` files at a time.
+/// The next file starts as soon as any of the running ones is done. The tasks can be awaited in the order of `files`
+/// with `join_processed_file` while the rest are still being processed.
+/// * `deadline` - files that have not started by then return `ProcessError::TimedOut` without being processed
+pub(crate) fn spawn_process_files(
+    files: Vec<FileToProcess>,
+    project_dir: &Path,
+    all_tree_files: Option<Arc<HashSet<String>>>,
+    settings: &ProcessFileSettings,
+    max_concurrency: usize,
+    deadline: Option<Instant>,
+) -> Vec<JoinHandle<Result<Vec<Tech>, ProcessError>>> {
+    debug!("Processing {} files, {} at a time", files.len(), max_concurrency);
+
+    // the semaphore is fair, so the files start in the order of submission
+    let semaphore = Arc::new(Semaphore::new(max_concurrency.max(1)));
+    let project_dir = Arc::new(project_dir.to_path_buf());
+
+    files
+        .into_iter()
+        .map(|file| {
+            let semaphore = semaphore.clone();
+            let project_dir: Arc<PathBuf> = project_dir.clone();
+            let all_tree_files = all_tree_files.clone();
+            let settings = settings.clone();
+            tokio::spawn(async move {
+                let _permit = semaphore
+                    .acquire_owned()
+                    .await
+                    .map_err(|e| ProcessError::Task(e.to_string()))?;
+                if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+                    return Err(ProcessError::TimedOut);
+                }
+                process_file(&file, &project_dir, all_tree_files.as_deref(), &settings).await
+            })
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Awaits a task from `spawn_process_files` and converts a failed or cancelled task into `ProcessError::Task`.
+pub(crate) async fn join_processed_file(
+    task: JoinHandle<Result<Vec<Tech>, ProcessError>>,
+) -> Result<Vec<Tech>, ProcessError> {
+    match task.await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Processing task failed with {}", e);
+            Err(ProcessError::Task(e.to_string()))
+        }
+    }
+}
+
+/// Returns the size of the file on disk or of its blob in bytes without reading the contents.
+/// Returns None if the size is not known, e.g. for blobs from the git index.
+async fn get_file_size(file_name: &String, blob: &GitBlob, project_dir: &Path, from_disk: bool) -> Option<u64> {
+    if !from_disk {
+        return blob.size;
+    }
+
+    match tokio::fs::metadata(project_dir.join(file_name)).await {
+        Ok(v) => Some(v.len()),
+        Err(e) => {
+            warn!("Cannot get the size of {} due to {}", file_name, e);
+            None
+        }
+    }
+}
+
+/// Returns an error if the file size is known and is over the limit. A file of exactly the max size is allowed.
+fn check_file_size(file_size: Option<u64>, max_file_size_bytes: Option<u64>) -> Result<(), ProcessError> {
+    match (file_size, max_file_size_bytes) {
+        (Some(size), Some(limit)) if exceeds_max_file_size(size, limit) => Err(ProcessError::TooLarge { size, limit }),
+        _ => Ok(()),
+    }
+}
+
+/// Returns true if a file of `file_size` bytes is over the limit. A file of exactly the max size is allowed.
+fn exceeds_max_file_size(file_size: u64, max_file_size_bytes: u64) -> bool {
+    file_size > max_file_size_bytes
+}
+
+/// Returns true if any of the first `Muncher::GENERATED_MARKER_LINES` lines match any of `generated_markers`.
+fn is_generated(contents: &str, generated_markers: &[Regex]) -> bool {
+    if generated_markers.is_empty() {
+        return false;
+    }
+
+    split_lines(contents)
+        .take(Muncher::GENERATED_MARKER_LINES)
+        .any(|line| generated_markers.iter().any(|marker| marker.is_match(line)))
+}
+
+/// Adds 1-based `line_no` to `line_ranges`, extending the last range if the line follows it.
+fn add_line_to_ranges(line_ranges: &mut Vec<(usize, usize)>, line_no: usize) {
+    match line_ranges.last_mut() {
+        Some((_, last)) if *last + 1 == line_no => *last = line_no,
+        _ => line_ranges.push((line_no, line_no)),
+    }
+}
+
+/// Returns the number of lines in 1-based inclusive `line_ranges`.
+fn lines_in_ranges(line_ranges: &[(usize, usize)]) -> u64 {
+    line_ranges.iter().map(|(first, last)| (last - first + 1) as u64).sum()
+}
+
+/// Returns the average number of characters per line, rounded down. Line ends are not counted.
+fn avg_line_length<I, S>(lines: I) -> usize
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let (total_chars, line_count) = lines
+        .into_iter()
+        .fold((0, 0), |(chars, count), line| (chars + line.as_ref().chars().count(), count + 1));
+    if line_count == 0 {
+        return 0;
+    }
+    total_chars / line_count
+}
+
+/// Classifies every line as code, comment, blank, etc. and adds the counts to `tech`.
+/// It is separate from `process_file` to be testable without a git repo.
+/// Returns the 1-based inclusive line ranges per embedded language. Those lines are not included in `tech.code_lines`.
+/// It is public only to be measured in isolation by the benchmarks and is not a part of the stable API.
+#[doc(hidden)]
+pub fn count_lines<I, S>(tech: &mut Tech, lines: I, rules: &Muncher) -> BTreeMap<String, Vec<(usize, usize)>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    // code lines attributed to other languages by `line_languages` signatures
+    let mut line_languages: BTreeMap<String, Vec<(usize, usize)>> = BTreeMap::new();
+
+    // the number of block comments the line is inside of, it is never more than 1 unless they can be nested
+    let mut block_comment_depth: usize = 0;
+    let nested_block_comments = rules.nested_block_comments.unwrap_or_default();
+    // a keyword, ref or package is counted once per line unless the muncher asks for every match
+    let count_all_keywords = rules.count_all_keywords.unwrap_or_default();
+    let count_all_refs = rules.count_all_refs.unwrap_or_default();
+    let count_all_packages = rules.count_all_packages.unwrap_or_default();
+    // set to true when the previous code line continues on this line, e.g. ends with `\` in C
+    let mut inside_continued_line = false;
+    // the index of `string_block_start` the line is inside of, e.g. a Python docstring opened with `"""`
+    let mut string_block: Option<usize> = None;
 
     // evaluate every line
-    for line in lines {
+    for (line_idx, line) in lines.into_iter().enumerate() {
+        let line = line.as_ref();
         trace!("{}", line);
+
+        // copyright notices are expected in the header, usually in a comment, and do not affect the line type
+        if line_idx < Muncher::COPYRIGHT_HEADER_LINES {
+            tech.add_copyright_years(&rules.copyright_regex, line);
+        }
+
         // check for non-code parts
 
+        // a shebang is an instruction for the OS and can only be the very first line of a script
+        if line_idx == 0 && is_shebang(line) {
+            tech.shebang_lines = 1;
+            trace!("shebang_lines");
+            continue;
+        }
+
+        // blank lines inside a multi-line string are a part of the string, not formatting
+        if let Some(block_idx) = string_block {
+            if match_line(&rules.blank_line_regex_set, line) {
+                tech.string_lines += 1;
+                trace!("string_lines");
+                continue;
+            }
+
+            // the rest of the string is counted as code as before
+            if closes_string_block(rules, block_idx, line) {
+                string_block = None;
+            }
+            tech.code_lines += 1;
+            trace!("code_lines");
+            tech.add_code_line_hash(line);
+            tech.count_keywords(&rules.keywords_regex, line, count_all_keywords);
+            continue;
+        }
+
         // check if it's inside a block comment
-        if inside_block_comment {
+        if block_comment_depth > 0 {
             tech.block_comments += 1;
             trace!("block_comments");
+            tech.count_todo_authors(&rules.todo_authors_regex, line);
+            tech.count_todo_comments(&rules.todo_markers_regex, line);
             // is it a closing block?
-            if match_line(&rules.block_comments_end_regex, &line) {
-                inside_block_comment = false;
+            if nested_block_comments {
+                block_comment_depth = (block_comment_depth + count_matches(&rules.block_comments_start_regex, line))
+                    .saturating_sub(count_matches(&rules.block_comments_end_regex, line));
+            } else if match_line(&rules.block_comments_end_regex_set, line) {
+                block_comment_depth = 0;
             }
             continue;
         }
 
-        if match_line(&rules.block_comments_start_regex, &line) {
-            tech.block_comments += 1;
-            trace!("block_comments");
+        // a continuation is code even if it looks like a comment or a bracket, e.g. inside a C macro
+        if inside_continued_line {
+            tech.code_lines += 1;
+            trace!("code_lines: continued");
+            tech.add_code_line_hash(line);
+            inside_continued_line = match_line(&rules.line_continuation_regex_set, line);
+            tech.count_refs(&rules.refs_regex, line, line_idx + 1, count_all_refs);
+            tech.count_pkgs(&rules.packages_regex, line, count_all_packages);
+            tech.count_keywords(&rules.keywords_regex, line, count_all_keywords);
+            continue;
+        }
+
+        // comment markers inside string literals, e.g. "http://x", are not comments
+        let masked_line = mask_string_literals(rules, line);
+        let comment_line = masked_line.as_deref().unwrap_or(line);
+
+        if match_line(&rules.block_comments_start_regex_set, comment_line) {
+            // a trailing comment after code, e.g. `x = 5; /* set x */`, does not make the line a comment
+            match code_before_block_comment(rules, comment_line) {
+                Some(code_len) => {
+                    // the masked line has the same length, but refs may be inside the string literals
+                    let code = &line[..code_len];
+                    tech.code_lines += 1;
+                    trace!("code_lines before block_comments");
+                    tech.add_code_line_hash(code);
+                    tech.count_refs(&rules.refs_regex, code, line_idx + 1, count_all_refs);
+                    tech.count_pkgs(&rules.packages_regex, code, count_all_packages);
+                    tech.count_keywords(&rules.keywords_regex, code, count_all_keywords);
+                }
+                None => {
+                    tech.block_comments += 1;
+                    trace!("block_comments");
+                }
+            }
+            tech.count_todo_authors(&rules.todo_authors_regex, line);
+            tech.count_todo_comments(&rules.todo_markers_regex, line);
 
             // mark it as the start of the block if there is no closing part on the same line
-            if !match_line(&rules.block_comments_end_regex, &line) {
-                inside_block_comment = true;
+            if nested_block_comments {
+                block_comment_depth = count_matches(&rules.block_comments_start_regex, comment_line)
+                    .saturating_sub(count_matches(&rules.block_comments_end_regex, comment_line));
+            } else if !match_line(&rules.block_comments_end_regex_set, comment_line) {
+                block_comment_depth = 1;
             }
 
             continue;
 
             // It is possible that some code may have multiple opening / closing comments on the same page.
             // That would probably be just messy code that can be ignored.
-            // Those comments may also be inside string literals, e.g. "some text like this /*".
-            // The same applies to other types of comments - they can be inside " ... "
+            // Those comments may also be inside string literals, e.g. "some text like this /*", which is only
+            // handled for munchers with `string_literals` rules.
         }
 
-        if match_line(&rules.doc_comments_regex, &line) {
+        if match_line(&rules.doc_comments_regex_set, comment_line) {
             tech.docs_comments += 1;
             trace!("doc_comments");
+            tech.count_todo_authors(&rules.todo_authors_regex, line);
+            tech.count_todo_comments(&rules.todo_markers_regex, line);
             continue;
         }
 
-        if match_line(&rules.line_comments_regex, &line) {
+        if match_line(&rules.line_comments_regex_set, comment_line) {
             tech.line_comments += 1;
             trace!("line_comments");
+            tech.count_todo_authors(&rules.todo_authors_regex, line);
+            tech.count_todo_comments(&rules.todo_markers_regex, line);
             continue;
         }
 
-        if match_line(&rules.inline_comments_regex, &line) {
+        if match_line(&rules.inline_comments_regex_set, comment_line) {
             tech.inline_comments += 1;
             trace!("inline_comments");
-            continue;
+            tech.count_todo_authors(&rules.todo_authors_regex, line);
+            tech.count_todo_comments(&rules.todo_markers_regex, line);
+            // the code part of the line is counted further down if the muncher says so
+            if !rules.inline_comments_count_as_code.unwrap_or_default() {
+                continue;
+            }
         }
 
-        if match_line(&rules.bracket_only_regex, &line) {
+        if match_line(&rules.bracket_only_regex_set, line) {
             tech.bracket_only_lines += 1;
             trace!("bracket_only_lines");
             continue;
         }
 
-        if match_line(&rules.blank_line_regex, &line) {
+        if match_line(&rules.blank_line_regex_set, line) {
             tech.blank_lines += 1;
             trace!("blank_lines");
             continue;
         }
 
+        // a line of another language interleaved with the host language, e.g. HTML in ERB
+        if let Some(language) = rules.line_language(line) {
+            add_line_to_ranges(line_languages.entry(language.clone()).or_default(), line_idx + 1);
+            trace!("code_lines: {}", language);
+            continue;
+        }
+
         // this is a code line of sorts
         tech.code_lines += 1;
         trace!("code_lines");
+        tech.add_code_line_hash(line);
+        inside_continued_line = match_line(&rules.line_continuation_regex_set, line);
+        string_block = opens_string_block(rules, line);
 
         // count keywords and package references
-        tech.count_refs(&rules.refs_regex, &line);
-        tech.count_pkgs(&rules.packages_regex, &line);
-        tech.count_keywords(&rules.keywords_regex, &line);
+        tech.count_refs(&rules.refs_regex, line, line_idx + 1, count_all_refs);
+        tech.count_pkgs(&rules.packages_regex, line, count_all_packages);
+        tech.count_keywords(&rules.keywords_regex, line, count_all_keywords);
     }
 
-    // remove refs names that match local file names
-    tech = tech.remove_local_imports(all_tree_files);
+    line_languages
+}
 
-    Ok(tech)
+/// Returns the raw contents of a text file with its first `Muncher::CONTENT_SIGNATURE_LINES` lines
+/// for `detect_muncher_by_content`.
+/// Returns None if the file cannot be read, is not a text file or is over `max_file_size_bytes`.
+/// See `process_file` for `from_disk`.
+pub(crate) async fn get_first_lines(
+    file_name: &String,
+    blob: &GitBlob,
+    project_dir: &Path,
+    from_disk: bool,
+    max_file_size_bytes: Option<u64>,
+) -> Option<(Vec<u8>, Vec<String>)> {
+    let (file, contents, _) =
+        get_file_contents(file_name, blob, project_dir, from_disk, max_file_size_bytes, false, None)
+            .await
+            .ok()?;
+    let first_lines = split_lines(&contents)
+        .take(Muncher::CONTENT_SIGNATURE_LINES)
+        .map(|line| line.to_owned())
+        .collect();
+
+    Some((file, first_lines))
 }
 
-/// Returns multiple lines from a text file, if the encoding is UTF-something.
-/// Returns an error if the file cannot be read or cannot be decoded.
-/// ANSI files may be incompatible with UTF, so use it with try_ansi=false first
-/// and then try_ansi=true to read it as WINDOWS_1252
-async fn get_file_lines(
+/// Returns the raw contents of a Markdown file with the tags of all its fenced code blocks, e.g. `rust`,
+/// for picking their munchers.
+/// Returns None if the file cannot be read or is over `max_file_size_bytes`.
+pub(crate) async fn get_fence_tags(
+    file_name: &String,
+    blob: &GitBlob,
+    project_dir: &Path,
+    from_disk: bool,
+    max_file_size_bytes: Option<u64>,
+) -> Option<(Vec<u8>, HashSet<String>)> {
+    let (file, contents, _) =
+        get_file_contents(file_name, blob, project_dir, from_disk, max_file_size_bytes, false, None)
+            .await
+            .ok()?;
+    let tags = markdown::fence_tags(&markdown::fenced_blocks(split_lines(&contents)));
+
+    Some((file, tags))
+}
+
+/// Returns the raw contents of a notebook with the extension of files in the language of its code cells, e.g. `.py`,
+/// for picking a muncher. The extension is None if the notebook is invalid or has no language metadata.
+/// Returns None if the file cannot be read or is over `max_file_size_bytes`.
+pub(crate) async fn read_notebook(
+    file_name: &String,
+    blob: &GitBlob,
+    project_dir: &Path,
+    from_disk: bool,
+    max_file_size_bytes: Option<u64>,
+) -> Option<(Vec<u8>, Option<String>)> {
+    let file = read_file_within_limit(file_name, blob, project_dir, from_disk, max_file_size_bytes)
+        .await
+        .ok()?;
+    let file_extension = decode_file(&file, file_name, false, None)
+        .ok()
+        .and_then(|(contents, _)| notebook::parse_notebook(&contents))
+        .and_then(|notebook| notebook.file_extension);
+
+    Some((file, file_extension))
+}
+
+/// Returns the raw contents of a text file with the contents decoded into UTF-8 and the name of the source encoding.
+/// Returns an error if the file cannot be read, is over `max_file_size_bytes`, is empty or cannot be decoded.
+/// See `read_file_within_limit` and `decode_file` for details.
+async fn get_file_contents(
+    file_name: &String,
+    blob: &GitBlob,
+    project_dir: &Path,
+    from_disk: bool,
+    max_file_size_bytes: Option<u64>,
+    try_ansi: bool,
+    strip_control_chars: Option<&Vec<char>>,
+) -> Result<(Vec<u8>, String, &'static str), ProcessError> {
+    let file = read_file_within_limit(file_name, blob, project_dir, from_disk, max_file_size_bytes).await?;
+    let (contents, encoding) = decode_file(&file, file_name, try_ansi, strip_control_chars)?;
+
+    Ok((file, contents, encoding))
+}
+
+/// Returns the raw contents of the file with `read_file` if it is not over `max_file_size_bytes`.
+/// The size is checked before loading the file into memory if it is known, e.g. from the tree listing,
+/// or after loading it otherwise, e.g. for blobs from the git index.
+async fn read_file_within_limit(
+    file_name: &String,
+    blob: &GitBlob,
+    project_dir: &Path,
+    from_disk: bool,
+    max_file_size_bytes: Option<u64>,
+) -> Result<Vec<u8>, ProcessError> {
+    let file_size = match max_file_size_bytes {
+        Some(_) => get_file_size(file_name, blob, project_dir, from_disk).await,
+        None => None,
+    };
+    check_file_size(file_size, max_file_size_bytes)?;
+
+    let file = read_file(file_name, &blob.sha1, project_dir, from_disk).await?;
+    if file_size.is_none() {
+        check_file_size(Some(file.len() as u64), max_file_size_bytes)?;
+    }
+
+    Ok(file)
+}
+
+/// Returns the raw contents of the file from `project_dir` on disk if `from_disk` is true or from the blob otherwise.
+async fn read_file(
     file_name: &String,
     blob_sha1: &String,
     project_dir: &Path,
+    from_disk: bool,
+) -> Result<Vec<u8>, ProcessError> {
+    if from_disk {
+        tokio::fs::read(project_dir.join(file_name)).await.map_err(|e| {
+            warn!("Cannot read {} due to {}", file_name, e);
+            ProcessError::FileRead(file_name.clone())
+        })
+    } else {
+        get_blob_contents(project_dir, blob_sha1)
+            .await
+            .map_err(|_| ProcessError::BlobRead(blob_sha1.clone()))
+    }
+}
+
+/// Decodes the raw contents of a text file into UTF-8 and returns it with the name of the source encoding.
+/// Returns an error if the file is empty or cannot be decoded. Files with NUL bytes that cannot be
+/// decoded are reported as `ProcessError::Binary`.
+/// ANSI files may be incompatible with UTF, so use it with try_ansi=false first
+/// and then try_ansi=true to read it as WINDOWS_1252
+fn decode_file(
+    file: &[u8],
+    file_name: &str,
     try_ansi: bool,
-) -> Result<Vec<String>, ()> {
-    // read the file
-    let file = get_blob_contents(project_dir, &blob_sha1).await?;
+    strip_control_chars: Option<&Vec<char>>,
+) -> Result<(String, &'static str), ProcessError> {
+    if file.is_empty() {
+        return Err(ProcessError::Empty);
+    }
+
+    // try to read the file
+    let (mut utf8_string, encoding) = match decode(file, try_ansi) {
+        Ok(v) => v,
+        Err(e) => {
+            // log an error only on the 2nd run of this function when ANSI is ON
+            if try_ansi {
+                warn!("Cannot decode {} as UTF due to {} with ANSI={}", file_name, e, try_ansi);
+            }
+
+            // text in any of the supported encodings has no NUL bytes, except for UTF-16, which was tried already
+            if file.contains(&0) {
+                return Err(ProcessError::Binary);
+            }
+            return Err(ProcessError::Decode(e.to_string()));
+        }
+    };
+
+    // stray control chars, e.g. form feeds, prevent regex from matching the rest of the line
+    // line feeds are kept because they split the contents into lines
+    if let Some(strip_control_chars) = strip_control_chars {
+        utf8_string.retain(|c| c == '\n' || !strip_control_chars.contains(&c));
+    }
+
+    Ok((utf8_string, encoding))
+}
+
+/// Decodes the file contents into a UTF-8 string and returns it with the name of the source encoding.
+/// The encoding is taken from the BOM, if any. Files with no BOM are decoded as UTF-16 if they look like it
+/// and as UTF-8 otherwise. `try_ansi=true` forces WINDOWS_1252.
+fn decode(file: &[u8], try_ansi: bool) -> Result<(String, &'static str), std::io::Error> {
     // this decoder is required to read non-UTF-8 files
-    let mut decoder = if try_ansi {
-        DecodeReaderBytesBuilder::new()
-            .encoding(Some(WINDOWS_1252))
-            .build(&file[..])
+    let (mut decoder, encoding) = if try_ansi {
+        let decoder = DecodeReaderBytesBuilder::new().encoding(Some(WINDOWS_1252)).build(file);
+        (decoder, WINDOWS_1252)
+    } else if let Some((encoding, _)) = Encoding::for_bom(file) {
+        // the decoder detects the BOM on its own
+        (DecodeReaderBytes::new(file), encoding)
+    } else if let Some(encoding) = sniff_utf16(file) {
+        let decoder = DecodeReaderBytesBuilder::new().encoding(Some(encoding)).build(file);
+        (decoder, encoding)
     } else {
-        DecodeReaderBytes::new(&file[..])
+        (DecodeReaderBytes::new(file), UTF_8)
     };
 
-    // output collector
-    let mut lines: Vec<String> = Vec::new();
-
-    // try to read the file
     let mut utf8_string = String::new();
-    if let Err(e) = decoder.read_to_string(&mut utf8_string) {
-        // log an error only on the 2nd run of this function when ANSI is ON
-        if try_ansi {
-            warn!("Cannot decode {} as UTF due to {} with ANSI={}", file_name, e, try_ansi);
+    decoder.read_to_string(&mut utf8_string)?;
+
+    // the decoder passes the BOM through as U+FEFF, which breaks regex anchored to the start of the first line
+    if utf8_string.starts_with('\u{FEFF}') {
+        utf8_string.remove(0);
+    }
+
+    Ok((utf8_string, encoding.name()))
+}
+
+/// Returns UTF_16LE or UTF_16BE if the file with no BOM looks like UTF-16 text, which is mostly ASCII with every
+/// other byte being zero. Real UTF-8 text has no zero bytes, so the check is unlikely to misfire.
+fn sniff_utf16(file: &[u8]) -> Option<&'static Encoding> {
+    // only the start of the file is checked for speed
+    let sample = &file[..file.len().min(UTF16_SNIFF_SAMPLE_LEN)];
+    if sample.len() < 2 || !sample.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let pairs = sample.len() / 2;
+    let zeros_even = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let zeros_odd = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+
+    // e.g. `a` is `61 00` in LE and `00 61` in BE
+    if zeros_odd * 10 >= pairs * 9 && zeros_even == 0 {
+        Some(UTF_16LE)
+    } else if zeros_even * 10 >= pairs * 9 && zeros_odd == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// The number of bytes at the start of the file checked by `sniff_utf16`. It must be an even number.
+const UTF16_SNIFF_SAMPLE_LEN: usize = 1024;
+
+/// Splits the file contents into lines. A trailing line terminator does not add an extra empty line,
+/// so `a\nb` and `a\nb\n` are both 2 lines, which is consistent with most other LoC tools.
+/// Both `\n` and `\r\n` are treated as line terminators.
+fn split_lines(contents: &str) -> std::str::Lines<'_> {
+    contents.lines()
+}
+
+/// Returns true if the line looks like a shebang, e.g. `#!/bin/bash` or `#! /usr/bin/env python`.
+/// Rust inner attributes such as `#![allow(dead_code)]` also start with `#!`, but they are code.
+fn is_shebang(line: &str) -> bool {
+    line.starts_with("#!") && !line.starts_with("#![")
+}
+
+/// Returns the index of `string_block_start` if the line has the start of a multi-line string with no matching end
+/// after it, e.g. `x = """` in Python, but not `x = """abc"""`. The earliest start on the line wins.
+fn opens_string_block(rules: &Muncher, line: &str) -> Option<usize> {
+    let (block_idx, start) = rules
+        .string_block_start_regex
+        .iter()
+        .flatten()
+        .enumerate()
+        .filter_map(|(block_idx, start_regex)| Some((block_idx, start_regex.find(line)?)))
+        .min_by_key(|(_, start)| start.start())?;
+
+    if closes_string_block(rules, block_idx, &line[start.end()..]) {
+        None
+    } else {
+        Some(block_idx)
+    }
+}
+
+/// Returns true if the line has the end of the multi-line string opened by `string_block_start` at `block_idx`.
+fn closes_string_block(rules: &Muncher, block_idx: usize, line: &str) -> bool {
+    rules
+        .string_block_end_regex
+        .as_ref()
+        .and_then(|end_regex| end_regex.get(block_idx))
+        .map(|end_regex| end_regex.is_match(line))
+        .unwrap_or_default()
+}
+
+/// Returns the length in bytes of the part of the line before the first block comment opener if it contains code,
+/// e.g. `x = 5;` in `x = 5; /* set x */`. Returns None if there is no opener or the part before it is blank or a comment.
+/// The opener is the whole match or the `opener` named capture group if the regex has one, e.g. `^[^/]*(?P<opener>/\*)`.
+fn code_before_block_comment(rules: &Muncher, line: &str) -> Option<usize> {
+    let opener_start = rules
+        .block_comments_start_regex
+        .iter()
+        .flatten()
+        .filter_map(|regex| {
+            let captures = regex.captures(line)?;
+            captures.name("opener").or_else(|| captures.get(0))
+        })
+        .map(|opener| opener.start())
+        .min()?;
+
+    let prefix = &line[..opener_start];
+    if match_line(&rules.blank_line_regex_set, prefix)
+        || match_line(&rules.doc_comments_regex_set, prefix)
+        || match_line(&rules.line_comments_regex_set, prefix)
+        || match_line(&rules.inline_comments_regex_set, prefix)
+    {
+        return None;
+    }
+
+    Some(prefix.len())
+}
+
+/// Returns a copy of the line with all `string_literals` matches replaced with spaces byte for byte, so that
+/// the offsets in the masked line are the same as in the original. Returns None if there was nothing to mask.
+fn mask_string_literals(rules: &Muncher, line: &str) -> Option<String> {
+    let mut masked: Option<Vec<u8>> = None;
+    for regex in rules.string_literals_regex.iter().flatten() {
+        for literal in regex.find_iter(line) {
+            let masked = masked.get_or_insert_with(|| line.as_bytes().to_vec());
+            for byte in &mut masked[literal.start()..literal.end()] {
+                *byte = b' ';
+            }
         }
+    }
+
+    // multi-byte chars are replaced in full because the matches always start and end at char boundaries
+    masked.map(|bytes| String::from_utf8(bytes).expect("Masked line is not a valid UTF-8. It's a bug."))
+}
+
+/// Returns the number of non-overlapping matches of all regex in the line.
+fn count_matches(regex: &Option<Vec<Regex>>, line: &str) -> usize {
+    regex.iter().flatten().map(|r| r.find_iter(line).count()).sum()
+}
+
+/// Returns true if there is a regex set and any of its regex match the line.
+#[inline(always)]
+fn match_line(regex_set: &Option<RegexSet>, line: &str) -> bool {
+    match regex_set {
+        Some(v) => v.is_match(line),
+        None => false,
+    }
+}
+
+#[test]
+fn test_split_lines_trailing_newline() {
+    assert_eq!(split_lines("a\nb").collect::<Vec<_>>(), vec!["a", "b"]);
+    assert_eq!(split_lines("a\nb\n").collect::<Vec<_>>(), vec!["a", "b"]);
+    assert_eq!(split_lines("a\r\nb\r\n").collect::<Vec<_>>(), vec!["a", "b"]);
+    // a blank last line is still a line if there is a terminator after it
+    assert_eq!(split_lines("a\n\n").collect::<Vec<_>>(), vec!["a", ""]);
+    assert!(split_lines("").next().is_none());
+}
+
+#[test]
+fn test_shebang_is_not_code() {
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    let shell = code_rules.get_muncher(&"deploy.sh".to_owned()).unwrap().clone();
+    let rust = code_rules.get_muncher(&"main.rs".to_owned()).unwrap().clone();
+
+    let tech = analyze_bytes(b"#!/usr/bin/env bash\necho hi\n", &shell, "deploy.sh");
+    assert_eq!(tech.shebang_lines, 1);
+    assert_eq!(tech.code_lines, 1);
+
+    // only the first line can be a shebang
+    let tech = analyze_bytes(b"echo hi\n#!/bin/bash\n", &shell, "deploy.sh");
+    assert_eq!(tech.shebang_lines, 0);
+
+    // a Rust inner attribute is code
+    let tech = analyze_bytes(b"#![allow(dead_code)]\nfn main() {}\n", &rust, "main.rs");
+    assert_eq!(tech.shebang_lines, 0);
+    assert_eq!(tech.code_lines, 2);
+}
+
+#[test]
+fn test_todo_authors() {
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    let rust = code_rules.get_muncher(&"main.rs".to_owned()).unwrap().clone();
+
+    let contents = b"// TODO(alice): fix this\n/// TODO( bob ) document\n/* TODO(alice) */\nlet s = \"TODO(carol)\";\n";
+    let tech = analyze_bytes(contents, &rust, "main.rs");
 
-        return Err(());
+    // markers in code are not counted
+    let mut report = crate::report::Report::new();
+    report.merge_tech_record(tech);
+    let expected: HashMap<String, usize> = [("alice".to_owned(), 2), ("bob".to_owned(), 1)]
+        .iter()
+        .cloned()
+        .collect();
+    assert_eq!(report.todo_by_author(), expected);
+
+    // a custom convention replaces the default one
+    let muncher = Muncher::new(
+        r#"{"language": "Test", "line_comments": ["^\\s*#"], "todo_authors": ["FIXME\\[(\\w+)\\]"]}"#,
+        &"test".to_owned(),
+    )
+    .unwrap();
+    let tech = analyze_bytes(b"# FIXME[dave] and TODO(alice)\n", &muncher, "test.txt");
+    let todo_authors = tech.todo_authors.unwrap();
+    assert_eq!(todo_authors.len(), 1);
+    assert_eq!(todo_authors.iter().next().unwrap().k, "dave");
+}
+
+#[test]
+fn test_line_continuation() {
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    let cpp = code_rules.get_muncher(&"main.cpp".to_owned()).unwrap().clone();
+
+    let contents = b"// a macro spanning 4 lines\n#define SWAP(a, b) \\\n    // not a comment \\\n    } \\\n\n\nint main() {\n    return 0;\n}\n";
+    let tech = analyze_bytes(contents, &cpp, "main.cpp");
+
+    // the continued lines are code even if they look like a comment, a bracket or a blank line
+    assert_eq!(tech.code_lines, 6);
+    assert_eq!(tech.line_comments, 1);
+    assert_eq!(tech.bracket_only_lines, 1);
+    assert_eq!(tech.blank_lines, 1);
+
+    // every line is counted exactly once
+    assert_eq!(
+        tech.code_lines
+            + tech.blank_lines
+            + tech.bracket_only_lines
+            + tech.line_comments
+            + tech.inline_comments
+            + tech.block_comments
+            + tech.docs_comments
+            + tech.shebang_lines
+            + tech.string_lines,
+        tech.total_lines
+    );
+}
+
+#[test]
+fn test_inline_comments_count_as_code() {
+    let lines = vec![
+        "let x = 1; // set x to one".to_string(),
+        "// just a comment here".to_string(),
+    ];
+    let blank_tech = Tech {
+        total_lines: 2,
+        ..Tech::for_test("Rust", 0)
+    };
+    let muncher_json = |inline_comments_count_as_code: bool| {
+        serde_json::json!({
+            "language": "Rust",
+            "line_comments": ["^\\s*//"],
+            "inline_comments": ["[^\\s]+\\s*//"],
+            "inline_comments_count_as_code": inline_comments_count_as_code,
+        })
+        .to_string()
+    };
+
+    // the default: a line with an inline comment is a comment only
+    let rules = Muncher::new(&muncher_json(false), &"test".to_string()).unwrap();
+    let mut tech = blank_tech.clone();
+    count_lines(&mut tech, lines.clone(), &rules);
+    assert_eq!(tech.inline_comments, 1);
+    assert_eq!(tech.line_comments, 1);
+    assert_eq!(tech.code_lines, 0);
+
+    // the same line is counted as both, an inline comment and code
+    let rules = Muncher::new(&muncher_json(true), &"test".to_string()).unwrap();
+    let mut tech = blank_tech;
+    count_lines(&mut tech, lines, &rules);
+    assert_eq!(tech.inline_comments, 1);
+    assert_eq!(tech.line_comments, 1);
+    assert_eq!(tech.code_lines, 1);
+}
+
+#[test]
+fn test_line_languages() {
+    let lines = vec![
+        "<div class=\"user\">".to_string(),
+        "<%= user.name %>".to_string(),
+        "</div>".to_string(),
+    ];
+    let mut tech = Tech {
+        total_lines: 3,
+        ..Tech::for_test("eRuby", 0)
+    };
+    let muncher_json = serde_json::json!({
+        "language": "eRuby",
+        "line_languages": { "HTML": ["^\\s*</?[a-zA-Z][^%]*$"] },
+    })
+    .to_string();
+    let rules = Muncher::new(&muncher_json, &"test".to_string()).unwrap();
+
+    let line_languages = count_lines(&mut tech, lines, &rules);
+    assert_eq!(tech.code_lines, 1);
+    assert_eq!(line_languages.get("HTML"), Some(&vec![(1, 1), (3, 3)]));
+}
+
+#[test]
+fn test_code_before_block_comment() {
+    let lines = vec![
+        "code(); /* trailing comment */".to_string(),
+        "/* full line */".to_string(),
+        "call(); /* opens a block".to_string(),
+        "still in the block".to_string(),
+        "*/".to_string(),
+        "/* opens another block".to_string(),
+        "closes it */".to_string(),
+        "done();".to_string(),
+    ];
+    let mut tech = Tech {
+        total_lines: 8,
+        ..Tech::for_test("C", 0)
     };
+    let muncher_json = serde_json::json!({
+        "language": "C",
+        "line_comments": ["^\\s*//"],
+        "block_comments_start": ["/\\*"],
+        "block_comments_end": ["\\*/\\s*$"],
+    })
+    .to_string();
+    let rules = Muncher::new(&muncher_json, &"test".to_string()).unwrap();
+
+    count_lines(&mut tech, lines, &rules);
+    assert_eq!(tech.code_lines, 3);
+    assert_eq!(tech.block_comments, 5);
+}
+
+#[test]
+fn test_blank_lines_in_string_blocks() {
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    let python = code_rules.get_muncher(&"main.py".to_owned()).unwrap().clone();
+
+    // a block is only closed by the same kind of quotes that opened it
+    let contents = r#"def f():
+    x = """
+
+    it's ''' not the end
+
+    """
 
-    // convert the file into a collection of lines
-    for line in utf8_string.as_str().lines() {
-        lines.push(line.into());
+    y = '''
+
+    '''
+"#;
+    let tech = analyze_bytes(contents.as_bytes(), &python, "main.py");
+    assert_eq!(tech.string_lines, 3);
+    assert_eq!(tech.blank_lines, 1);
+    assert_eq!(tech.code_lines, 6);
+
+    // a muncher needs an end for every start
+    let muncher_json = serde_json::json!({
+        "language": "Test",
+        "string_block_start": ["\"{3}", "'{3}"],
+        "string_block_end": ["\"{3}"],
+    })
+    .to_string();
+    assert!(Muncher::new(&muncher_json, &"test".to_owned()).is_none());
+}
+
+#[test]
+fn test_string_literals_are_not_comments() {
+    let lines = vec![
+        r#"let s = "http://x /* not a comment */";"#.to_string(),
+        r#"let url = "https://example.com"; // a real comment"#.to_string(),
+        r#"let c = '//';"#.to_string(),
+        r#"let ok = "fine";"#.to_string(),
+    ];
+    let blank_tech = Tech {
+        total_lines: 4,
+        ..Tech::for_test("Rust", 0)
+    };
+    let muncher_json = |string_literals: Option<Vec<&str>>| {
+        serde_json::json!({
+            "language": "Rust",
+            "line_comments": ["^\\s*//"],
+            "inline_comments": ["[^\\s]+\\s*//"],
+            "block_comments_start": ["/\\*"],
+            "block_comments_end": ["\\*/"],
+            "string_literals": string_literals,
+        })
+        .to_string()
+    };
+
+    // comment markers in strings are mistaken for comments without the rules
+    let rules = Muncher::new(&muncher_json(None), &"test".to_string()).unwrap();
+    let mut tech = blank_tech.clone();
+    count_lines(&mut tech, lines.clone(), &rules);
+    assert_eq!(tech.code_lines, 1);
+
+    // single and double quoted strings are blanked out
+    let rules = Muncher::new(
+        &muncher_json(Some(vec![r#""(?:[^"\\]|\\.)*""#, r#"'(?:[^'\\]|\\.)*'"#])),
+        &"test".to_string(),
+    )
+    .unwrap();
+    let mut tech = blank_tech;
+    count_lines(&mut tech, lines, &rules);
+    assert_eq!(tech.code_lines, 3);
+    assert_eq!(tech.inline_comments, 1);
+    assert_eq!(tech.block_comments, 0);
+}
+
+#[test]
+fn test_nested_block_comments() {
+    let lines = vec![
+        "/* outer".to_string(),
+        "   /* inner */".to_string(),
+        "   still outer".to_string(),
+        "*/".to_string(),
+        "code();".to_string(),
+    ];
+    let blank_tech = Tech {
+        total_lines: 5,
+        ..Tech::for_test("Rust", 0)
+    };
+    let muncher_json = |nested_block_comments: bool| {
+        serde_json::json!({
+            "language": "Rust",
+            "block_comments_start": ["^\\s*/\\*"],
+            "block_comments_end": ["\\*/\\s*$"],
+            "nested_block_comments": nested_block_comments,
+        })
+        .to_string()
+    };
+
+    // the inner comment ends the outer one
+    let rules = Muncher::new(&muncher_json(false), &"test".to_string()).unwrap();
+    let mut tech = blank_tech.clone();
+    count_lines(&mut tech, lines.clone(), &rules);
+    assert_eq!(tech.block_comments, 2);
+    assert_eq!(tech.code_lines, 3);
+
+    // the outer comment ends only after the inner one
+    let rules = Muncher::new(&muncher_json(true), &"test".to_string()).unwrap();
+    let mut tech = blank_tech;
+    count_lines(&mut tech, lines, &rules);
+    assert_eq!(tech.block_comments, 4);
+    assert_eq!(tech.code_lines, 1);
+}
+
+#[test]
+fn test_todo_comments() {
+    let lines = vec![
+        "// TODO: split this file".to_string(),
+        "/* FIXME".to_string(),
+        "   still a HACK */".to_string(),
+        "let todo = \"TODO\";".to_string(),
+        "// nothing to do here".to_string(),
+    ];
+    let mut tech = Tech {
+        total_lines: 5,
+        ..Tech::for_test("Rust", 0)
+    };
+    let muncher_json = serde_json::json!({
+        "language": "Rust",
+        "line_comments": ["^\\s*//"],
+        "block_comments_start": ["^\\s*/\\*"],
+        "block_comments_end": ["\\*/"],
+    })
+    .to_string();
+    let rules = Muncher::new(&muncher_json, &"test".to_string()).unwrap();
+    count_lines(&mut tech, lines, &rules);
+
+    // markers in code are not counted and the comment categories are not affected
+    assert_eq!(tech.todo_comments, 3);
+    assert_eq!(tech.line_comments, 2);
+    assert_eq!(tech.block_comments, 2);
+    assert_eq!(tech.code_lines, 1);
+}
+
+#[test]
+fn test_count_all_refs_on_a_line() {
+    let lines = ["let a = std::fs::read(x); let b = std::io::stdin();".to_string()];
+    let blank_tech = Tech {
+        total_lines: 1,
+        ..Tech::for_test("Rust", 0)
+    };
+    let std_refs = |count_all_refs: Option<bool>| {
+        let mut muncher_json = serde_json::json!({
+            "language": "Rust",
+            "refs": ["\\b(?P<name>std)::"],
+        });
+        if let Some(count_all_refs) = count_all_refs {
+            muncher_json["count_all_refs"] = serde_json::json!(count_all_refs);
+        }
+        let rules = Muncher::new(&muncher_json.to_string(), &"test".to_string()).unwrap();
+        let mut tech = blank_tech.clone();
+        count_lines(&mut tech, lines.iter(), &rules);
+        tech.refs.iter().flatten().find(|kwc| kwc.k == "std").unwrap().c
+    };
+
+    // the default is once per line
+    assert_eq!(std_refs(None), 1);
+    assert_eq!(std_refs(Some(false)), 1);
+    assert_eq!(std_refs(Some(true)), 2);
+}
+
+#[test]
+fn test_streamed_lines_match_collected_lines() {
+    let contents = "#!/usr/bin/env run-cargo-script\r\n// Copyright 2019-2021 Acme\n\nuse std::io;\nuse serde::Deserialize;\n\n/* a block\n   comment */\n/// docs\nfn main() { // entry\n    let s = \"// not a comment\";\n    println!(\"{}\", s);\n}\n";
+    let blank_tech = Tech::for_test("Rust", 0);
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    let rules = code_rules.get_muncher(&"main.rs".to_owned()).unwrap();
+
+    // the lines used to be collected into a Vec<String> before counting
+    let mut collected = blank_tech.clone();
+    let collected_languages = count_lines(
+        &mut collected,
+        contents.lines().map(|line| line.to_owned()).collect::<Vec<String>>(),
+        rules,
+    );
+    let mut streamed = blank_tech;
+    let streamed_languages = count_lines(&mut streamed, split_lines(contents), rules);
+
+    assert_eq!(streamed_languages, collected_languages);
+
+    // keyword sets are compared separately because their order is random
+    let sorted_kws = |kws: &Option<HashSet<crate::report::kwc::KeywordCounter>>| {
+        let mut kws = kws
+            .iter()
+            .flatten()
+            .map(|kw| (kw.k.clone(), kw.c))
+            .collect::<Vec<(String, u64)>>();
+        kws.sort();
+        kws
+    };
+    assert_eq!(sorted_kws(&streamed.keywords), sorted_kws(&collected.keywords));
+    assert_eq!(sorted_kws(&streamed.refs), sorted_kws(&collected.refs));
+    for tech in [&mut streamed, &mut collected].iter_mut() {
+        tech.keywords = None;
+        tech.refs = None;
     }
+    assert_eq!(serde_json::to_value(&streamed).unwrap(), serde_json::to_value(&collected).unwrap());
+    assert_eq!(streamed.code_lines, 6);
+    assert_eq!(streamed.block_comments, 2);
+}
+
+#[test]
+fn test_strip_control_chars() {
+    let strip_control_chars = crate::config::Config::COMMON_STRAY_CONTROL_CHARS.to_vec();
+    let file = b"int x;\n\x0C}\n\x0Bint y;\n";
 
-    Ok(lines)
+    let (contents, _) = decode_file(file, "main.c", false, None).unwrap();
+    assert_eq!(contents, "int x;\n\x0C}\n\x0Bint y;\n");
+    let (contents, _) = decode_file(file, "main.c", false, Some(&strip_control_chars)).unwrap();
+    assert_eq!(contents, "int x;\n}\nint y;\n");
+
+    // a form feed before a closing bracket makes it look like code
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    let cpp = code_rules.get_muncher(&"main.cpp".to_owned()).unwrap().clone();
+    let analyze = |strip_control_chars: Option<Vec<char>>| {
+        let settings = ProcessFileSettings {
+            strip_control_chars,
+            ..Default::default()
+        };
+        analyze_file(file, "main.cpp", blank_tech(&cpp, "main.cpp"), &cpp, None, &settings, &HashMap::new())
+            .unwrap()
+            .remove(0)
+    };
+    let tech = analyze(None);
+    assert_eq!((tech.code_lines, tech.bracket_only_lines), (3, 0));
+    let tech = analyze(Some(strip_control_chars));
+    assert_eq!((tech.code_lines, tech.bracket_only_lines), (2, 1));
 }
 
-/// Returns true if there is a regex and it matches the line.
-#[inline(always)]
-fn match_line(regex: &Option<Vec<Regex>>, line: &String) -> bool {
-    if let Some(v) = regex {
-        for r in v {
-            if r.is_match(&line) {
-                trace!("{}", r);
-                return true;
-            }
+#[test]
+fn test_decode_utf16() {
+    let text = "// header\nfn main() {}\n";
+    let utf8_contents = decode(text.as_bytes(), false).unwrap().0;
+    let utf8_lines = split_lines(&utf8_contents).collect::<Vec<_>>();
+
+    let utf16le = text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect::<Vec<u8>>();
+    let utf16be = text.encode_utf16().flat_map(|c| c.to_be_bytes()).collect::<Vec<u8>>();
+    let utf16le_bom = [vec![0xFF, 0xFE], utf16le.clone()].concat();
+
+    let (contents, encoding) = decode(&utf16le_bom, false).unwrap();
+    assert_eq!(encoding, "UTF-16LE");
+    assert_eq!(split_lines(&contents).collect::<Vec<_>>(), utf8_lines);
+
+    let (contents, encoding) = decode(&utf16le, false).unwrap();
+    assert_eq!(encoding, "UTF-16LE");
+    assert_eq!(split_lines(&contents).collect::<Vec<_>>(), utf8_lines);
+
+    let (contents, encoding) = decode(&utf16be, false).unwrap();
+    assert_eq!(encoding, "UTF-16BE");
+    assert_eq!(split_lines(&contents).collect::<Vec<_>>(), utf8_lines);
+
+    // plain UTF-8 is not mistaken for UTF-16
+    assert_eq!(decode(text.as_bytes(), false).unwrap().1, "UTF-8");
+}
+
+#[test]
+fn test_utf8_bom_is_stripped() {
+    let file = "\u{FEFF}// header\nfn main() {}\n".as_bytes();
+    let (contents, encoding) = decode(file, false).unwrap();
+    assert_eq!(encoding, "UTF-8");
+
+    let lines = split_lines(&contents);
+    let mut tech = Tech {
+        total_lines: 2,
+        ..Tech::for_test("Rust", 0)
+    };
+    let muncher_json = serde_json::json!({
+        "language": "Rust",
+        "line_comments": ["^//"],
+    })
+    .to_string();
+    let rules = Muncher::new(&muncher_json, &"test".to_string()).unwrap();
+
+    count_lines(&mut tech, lines, &rules);
+    assert_eq!(tech.line_comments, 1);
+    assert_eq!(tech.code_lines, 1);
+}
+
+#[test]
+fn test_exceeds_max_file_size() {
+    let max = crate::config::Config::DEFAULT_MAX_FILE_SIZE_BYTES;
+    assert!(!exceeds_max_file_size(0, max));
+    assert!(!exceeds_max_file_size(max, max));
+    assert!(exceeds_max_file_size(max + 1, max));
+}
+
+#[test]
+fn test_regex_sets_match_regex_lists() {
+    let lines = [
+        "use std::collections::HashMap;",
+        "/// Adds up the values in the map.",
+        "// a line comment",
+        "let x = 1; // an inline comment",
+        "/* a block comment",
+        "   ends here */",
+        "}",
+        "",
+        "    ",
+        "    total += value; \\",
+        "#!/usr/bin/env python",
+        "# a python comment",
+        "\"\"\"a docstring\"\"\"",
+        "<!-- an html comment -->",
+    ];
+
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    for file_name in ["a.rs", "a.py", "a.cs", "a.html", "a.sh"].iter() {
+        let rules = code_rules.get_muncher(&file_name.to_string()).unwrap().clone();
+        let categories = [
+            (&rules.bracket_only_regex, &rules.bracket_only_regex_set),
+            (&rules.line_comments_regex, &rules.line_comments_regex_set),
+            (&rules.inline_comments_regex, &rules.inline_comments_regex_set),
+            (&rules.doc_comments_regex, &rules.doc_comments_regex_set),
+            (&rules.block_comments_start_regex, &rules.block_comments_start_regex_set),
+            (&rules.block_comments_end_regex, &rules.block_comments_end_regex_set),
+            (&rules.blank_line_regex, &rules.blank_line_regex_set),
+            (&rules.line_continuation_regex, &rules.line_continuation_regex_set),
+        ];
+
+        // the number of matching lines per category must be the same for the list and the set
+        for (regex_list, regex_set) in categories.iter() {
+            assert_eq!(regex_list.is_some(), regex_set.is_some());
+            let list_matches = lines
+                .iter()
+                .filter(|line| regex_list.iter().flatten().any(|r| r.is_match(line)))
+                .count();
+            let set_matches = lines.iter().filter(|line| match_line(regex_set, line)).count();
+            assert_eq!(list_matches, set_matches, "{} {:?}", file_name, regex_list);
+        }
+    }
+}
+
+#[test]
+fn test_avg_line_length_of_minified_file() {
+    let max = crate::config::Config::DEFAULT_MAX_AVG_LINE_LENGTH;
+
+    // a typical minified bundle is a single very long line
+    let minified = vec!["var a=1;".repeat(5 * 1024)];
+    assert_eq!(avg_line_length(&minified), 40 * 1024);
+    assert!(avg_line_length(&minified) > max);
+
+    // a normal file stays under the limit even with the odd long line
+    let mut normal = ["function main() {", "    return 1;", "}"]
+        .repeat(50)
+        .into_iter()
+        .map(|line| line.to_owned())
+        .collect::<Vec<String>>();
+    normal.push("x".repeat(2000));
+    assert!(avg_line_length(&normal) <= max);
+
+    assert_eq!(avg_line_length(Vec::<String>::new()), 0);
+}
+
+#[test]
+fn test_is_generated() {
+    let markers = crate::code_rules::CodeRules::new().generated_markers;
+
+    let protobuf_go = "// Code generated by protoc-gen-go. DO NOT EDIT.\n// versions:\n// \tprotoc v3.19.1\n// source: user.proto\n\npackage user\n";
+    assert!(is_generated(protobuf_go, &markers));
+
+    // the marker in the body of a normal file is not a header
+    let mut normal = "package main\n\nimport \"fmt\"\n\nfunc main() {\n".to_owned();
+    normal.push_str(&"    fmt.Println(1)\n".repeat(Muncher::GENERATED_MARKER_LINES));
+    normal.push_str("    // Code generated by hand. DO NOT EDIT.\n}\n");
+    assert!(!is_generated(&normal, &markers));
+
+    assert!(!is_generated(protobuf_go, &[]));
+
+    // the code lines of a generated file are also counted as generated code
+    let go = crate::code_rules::CodeRules::new()
+        .get_muncher(&"user.pb.go".to_owned())
+        .unwrap()
+        .clone();
+    let settings = ProcessFileSettings {
+        generated_markers: markers,
+        ..Default::default()
+    };
+    let techs = analyze_file(
+        protobuf_go.as_bytes(),
+        "user.pb.go",
+        blank_tech(&go, "user.pb.go"),
+        &go,
+        None,
+        &settings,
+        &HashMap::new(),
+    )
+    .unwrap();
+    assert!(techs[0].is_generated);
+    assert_eq!(techs[0].generated_code_lines, 1);
+    assert_eq!(techs[0].code_lines, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_process_files_matches_serial_processing() {
+    let repo = crate::utils::TestRepo::new("process_files");
+    let project_dir = repo.dir.clone();
+
+    // files of different length with a mix of code, comments and blank lines
+    let file_names = (0..300).map(|i| format!("src/file_{}.rs", i)).collect::<Vec<String>>();
+    for (i, file_name) in file_names.iter().enumerate() {
+        let mut contents = String::from("// header\nuse std::collections::HashMap;\n\n");
+        for j in 0..i % 17 {
+            contents.push_str(&format!("fn f{}() {{\n    /* block */\n    let x = {};\n}}\n\n", j, i));
+        }
+        repo.write(file_name, contents);
+    }
+    let mut args = vec!["hash-object", "-w"];
+    args.extend(file_names.iter().map(|v| v.as_str()));
+    let blob_sha1s = repo.git(&args).lines().map(|v| v.to_owned()).collect::<Vec<String>>();
+    assert_eq!(blob_sha1s.len(), file_names.len());
+
+    let muncher = Arc::new(
+        crate::code_rules::CodeRules::new()
+            .get_muncher(&"main.rs".to_owned())
+            .unwrap()
+            .clone(),
+    );
+    // keywords are in a HashSet with no particular order
+    let summary = |techs: Vec<Tech>| {
+        techs
+            .into_iter()
+            .map(|tech| {
+                let mut keywords = tech
+                    .keywords
+                    .iter()
+                    .flatten()
+                    .map(|kwc| (kwc.k.clone(), kwc.c))
+                    .collect::<Vec<_>>();
+                keywords.sort();
+                (
+                    tech.file_name.clone(),
+                    tech.language.clone(),
+                    [
+                        tech.total_lines,
+                        tech.code_lines,
+                        tech.blank_lines,
+                        tech.block_comments,
+                        tech.line_comments,
+                    ],
+                    keywords,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let settings = ProcessFileSettings::default();
+    let files = file_names
+        .iter()
+        .zip(blob_sha1s.iter())
+        .map(|(file_name, sha1)| FileToProcess {
+            file_name: file_name.clone(),
+            blob: GitBlob {
+                sha1: sha1.clone(),
+                commit_sha1: "commit_sha1".to_owned(),
+                commit_date_epoch: 1,
+                commit_date_iso: "2021-01-01T00:00:00+00:00".to_owned(),
+                size: None,
+            },
+            muncher: muncher.clone(),
+            fence_munchers: HashMap::new(),
+            contents: None,
+        })
+        .collect::<Vec<FileToProcess>>();
+
+    let mut serial = Vec::new();
+    for file in &files {
+        let techs = process_file(file, &project_dir, None, &ProcessFileSettings::default()).await;
+        serial.push(summary(techs.unwrap()));
+    }
+
+    let mut concurrent = Vec::new();
+    for task in spawn_process_files(files, &project_dir, None, &settings, 8, None) {
+        concurrent.push(summary(join_processed_file(task).await.unwrap()));
+    }
+
+    // the same records in the same order
+    assert_eq!(concurrent, serial);
+}
+
+#[tokio::test]
+async fn test_get_file_contents_errors() {
+    let repo = crate::utils::TestRepo::new("file_contents");
+    let project_dir = repo.dir.clone();
+
+    // a PNG header is not valid UTF-8 and has NUL bytes
+    repo.write("logo.png", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\xff\xfe\0");
+    let binary_sha1 = repo.git(&["hash-object", "-w", "logo.png"]);
+    repo.write("empty.rs", b"");
+    let empty_sha1 = repo.git(&["hash-object", "-w", "empty.rs"]);
+
+    let file_name = "logo.png".to_owned();
+    assert_eq!(
+        get_file_contents(&file_name, &test_blob(&binary_sha1), &project_dir, false, None, false, None).await,
+        Err(ProcessError::Binary)
+    );
+    assert_eq!(
+        get_file_contents(&file_name, &test_blob(&empty_sha1), &project_dir, false, None, false, None).await,
+        Err(ProcessError::Empty)
+    );
+
+    let missing_sha1 = "0123456789abcdef0123456789abcdef01234567".to_owned();
+    assert_eq!(
+        get_file_contents(&file_name, &test_blob(&missing_sha1), &project_dir, false, None, false, None).await,
+        Err(ProcessError::BlobRead(missing_sha1.clone()))
+    );
+
+    // a blob over the limit is not read if its size is known from the tree listing
+    let missing_blob = GitBlob {
+        size: Some(1000),
+        ..test_blob(&missing_sha1)
+    };
+    assert_eq!(
+        get_file_contents(&file_name, &missing_blob, &project_dir, false, Some(100), false, None).await,
+        Err(ProcessError::TooLarge { size: 1000, limit: 100 })
+    );
+    // or after it is loaded otherwise
+    assert_eq!(
+        get_file_contents(&file_name, &test_blob(&binary_sha1), &project_dir, false, Some(10), false, None).await,
+        Err(ProcessError::TooLarge { size: 19, limit: 10 })
+    );
+}
+
+/// Returns a blob with no commit info and an unknown size for calling `process_file` in tests.
+#[cfg(test)]
+fn test_blob(blob_sha1: &str) -> GitBlob {
+    GitBlob {
+        sha1: blob_sha1.to_owned(),
+        commit_sha1: String::new(),
+        commit_date_epoch: 0,
+        commit_date_iso: String::new(),
+        size: None,
+    }
+}
+
+/// Returns a file for `process_file` with `test_blob` and no fence munchers that is read from the repo.
+#[cfg(test)]
+fn test_file(file_name: &str, blob_sha1: &str, muncher: &Muncher) -> FileToProcess {
+    FileToProcess {
+        file_name: file_name.to_owned(),
+        blob: test_blob(blob_sha1),
+        muncher: Arc::new(muncher.clone()),
+        fence_munchers: HashMap::new(),
+        contents: None,
+    }
+}
+
+#[tokio::test]
+async fn test_process_untracked_file_from_disk() {
+    let repo = crate::utils::TestRepo::new("working_tree");
+    let project_dir = repo.dir.clone();
+
+    // ANSI text is not valid UTF-8 and needs the same fallback as the blobs
+    let file_name = "main.rs".to_owned();
+    std::fs::write(
+        project_dir.join(&file_name),
+        b"// caf\xe9\nuse std::io;\n\nfn main() {\n    /* block */\n    let x = 1;\n}\n",
+    )
+    .unwrap();
+    let blob_sha1 = repo.git(&["hash-object", "-w", "main.rs"]);
+    assert!(repo.git(&["status", "--porcelain"]).starts_with("??"));
+
+    let muncher = crate::code_rules::CodeRules::new()
+        .get_muncher(&file_name)
+        .unwrap()
+        .clone();
+    let settings = ProcessFileSettings {
+        max_file_size_bytes: Some(1024),
+        ..Default::default()
+    };
+    let process = |blob_sha1: String| {
+        let muncher = muncher.clone();
+        let settings = settings.clone();
+        let project_dir = project_dir.clone();
+        let file_name = file_name.clone();
+        async move {
+            process_file(&test_file(&file_name, &blob_sha1, &muncher), &project_dir, None, &settings)
+                .await
+                .unwrap()
+        }
+    };
+
+    // an untracked file has no blob SHA1
+    let from_disk = process(String::new()).await;
+    let from_blob = process(blob_sha1).await;
+    // Tech equality only compares the name, language and file, so the counts are compared via serialization
+    assert_eq!(serde_json::to_value(&from_disk).unwrap(), serde_json::to_value(&from_blob).unwrap());
+    assert_eq!(from_disk[0].detected_encoding.as_deref(), Some("windows-1252"));
+    assert_eq!(from_disk[0].code_lines, 4);
+    assert_eq!(from_disk[0].total_lines, 7);
+
+    // a missing file is an error, not an empty record
+    let missing = "missing.rs".to_owned();
+    assert_eq!(
+        get_file_contents(&missing, &test_blob(""), &project_dir, true, None, false, None).await,
+        Err(ProcessError::FileRead(missing.clone()))
+    );
+}
+
+#[tokio::test]
+async fn test_analyze_bytes_matches_git_blob() {
+    let repo = crate::utils::TestRepo::new("analyze_bytes");
+    let project_dir = repo.dir.clone();
+
+    let files: [(&str, &[u8]); 4] = [
+        (
+            "main.rs",
+            b"//! docs\nuse std::collections::HashMap;\n\nfn main() {\n    /* block */\n    let x = 1; // inline\n}\n",
+        ),
+        ("setup.py", b"#!/usr/bin/env python\nimport os\n\ndef main():\n    \"\"\"docs\"\"\"\n    pass\n"),
+        // UTF-16 with a BOM and ANSI need the encoding fallback
+        ("utf16.cs", b"\xff\xfeu\0s\0i\0n\0g\0 \0S\0y\0s\0t\0e\0m\0;\0\n\0"),
+        ("ansi.sh", b"# caf\xe9\necho hi\n"),
+    ];
+
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    for (file_name, contents) in files.iter() {
+        repo.write(file_name, contents);
+        let blob_sha1 = repo.git(&["hash-object", "-w", file_name]);
+        let file_name = file_name.to_string();
+        let muncher = code_rules.get_muncher(&file_name).unwrap().clone();
+
+        let from_blob = process_file(
+            &test_file(&file_name, &blob_sha1, &muncher),
+            &project_dir,
+            None,
+            &ProcessFileSettings::default(),
+        )
+        .await
+        .unwrap()
+        .remove(0);
+        let from_bytes = analyze_bytes(contents, &muncher, &file_name);
+
+        // `Tech` equality only compares the names, the JSON has all the counts
+        assert!(from_bytes.commit_sha1.is_none());
+        let from_blob = Tech {
+            commit_sha1: None,
+            commit_date_epoch: None,
+            commit_date_iso: None,
+            ..from_blob
+        };
+        assert!(from_bytes.total_lines > 0, "{}", file_name);
+        assert_eq!(
+            serde_json::to_value(&from_bytes).unwrap(),
+            serde_json::to_value(&from_blob).unwrap(),
+            "{}",
+            file_name
+        );
+    }
+
+    // no content is not an error
+    let muncher = code_rules.get_muncher(&"main.rs".to_owned()).unwrap().clone();
+    let empty = analyze_bytes(b"", &muncher, "main.rs");
+    assert_eq!(empty.total_lines, 0);
+    assert_eq!(empty.files, 1);
+
+    // file names are stored with `/` regardless of the separators passed in
+    let windows_path = analyze_bytes(b"fn main() {}\n", &muncher, "src\\main.rs");
+    assert_eq!(windows_path.file_name.as_deref(), Some("src/main.rs"));
+}
+
+#[test]
+fn test_munch_fenced_code_in_readme() {
+    let readme = r#"# Demo
+
+Install it with:
+
+```bash
+# install the binary from crates.io
+cargo install demo
+```
+
+Then call it from your code:
+
+```rust
+use demo::run;
+
+fn main() {
+    run();
+}
+```
+
+```text
+demo output
+```
+"#;
+
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    let markdown = code_rules.get_muncher(&"README.md".to_owned()).unwrap().clone();
+    let mut fence_munchers = HashMap::new();
+    for tag in ["bash", "rust", "text"].iter() {
+        if let Some(muncher) = code_rules.get_muncher_for_fence_tag(tag) {
+            fence_munchers.insert(tag.to_string(), Arc::new(muncher.clone()));
+        }
+    }
+    // unknown tags are left as Markdown
+    assert_eq!(fence_munchers.len(), 2);
+
+    let tech = blank_tech(&markdown, "README.md");
+    let techs = analyze_file(
+        readme.as_bytes(),
+        "README.md",
+        tech,
+        &markdown,
+        None,
+        &ProcessFileSettings::default(),
+        &fence_munchers,
+    )
+    .unwrap();
+
+    let languages = techs.iter().map(|tech| tech.language.as_str()).collect::<Vec<&str>>();
+    assert_eq!(languages, vec!["Markdown", "Rust", "Shell"]);
+    // 22 lines in total less 7 lines of Rust and Bash code
+    assert_eq!(techs[0].total_lines, 15);
+    assert_eq!(techs[0].files, 1);
+    assert!(techs[0].line_ranges.is_none());
+
+    let rust = &techs[1];
+    assert_eq!(rust.total_lines, 5);
+    assert_eq!(rust.code_lines, 3);
+    assert_eq!(rust.bracket_only_lines, 1);
+    assert_eq!(rust.blank_lines, 1);
+    assert_eq!(rust.files, 0);
+    assert_eq!(rust.muncher_name, "rust.rs");
+    // the lines inside the fences as numbered in the file
+    assert_eq!(rust.line_ranges, Some(vec![(13, 17)]));
+
+    let bash = &techs[2];
+    assert_eq!(bash.total_lines, 2);
+    assert_eq!(bash.code_lines, 1);
+    assert_eq!(bash.line_comments, 1);
+    assert_eq!(bash.line_ranges, Some(vec![(6, 7)]));
+}
+
+#[tokio::test]
+async fn test_duplicated_lines_across_files() {
+    let repo = crate::utils::TestRepo::new("duplicated_lines");
+    let project_dir = repo.dir.clone();
+
+    // the same 10 lines with different whitespace
+    let mut a = String::from("fn a() {\n");
+    let mut b = String::from("fn b() {\n");
+    for i in 0..10 {
+        a.push_str(&format!("    let v{} = {} * 2;\n", i, i));
+        b.push_str(&format!("        let v{}  =  {} * 2;\n", i, i));
+    }
+    a.push_str("}\n");
+    b.push_str("    let unique = 1;\n}\n");
+    repo.write("a.rs", a);
+    repo.write("b.rs", b);
+    repo.commit_all("init");
+
+    let process = |detect_duplicate_lines: bool| {
+        let project_dir = project_dir.clone();
+        async move {
+            let mut code_rules = crate::code_rules::CodeRules::new();
+            code_rules.detect_duplicate_lines = detect_duplicate_lines;
+            crate::report::Report::process_project(&mut code_rules, &project_dir, &None, None)
+                .await
+                .unwrap()
+                .unwrap()
         }
+    };
+    let duplicated_lines = |report: &crate::report::Report, file_name: &str| {
+        report
+            .per_file_tech
+            .iter()
+            .find(|tech| tech.file_name.as_deref() == Some(file_name))
+            .unwrap()
+            .duplicated_lines
+    };
+
+    // the first file in the order of names has the originals
+    let report = process(true).await;
+    assert_eq!(duplicated_lines(&report, "a.rs"), 0);
+    assert_eq!(duplicated_lines(&report, "b.rs"), 10);
+    assert_eq!(report.totals().code_lines, 23);
+    assert_eq!(report.totals().duplicated_lines, 10);
+    assert_eq!(report.duplicate_ratio(), 10.0 / 23.0);
+    // the hashes are not kept after processing
+    assert!(report.per_file_tech.iter().all(|tech| tech.code_line_hashes.is_none()));
+
+    let report = process(false).await;
+    assert_eq!(duplicated_lines(&report, "b.rs"), 0);
+    assert_eq!(report.duplicate_ratio(), 0.0);
+}
+
+#[tokio::test]
+async fn test_identical_blobs_are_munched_once() {
+    let repo = crate::utils::TestRepo::new("identical_blobs");
+    let project_dir = repo.dir.clone();
+    let contents = "fn main() {\n    let x = 1;\n}\n";
+    repo.write("main.rs", contents);
+    repo.write("vendor/main.rs", contents);
+    repo.write("lib.rs", "fn lib() {}\n");
+    repo.commit_all("init");
+    let blob_sha1 = repo.git(&["rev-parse", "HEAD:main.rs"]);
+    assert_eq!(repo.git(&["rev-parse", "HEAD:vendor/main.rs"]), blob_sha1);
+
+    for max_concurrent_files in [1, 4].iter() {
+        let mut code_rules = crate::code_rules::CodeRules::new();
+        code_rules.max_concurrent_files = *max_concurrent_files;
+        let report = crate::report::Report::process_project(&mut code_rules, &project_dir, &None, None)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // the copy has its own record with its own name
+        let copies = report
+            .per_file_tech
+            .iter()
+            .filter(|tech| tech.file_name.as_deref().is_some_and(|v| v.ends_with("main.rs")))
+            .collect::<Vec<&Tech>>();
+        assert_eq!(copies.len(), 2);
+        assert_eq!(serde_json::to_value(copies[0]).unwrap()["code_lines"], 2);
+        assert_eq!(
+            serde_json::to_value(copies[0]).unwrap()["keywords"],
+            serde_json::to_value(copies[1]).unwrap()["keywords"]
+        );
+        assert_eq!(report.tech().iter().map(|tech| tech.files).sum::<u64>(), 3);
+
+        // main.rs and lib.rs, but not the copy
+        assert_eq!(code_rules.munched_files, 2, "max_concurrent_files: {}", max_concurrent_files);
+    }
+}
+
+#[test]
+fn test_max_avg_line_length_per_muncher() {
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    let long_line = "word ".repeat(100);
+    let contents = [long_line.as_str(), long_line.as_str()].join("\n");
+
+    let settings = ProcessFileSettings {
+        max_avg_line_length: Some(200),
+        ..Default::default()
+    };
+
+    // docs with a paragraph per line are munched, code and CSS with lines that long are likely minified
+    for (file_name, is_minified) in [("README.md", false), ("main.rs", true), ("style.css", true)].iter() {
+        let muncher = code_rules.get_muncher(&file_name.to_string()).unwrap().clone();
+        let techs = analyze_file(
+            contents.as_bytes(),
+            file_name,
+            blank_tech(&muncher, file_name),
+            &muncher,
+            None,
+            &settings,
+            &HashMap::new(),
+        );
+        assert_eq!(matches!(techs, Err(ProcessError::Minified { .. })), *is_minified, "{}", file_name);
     }
+}
+
+#[test]
+fn test_results_hash() {
+    let settings = ProcessFileSettings::default();
+    let tree = |files: &[&str]| files.iter().map(|v| v.to_string()).collect::<HashSet<String>>();
+    let hash = settings.results_hash(Some(&tree(&["a.py", "b.py"])));
+
+    // the order of the files in the set does not matter
+    assert_eq!(hash, settings.results_hash(Some(&tree(&["b.py", "a.py"]))));
+    assert_ne!(hash, settings.results_hash(Some(&tree(&["a.py", "b.py", "c.py"]))));
+    assert_ne!(hash, settings.results_hash(None));
+
+    let changes = vec![
+        ProcessFileSettings {
+            strip_control_chars: Some(vec!['\x0c']),
+            ..Default::default()
+        },
+        ProcessFileSettings {
+            track_ref_first_seen: true,
+            ..Default::default()
+        },
+        ProcessFileSettings {
+            max_file_size_bytes: Some(1000),
+            ..Default::default()
+        },
+        ProcessFileSettings {
+            max_avg_line_length: Some(200),
+            ..Default::default()
+        },
+        ProcessFileSettings {
+            generated_markers: vec![Regex::new("@generated").unwrap()],
+            ..Default::default()
+        },
+    ];
+    for changed in changes {
+        assert_ne!(hash, changed.results_hash(Some(&tree(&["a.py", "b.py"]))));
+    }
+}
+
+#[test]
+fn test_code_before_block_comment_with_shipped_munchers() {
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    let rust = code_rules.get_muncher(&"main.rs".to_owned()).unwrap().clone();
+    let js = code_rules.get_muncher(&"app.js".to_owned()).unwrap().clone();
+
+    let rust_code = r#"let x = 5; /* set x */
+/* a full line comment */
+let y = x; /* opens a block
+   that ends here */
+let glob = "src/*.rs";
+call(/* unused */ 0);
+// not a block /* comment */
+/*
+ * a doc block
+ */
+"#;
+    let tech = analyze_bytes(rust_code.as_bytes(), &rust, "main.rs");
+    assert_eq!(tech.code_lines, 4);
+    assert_eq!(tech.block_comments, 5);
 
-    // no match found
-    false
+    let js_code = "const url = '/*'; /* not a string */\nconst x = 1;\n";
+    let tech = analyze_bytes(js_code.as_bytes(), &js, "app.js");
+    assert_eq!(tech.code_lines, 2);
+    assert_eq!(tech.block_comments, 0);
 }
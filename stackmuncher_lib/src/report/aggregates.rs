@@ -0,0 +1,275 @@
+use super::{Report, Tech};
+use crate::code_rules::CodeRules;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::Mutex;
+
+/// Line and file counts added up across all `tech` records of a report.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ReportTotals {
+    pub files: u64,
+    pub total_lines: u64,
+    pub code_lines: u64,
+    pub blank_lines: u64,
+    /// The sum of inline, line, block and docs comments
+    pub comment_lines: u64,
+    /// Code lines seen in another file, see `Tech.duplicated_lines`
+    pub duplicated_lines: u64,
+}
+
+/// Values derived from `Report.tech` that are expensive enough to be worth computing once.
+#[derive(Debug, Clone)]
+pub(crate) struct ReportAggregates {
+    /// The value of `Report.tech_generation` the aggregates were computed for
+    generation: u64,
+    totals: ReportTotals,
+    /// LoC per language with records from different munchers for the same language added up
+    language_loc: BTreeMap<String, u64>,
+    primary_language: Option<String>,
+}
+
+/// A memoization slot for `ReportAggregates`. It is never serialized and starts empty in clones,
+/// so the aggregates are recomputed on first use after deserialization or cloning.
+#[derive(Default)]
+pub(crate) struct AggregatesCache(Mutex<Option<ReportAggregates>>);
+
+impl Clone for AggregatesCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl std::fmt::Debug for AggregatesCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AggregatesCache")
+    }
+}
+
+impl ReportAggregates {
+    /// Computes all aggregates from scratch.
+    fn new(report: &Report) -> Self {
+        let mut totals = ReportTotals::default();
+        let mut language_loc: BTreeMap<String, u64> = BTreeMap::new();
+
+        for tech in &report.tech {
+            totals.files += tech.files;
+            totals.total_lines += tech.total_lines;
+            totals.code_lines += tech.code_lines;
+            totals.blank_lines += tech.blank_lines;
+            totals.duplicated_lines += tech.duplicated_lines;
+            totals.comment_lines +=
+                tech.inline_comments + tech.line_comments + tech.block_comments + tech.docs_comments;
+            *language_loc.entry(tech.language.clone()).or_default() += tech.code_lines;
+        }
+
+        // the largest LoC wins, ties go to the name that comes first a-z for a stable result
+        let primary_language = language_loc
+            .iter()
+            .filter(|(_, loc)| **loc > 0)
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(language, _)| language.clone());
+
+        ReportAggregates {
+            generation: report.tech_generation,
+            totals,
+            language_loc,
+            primary_language,
+        }
+    }
+}
+
+impl Report {
+    /// Returns the combined summary per technology.
+    pub fn tech(&self) -> &HashSet<Tech> {
+        &self.tech
+    }
+
+    /// Returns the combined summary per technology for modification.
+    /// The memoized aggregates are marked as stale on every call.
+    pub fn tech_mut(&mut self) -> &mut HashSet<Tech> {
+        self.invalidate_aggregates();
+        &mut self.tech
+    }
+
+    /// Marks the memoized aggregates as stale. It has to be called after modifying `tech` directly
+    /// rather than via `tech_mut()`.
+    pub fn invalidate_aggregates(&mut self) {
+        self.tech_generation = self.tech_generation.wrapping_add(1);
+    }
+
+    /// Runs `f` over the memoized aggregates, recomputing them first if `tech` changed since the last run.
+    fn with_aggregates<T, F: FnOnce(&ReportAggregates) -> T>(&self, f: F) -> T {
+        // a poisoned lock only means another thread panicked mid-computation, the slot is overwritten below anyway
+        let mut cached = self.aggregates_cache.0.lock().unwrap_or_else(|e| e.into_inner());
+
+        match cached.as_ref() {
+            Some(aggregates) if aggregates.generation == self.tech_generation => {}
+            _ => *cached = Some(ReportAggregates::new(self)),
+        }
+
+        f(cached.as_ref().expect("aggregates were computed above"))
+    }
+
+    /// Returns line and file counts added up across all `tech` records.
+    pub fn totals(&self) -> ReportTotals {
+        self.with_aggregates(|aggregates| aggregates.totals.clone())
+    }
+
+    /// Returns `duplicated_lines` as a fraction of all `code_lines` in the range of 0..1 or 0.0 if there are no code lines.
+    /// It is only meaningful for reports processed with `Config.detect_duplicate_lines`.
+    pub fn duplicate_ratio(&self) -> f64 {
+        self.with_aggregates(|aggregates| {
+            if aggregates.totals.code_lines == 0 {
+                return 0.0;
+            }
+
+            aggregates.totals.duplicated_lines as f64 / aggregates.totals.code_lines as f64
+        })
+    }
+
+    /// Returns the share of each language in the total `code_lines` as a percentage rounded to 2 decimal places,
+    /// largest first. Returns an empty list if there are no code lines at all.
+    pub fn language_percentages(&self) -> Vec<(String, f64)> {
+        self.with_aggregates(|aggregates| {
+            let total_loc = aggregates.totals.code_lines;
+            if total_loc == 0 {
+                return Vec::new();
+            }
+
+            let mut percentages = aggregates
+                .language_loc
+                .iter()
+                .map(|(language, loc)| {
+                    let percentage = *loc as f64 * 100.0 / total_loc as f64;
+                    (language.clone(), (percentage * 100.0).round() / 100.0)
+                })
+                .collect::<Vec<(String, f64)>>();
+            // language_loc is sorted a-z and the sort is stable, so languages with equal shares stay a-z
+            percentages.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+            percentages
+        })
+    }
+
+    /// Returns the number of docs, block and line comments per line of code across all `tech` records.
+    /// See `Tech::doc_ratio()` for details. Returns 0.0 if there are no code lines.
+    pub fn doc_ratio(&self) -> f64 {
+        let (doc_lines, code_lines) = self.tech.iter().fold((0u64, 0u64), |(doc_lines, code_lines), tech| {
+            (
+                doc_lines + tech.docs_comments + tech.block_comments + tech.line_comments,
+                code_lines + tech.code_lines,
+            )
+        });
+        if code_lines == 0 {
+            return 0.0;
+        }
+
+        doc_lines as f64 / code_lines as f64
+    }
+
+    /// Returns the language with the most `code_lines` or None if there is no code in the report.
+    pub fn primary_language(&self) -> Option<String> {
+        self.with_aggregates(|aggregates| aggregates.primary_language.clone())
+    }
+
+    /// Returns up to `n` languages with the most `code_lines`, largest first, with ties sorted by name a-z.
+    /// Languages with no code lines are not included.
+    pub fn top_languages(&self, n: usize) -> Vec<(String, usize)> {
+        self.with_aggregates(|aggregates| {
+            let mut languages = aggregates
+                .language_loc
+                .iter()
+                .filter(|(_, loc)| **loc > 0)
+                .map(|(language, loc)| (language.clone(), *loc as usize))
+                .collect::<Vec<(String, usize)>>();
+            // language_loc is sorted a-z and the sort is stable, so languages with equal LoC stay a-z
+            languages.sort_by_key(|v| Reverse(v.1));
+            languages.truncate(n);
+
+            languages
+        })
+    }
+}
+
+/// Returns every language found in `reports` with the number of reports that contain it, sorted a-z.
+/// A report is counted once per language no matter how many `tech` records it has for that language.
+/// Language names from older rule sets are canonicalized with `CodeRules::canonical_language()`.
+pub fn languages_across_reports(reports: &[Report]) -> BTreeMap<String, usize> {
+    let mut languages: BTreeMap<String, usize> = BTreeMap::new();
+
+    for report in reports {
+        let report_languages = report
+            .tech
+            .iter()
+            .map(|tech| CodeRules::canonical_language(&tech.language).unwrap_or(&tech.language))
+            .collect::<BTreeSet<&str>>();
+
+        for language in report_languages {
+            *languages.entry(language.to_owned()).or_default() += 1;
+        }
+    }
+
+    languages
+}
+
+#[test]
+fn test_language_percentages() {
+    let mut report = Report::new();
+    report.tech_mut().insert(Tech::for_test("Markdown", 0));
+    assert!(report.language_percentages().is_empty());
+
+    report.tech_mut().insert(Tech::for_test("Go", 600));
+    report.tech_mut().insert(Tech::for_test("Rust", 200));
+    report.tech_mut().insert(Tech::for_test("Shell", 100));
+    report.tech_mut().insert(Tech::for_test("Python", 100));
+
+    let percentages = report.language_percentages();
+    assert_eq!(percentages[0], ("Go".to_owned(), 60.0));
+    // equal shares are sorted a-z
+    assert_eq!(percentages[2], ("Python".to_owned(), 10.0));
+    let total = percentages.iter().map(|(_, percentage)| percentage).sum::<f64>();
+    assert!((total - 100.0).abs() < 0.01);
+
+    // 1 / 3 of the total
+    report.tech_mut().insert(Tech::for_test("C", 500));
+    assert!(report.language_percentages().contains(&("C".to_owned(), 33.33)));
+
+    // direct changes to `tech` are picked up after invalidation
+    report.tech.insert(Tech::for_test("Java", 1500));
+    report.invalidate_aggregates();
+    assert_eq!(report.language_percentages()[0], ("Java".to_owned(), 50.0));
+}
+
+#[test]
+fn test_languages_across_reports() {
+    assert!(languages_across_reports(&[]).is_empty());
+
+    let report = |languages: &[&str]| {
+        let mut report = Report::new();
+        for (idx, language) in languages.iter().enumerate() {
+            // a different muncher makes a separate record for the same language
+            report.tech_mut().insert(Tech {
+                muncher_name: format!("muncher{}", idx),
+                ..Tech::for_test(language, 10)
+            });
+        }
+        report
+    };
+
+    // Rust is counted once for the 1st report even though it has 2 records
+    // and the old names are counted together with the current ones
+    let reports = vec![
+        report(&["Rust", "Rust", "JS"]),
+        report(&["JavaScript", "Go"]),
+        report(&["Golang", "Go"]),
+    ];
+    let languages = languages_across_reports(&reports);
+    assert_eq!(
+        languages.into_iter().collect::<Vec<(String, usize)>>(),
+        vec![
+            ("Go".to_owned(), 2),
+            ("JavaScript".to_owned(), 2),
+            ("Rust".to_owned(), 1)
+        ]
+    );
+}
@@ -0,0 +1,127 @@
+use super::{Report, Tech};
+use crate::contributor::Contributor;
+use std::collections::{BTreeMap, HashMap};
+
+impl Report {
+    /// The header row of `contributors_csv()`. The order of columns is fixed.
+    pub const CONTRIBUTORS_CSV_HEADER: &'static str = "git_id,commit_count,top_language,code_lines";
+
+    /// Returns a CSV with one row per contributor sorted by `git_id`: git_id, commit_count, top language, code_lines.
+    /// All lines of code of every file touched by the contributor are attributed to them, so the totals across
+    /// contributors may exceed the project LoC. The report must have `per_file_tech` for the numbers to be meaningful.
+    /// Returns only the header if there are no contributors.
+    pub fn contributors_csv(&self) -> String {
+        let mut csv = String::from(Report::CONTRIBUTORS_CSV_HEADER);
+        csv.push('\n');
+
+        let contributors = match self.contributors.as_ref() {
+            Some(v) => v,
+            None => return csv,
+        };
+
+        // per_file_tech is a set, so file names have to be indexed for lookups
+        let per_file_tech = self
+            .per_file_tech
+            .iter()
+            .filter_map(|tech| Some((tech.file_name.as_ref()?, tech)))
+            .collect::<HashMap<&String, &Tech>>();
+
+        let mut contributors = contributors.iter().collect::<Vec<&Contributor>>();
+        contributors.sort_by(|a, b| a.git_id.cmp(&b.git_id));
+
+        for contributor in contributors {
+            // LoC per language for the files touched by this contributor
+            let mut loc_per_language: BTreeMap<&String, u64> = BTreeMap::new();
+            for file in &contributor.touched_files {
+                if let Some(tech) = per_file_tech.get(&file.name) {
+                    *loc_per_language.entry(&tech.language).or_default() += tech.code_lines;
+                }
+            }
+
+            // ties go to the language that comes first a-z
+            let top_language = loc_per_language
+                .iter()
+                .filter(|(_, loc)| **loc > 0)
+                .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+                .map(|(language, _)| language.as_str())
+                .unwrap_or_default();
+            let code_lines = loc_per_language.values().sum::<u64>();
+
+            csv.push_str(&csv_field(&contributor.git_id));
+            csv.push(',');
+            csv.push_str(&contributor.commit_count.to_string());
+            csv.push(',');
+            csv.push_str(&csv_field(top_language));
+            csv.push(',');
+            csv.push_str(&code_lines.to_string());
+            csv.push('\n');
+        }
+
+        csv
+    }
+}
+
+/// Quotes the value if it contains a delimiter, a quote or a line break, as per RFC 4180.
+pub(super) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        ["\"", &value.replace('"', "\"\""), "\""].concat()
+    } else {
+        value.to_string()
+    }
+}
+
+#[test]
+fn test_contributors_csv() {
+    use crate::contributor::ContributorFile;
+    use std::collections::HashSet;
+
+    let contributor = |git_id: &str, commit_count: u64, files: &[&str]| Contributor {
+        git_id: git_id.to_owned(),
+        name_email_pairs: HashSet::new(),
+        last_commit_sha1: String::new(),
+        last_commit_epoch: 0,
+        last_commit_date: String::new(),
+        commit_count,
+        lines_added: 0,
+        lines_removed: 0,
+        commit_type_counts: None,
+        touched_files: files
+            .iter()
+            .map(|name| ContributorFile {
+                name: name.to_string(),
+                commit: String::new(),
+                date_epoch: 0,
+                date_iso: String::new(),
+            })
+            .collect(),
+        commits: Vec::new(),
+    };
+    let file_tech = |file_name: &str, language: &str, code_lines: u64| Tech {
+        file_name: Some(file_name.to_owned()),
+        ..Tech::for_test(language, code_lines)
+    };
+
+    let mut report = Report::new();
+    assert_eq!(report.contributors_csv(), "git_id,commit_count,top_language,code_lines\n");
+
+    report.per_file_tech.insert(file_tech("main.rs", "Rust", 30));
+    report.per_file_tech.insert(file_tech("main.go", "Go", 20));
+    report.per_file_tech.insert(file_tech("lib.go", "Go", 20));
+    report.contributors = Some(vec![
+        contributor("bob@example.com", 3, &["main.rs", "main.go", "lib.go"]),
+        contributor("Doe, \"Jane\"", 1, &["README.md"]),
+        contributor("alice@example.com", 2, &["main.rs"]),
+    ]);
+
+    assert_eq!(
+        report.contributors_csv(),
+        [
+            "git_id,commit_count,top_language,code_lines",
+            "\"Doe, \"\"Jane\"\"\",1,,0",
+            "alice@example.com,2,Rust,30",
+            "bob@example.com,3,Go,70",
+            "",
+        ]
+        .join("\n")
+    );
+}
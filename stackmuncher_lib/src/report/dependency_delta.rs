@@ -0,0 +1,120 @@
+use super::Report;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use tracing::debug;
+
+/// Lists of refs and pkgs added or removed between two reports for a single language.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct DependencyDelta {
+    /// Names present in the current report, but not in the baseline, sorted a-z
+    pub added: Vec<String>,
+    /// Names present in the baseline report, but not in the current one, sorted a-z
+    pub removed: Vec<String>,
+}
+
+impl DependencyDelta {
+    /// Returns `true` if nothing was added or removed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl Report {
+    /// Compares `refs` and `pkgs` of all `tech` records with the `baseline` report and returns the names that were
+    /// added or removed per language. Languages with no changes are not included.
+    pub fn dependency_delta(&self, baseline: &Report) -> BTreeMap<String, DependencyDelta> {
+        let current_deps = self.dependencies_per_language();
+        let mut baseline_deps = baseline.dependencies_per_language();
+
+        let mut deltas: BTreeMap<String, DependencyDelta> = BTreeMap::new();
+
+        for (language, current) in current_deps {
+            // a language that is missing from the baseline has all its dependencies added
+            let baseline = baseline_deps.remove(&language).unwrap_or_default();
+            let mut delta = DependencyDelta {
+                added: current.difference(&baseline).cloned().collect(),
+                removed: baseline.difference(&current).cloned().collect(),
+            };
+            delta.added.sort_unstable();
+            delta.removed.sort_unstable();
+
+            if !delta.is_empty() {
+                deltas.insert(language, delta);
+            }
+        }
+
+        // languages that are no longer present in the current report had all their dependencies removed
+        for (language, baseline) in baseline_deps {
+            if baseline.is_empty() {
+                continue;
+            }
+            let mut removed = baseline.into_iter().collect::<Vec<String>>();
+            removed.sort_unstable();
+            deltas.insert(
+                language,
+                DependencyDelta {
+                    added: Vec::new(),
+                    removed,
+                },
+            );
+        }
+
+        debug!("Dependency changes found in {} languages", deltas.len());
+        deltas
+    }
+
+    /// Collects unique names from `refs` and `pkgs` per language. There may be multiple tech records for the same
+    /// language, e.g. Rust/.rs and Rust/.toml.
+    fn dependencies_per_language(&self) -> BTreeMap<String, HashSet<String>> {
+        let mut deps: BTreeMap<String, HashSet<String>> = BTreeMap::new();
+
+        for tech in &self.tech {
+            let lang_deps = deps.entry(tech.language.clone()).or_default();
+            for kwc in tech.refs.iter().flatten().chain(tech.pkgs.iter().flatten()) {
+                lang_deps.insert(kwc.k.clone());
+            }
+        }
+
+        deps
+    }
+}
+
+#[test]
+fn test_dependency_delta() {
+    use super::kwc::KeywordCounter;
+    use super::Tech;
+
+    let tech = |language: &str, refs: &[&str], pkgs: &[&str]| {
+        let kwcs = |names: &[&str]| {
+            Some(
+                names
+                    .iter()
+                    .map(|name| KeywordCounter::new_keyword(name.to_string(), 1))
+                    .collect::<HashSet<KeywordCounter>>(),
+            )
+        };
+        Tech {
+            refs: kwcs(refs),
+            pkgs: kwcs(pkgs),
+            ..Tech::for_test(language, 10)
+        }
+    };
+
+    let mut baseline = Report::new();
+    baseline.merge_tech_record(tech("Rust", &["serde", "regex"], &[]));
+    baseline.merge_tech_record(tech("Go", &["fmt"], &[]));
+    let mut current = Report::new();
+    current.merge_tech_record(tech("Rust", &["serde"], &["tokio"]));
+    current.merge_tech_record(tech("Python", &["numpy"], &[]));
+
+    let deltas = current.dependency_delta(&baseline);
+    assert_eq!(deltas.len(), 3);
+    assert_eq!(deltas["Rust"].added, vec!["tokio"]);
+    assert_eq!(deltas["Rust"].removed, vec!["regex"]);
+    assert!(deltas["Go"].added.is_empty());
+    assert_eq!(deltas["Go"].removed, vec!["fmt"]);
+    assert_eq!(deltas["Python"].added, vec!["numpy"]);
+
+    // no changes, no deltas
+    assert!(current.dependency_delta(&current).is_empty());
+}
@@ -0,0 +1,98 @@
+use super::{DependencyDelta, Report};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::debug;
+
+/// Changes between two snapshots of the same project or developer, e.g. reports generated a month apart.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct ReportDiff {
+    /// Languages present in the newer report, but not in the older one, sorted a-z
+    pub added_languages: Vec<String>,
+    /// Languages present in the older report, but not in the newer one, sorted a-z
+    pub removed_languages: Vec<String>,
+    /// Changes per language, including added and removed ones. Languages with no changes are not included.
+    pub languages: BTreeMap<String, LanguageDelta>,
+}
+
+/// Changes in a single language between two reports. The deltas are negative if the number went down.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct LanguageDelta {
+    pub code_lines: i64,
+    pub files: i64,
+    /// Refs and pkgs added or removed
+    #[serde(skip_serializing_if = "DependencyDelta::is_empty", default)]
+    pub dependencies: DependencyDelta,
+}
+
+impl LanguageDelta {
+    /// Returns `true` if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.code_lines == 0 && self.files == 0 && self.dependencies.is_empty()
+    }
+}
+
+impl Report {
+    /// Returns changes from `self` to `newer` per language. A language present in only one of the reports
+    /// is listed as added or removed with all its lines, files and dependencies.
+    pub fn diff(&self, newer: &Report) -> ReportDiff {
+        let older_totals = self.totals_per_language();
+        let newer_totals = newer.totals_per_language();
+
+        let mut diff = ReportDiff {
+            added_languages: newer_totals
+                .keys()
+                .filter(|language| !older_totals.contains_key(*language))
+                .cloned()
+                .collect(),
+            removed_languages: older_totals
+                .keys()
+                .filter(|language| !newer_totals.contains_key(*language))
+                .cloned()
+                .collect(),
+            languages: BTreeMap::new(),
+        };
+
+        // a language missing from either side counts as zero lines and files
+        let mut dependency_deltas = newer.dependency_delta(self);
+        for language in older_totals.keys().chain(newer_totals.keys()) {
+            if diff.languages.contains_key(language) {
+                continue;
+            }
+            let (older_code_lines, older_files) = older_totals.get(language).cloned().unwrap_or_default();
+            let (newer_code_lines, newer_files) = newer_totals.get(language).cloned().unwrap_or_default();
+
+            let delta = LanguageDelta {
+                code_lines: newer_code_lines as i64 - older_code_lines as i64,
+                files: newer_files as i64 - older_files as i64,
+                dependencies: dependency_deltas.remove(language).unwrap_or_default(),
+            };
+
+            if !delta.is_empty() {
+                diff.languages.insert(language.clone(), delta);
+            }
+        }
+
+        debug!(
+            "Report diff: {} added, {} removed, {} changed languages",
+            diff.added_languages.len(),
+            diff.removed_languages.len(),
+            diff.languages.len()
+        );
+
+        diff
+    }
+
+    /// Adds up `code_lines` and `files` per language. There may be multiple tech records for the same
+    /// language, e.g. Rust/.rs and Rust/.toml.
+    fn totals_per_language(&self) -> BTreeMap<String, (u64, u64)> {
+        let mut totals: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+
+        for tech in &self.tech {
+            let (code_lines, files) = totals.entry(tech.language.clone()).or_default();
+            *code_lines += tech.code_lines;
+            *files += tech.files;
+        }
+
+        totals
+    }
+}
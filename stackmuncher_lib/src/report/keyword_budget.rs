@@ -0,0 +1,95 @@
+use super::kwc::KeywordCounter;
+use super::{Report, Tech};
+use std::collections::HashSet;
+use tracing::debug;
+
+/// Tech members subject to `Report::enforce_keyword_budget`
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum KeywordKind {
+    Keywords,
+    Pkgs,
+    Refs,
+}
+
+impl Report {
+    /// Keeps at most `max_total` entries across `keywords`, `refs` and `pkgs` of all `tech` records and drops the rest.
+    /// Entries are ranked by their counts across all languages, ties go to the key that comes first a-z.
+    /// `refs_kw` and `pkgs_kw` summaries are not affected. Returns the number of dropped entries.
+    pub fn enforce_keyword_budget(&mut self, max_total: usize) -> usize {
+        let mut techs = self.tech_mut().drain().collect::<Vec<Tech>>();
+
+        // rank all entries from all tech records together
+        let mut ranked: Vec<(u64, &str, KeywordKind, usize)> = Vec::new();
+        for (idx, tech) in techs.iter().enumerate() {
+            for (kind, kwcs) in [
+                (KeywordKind::Keywords, &tech.keywords),
+                (KeywordKind::Pkgs, &tech.pkgs),
+                (KeywordKind::Refs, &tech.refs),
+            ] {
+                for kwc in kwcs.iter().flatten() {
+                    ranked.push((kwc.c, kwc.k.as_str(), kind, idx));
+                }
+            }
+        }
+
+        let dropped = ranked.len().saturating_sub(max_total);
+        if dropped == 0 {
+            *self.tech_mut() = techs.into_iter().collect();
+            return 0;
+        }
+
+        ranked.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)).then_with(|| a.2.cmp(&b.2)));
+        let keep = ranked
+            .into_iter()
+            .take(max_total)
+            .map(|(_, k, kind, idx)| (k.to_owned(), kind, idx))
+            .collect::<HashSet<(String, KeywordKind, usize)>>();
+
+        for (idx, tech) in techs.iter_mut().enumerate() {
+            let is_kept = |kwc: &KeywordCounter, kind: KeywordKind| keep.contains(&(kwc.k.clone(), kind, idx));
+            for (kind, kwcs) in [
+                (KeywordKind::Keywords, &mut tech.keywords),
+                (KeywordKind::Pkgs, &mut tech.pkgs),
+                (KeywordKind::Refs, &mut tech.refs),
+            ] {
+                if let Some(v) = kwcs.as_mut() {
+                    v.retain(|kwc| is_kept(kwc, kind));
+                }
+                // nothing left is the same as no data
+                if kwcs.as_ref().is_some_and(|v| v.is_empty()) {
+                    *kwcs = None;
+                }
+            }
+        }
+
+        debug!("Dropped {} keywords over the budget of {}", dropped, max_total);
+        *self.tech_mut() = techs.into_iter().collect();
+
+        dropped
+    }
+}
+
+#[test]
+fn test_enforce_keyword_budget() {
+    let tech = |language: &str, refs: serde_json::Value| Tech {
+        refs: serde_json::from_value(refs).unwrap(),
+        ..Tech::for_test(language, 10)
+    };
+
+    let mut report = Report::new();
+    report.merge_tech_record(tech("Rust", serde_json::json!([{"k": "serde", "c": 9}, {"k": "regex", "c": 2}])));
+    report.merge_tech_record(tech("Go", serde_json::json!([{"k": "fmt", "c": 5}, {"k": "os", "c": 1}])));
+
+    // the 2nd most used ref is in a different language from the 1st
+    assert_eq!(report.enforce_keyword_budget(2), 2);
+    let mut refs = report
+        .tech
+        .iter()
+        .flat_map(|tech| tech.refs.iter().flatten().map(|kwc| kwc.k.clone()))
+        .collect::<Vec<String>>();
+    refs.sort();
+    assert_eq!(refs, vec!["fmt", "serde"]);
+
+    // nothing to drop if within the budget
+    assert_eq!(report.enforce_keyword_budget(10), 0);
+}
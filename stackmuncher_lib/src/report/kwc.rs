@@ -8,6 +8,7 @@ pub struct KeywordCounter {
     pub k: String,
     /// array of free text after the keyword
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "super::sorted::option_set")]
     pub t: Option<HashSet<String>>,
     /// count
     pub c: u64,
@@ -33,6 +34,19 @@ impl PartialEq for KeywordCounter {
     }
 }
 
+/// Sorted by the keyword only to be consistent with `PartialEq`
+impl Ord for KeywordCounter {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.k.cmp(&other.k)
+    }
+}
+
+impl PartialOrd for KeywordCounter {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl KeywordCounterSet for HashSet<KeywordCounter> {
     /// Insert a new record or increment the counter for the existing one
     fn increment_counters(&mut self, new_kw_counter: KeywordCounter) {
@@ -0,0 +1,117 @@
+use super::Report;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A broad category of a language to tell apart code from docs, config and data.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LanguageCategory {
+    Programming,
+    Markup,
+    Data,
+    Config,
+}
+
+impl LanguageCategory {
+    /// Returns the category of the language as named by the muncher (`Tech.language`) from the built-in table.
+    /// Unknown languages are assumed to be `Programming`.
+    pub fn from_language(language: &str) -> Self {
+        match language {
+            "HTML" | "HAML" | "CSS" | "SCSS" | "Liquid" | "eRuby" | "Markdown" | "reStructuredText" | "Text" => {
+                Self::Markup
+            }
+            "JSON" | "XML" | "CSV" => Self::Data,
+            "DevOps" | "Docker" | "Terraform" | "Puppet" | "Makefile" | "YAML" | "TOML" | "INI" => Self::Config,
+            _ => Self::Programming,
+        }
+    }
+}
+
+impl Report {
+    /// Returns the total number of code lines per language category using the built-in category table.
+    pub fn lines_by_category(&self) -> HashMap<LanguageCategory, usize> {
+        self.lines_by_category_with_overrides(&HashMap::new())
+    }
+
+    /// Returns `true` if the report has any code in a language from `LanguageCategory::Programming`,
+    /// e.g. `false` for repos with only docs, data or config files.
+    pub fn has_programming_language(&self) -> bool {
+        self.tech.iter().any(|tech| {
+            tech.code_lines > 0 && LanguageCategory::from_language(&tech.language) == LanguageCategory::Programming
+        })
+    }
+
+    /// Returns the total number of code lines per language category.
+    /// * `overrides` - language name to category pairs that take precedence over the built-in table
+    pub fn lines_by_category_with_overrides(
+        &self,
+        overrides: &HashMap<String, LanguageCategory>,
+    ) -> HashMap<LanguageCategory, usize> {
+        let mut lines_by_category: HashMap<LanguageCategory, usize> = HashMap::new();
+
+        for tech in &self.tech {
+            let category = match overrides.get(&tech.language) {
+                Some(v) => *v,
+                None => LanguageCategory::from_language(&tech.language),
+            };
+            *lines_by_category.entry(category).or_insert(0) += tech.code_lines as usize;
+        }
+
+        lines_by_category
+    }
+}
+
+#[test]
+fn test_lines_by_category() {
+    use super::Tech;
+
+    let mut report = Report::new();
+    report.merge_tech_record(Tech::for_test("Markdown", 20));
+    report.merge_tech_record(Tech::for_test("YAML", 10));
+    report.merge_tech_record(Tech::for_test("Rust", 70));
+    report.merge_tech_record(Tech::for_test("Go", 5));
+
+    let lines_by_category = report.lines_by_category();
+    assert_eq!(lines_by_category.get(&LanguageCategory::Programming), Some(&75));
+    assert_eq!(lines_by_category.get(&LanguageCategory::Markup), Some(&20));
+    assert_eq!(lines_by_category.get(&LanguageCategory::Config), Some(&10));
+    assert!(!lines_by_category.contains_key(&LanguageCategory::Data));
+
+    // overrides take precedence over the built-in table
+    let overrides = [("YAML".to_owned(), LanguageCategory::Data)].iter().cloned().collect();
+    let lines_by_category = report.lines_by_category_with_overrides(&overrides);
+    assert_eq!(lines_by_category.get(&LanguageCategory::Data), Some(&10));
+    assert!(!lines_by_category.contains_key(&LanguageCategory::Config));
+}
+
+#[test]
+fn test_has_programming_language() {
+    use super::Tech;
+
+    let mut report = Report::new();
+    assert!(!report.has_programming_language());
+    report.merge_tech_record(Tech::for_test("Markdown", 20));
+    report.merge_tech_record(Tech::for_test("YAML", 10));
+    // a source file with only comments in it
+    report.merge_tech_record(Tech::for_test("Rust", 0));
+    assert!(!report.has_programming_language());
+
+    report.merge_tech_record(Tech::for_test("Go", 5));
+    assert!(report.has_programming_language());
+}
+
+#[test]
+fn test_default_text_muncher_is_not_programming() {
+    let mut code_rules = crate::code_rules::CodeRules::new();
+    code_rules.set_default_text_muncher(&"text".to_owned());
+    let muncher = code_rules.get_muncher(&"NOTICE".to_owned()).unwrap().clone();
+    assert_eq!(LanguageCategory::from_language(&muncher.language), LanguageCategory::Markup);
+
+    // every non-blank line of plain text is counted as a code line
+    let tech = crate::processors::analyze_bytes(b"Some notice\n\nfor the users\n", &muncher, "NOTICE");
+    assert_eq!(tech.code_lines, 2);
+
+    let mut report = Report::new();
+    report.merge_tech_record(tech);
+    assert!(!report.has_programming_language());
+    assert_eq!(report.lines_by_category().get(&LanguageCategory::Markup), Some(&2));
+}
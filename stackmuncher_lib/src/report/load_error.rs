@@ -0,0 +1,19 @@
+use thiserror::Error;
+
+/// Reasons for a report that exists not being loaded, e.g. because it is corrupt.
+/// A missing report is not an error.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// The report file exists, but could not be read
+    #[error("Cannot read the report: {0}")]
+    Read(String),
+    /// The contents start with the gzip magic bytes, but could not be decompressed, e.g. a truncated download
+    #[error("Cannot decompress the report: {0}")]
+    Decompress(String),
+    /// The contents are not a valid report, e.g. truncated JSON
+    #[error("Cannot parse the report: {0}")]
+    Parse(String),
+    /// The report was parsed, but has no section with this name, e.g. `contributors`
+    #[error("The report has no {0} section")]
+    MissingSection(&'static str),
+}
@@ -0,0 +1,109 @@
+use super::Report;
+use crate::contributor::Contributor;
+use std::collections::BTreeMap;
+
+impl Report {
+    /// Returns a Markdown summary for pasting into a PR description or a README: the dates of the first and the
+    /// last commits, a table of languages sorted by code lines and a table of contributors sorted by commit count.
+    /// Ties are broken by the name a-z. Sections with no data are omitted.
+    pub fn to_markdown(&self) -> String {
+        // a language may have multiple tech records from different munchers
+        let mut languages: BTreeMap<&String, (u64, u64, u64)> = BTreeMap::new();
+        for tech in &self.tech {
+            let (files, code_lines, total_lines) = languages.entry(&tech.language).or_default();
+            *files += tech.files;
+            *code_lines += tech.code_lines;
+            *total_lines += tech.total_lines;
+        }
+        let mut languages = languages.into_iter().collect::<Vec<_>>();
+        languages.sort_by(|a, b| b.1 .1.cmp(&a.1 .1).then_with(|| a.0.cmp(b.0)));
+
+        let mut md = String::from("## Stack summary\n\n");
+        md.push_str(&format!("* First commit: {}\n", self.date_init.as_deref().unwrap_or("unknown")));
+        md.push_str(&format!("* Last commit: {}\n", self.date_head.as_deref().unwrap_or("unknown")));
+        md.push_str(&format!("* Files: {}\n", languages.iter().map(|(_, (files, _, _))| files).sum::<u64>()));
+
+        if !languages.is_empty() {
+            md.push_str("\n### Languages\n\n| Language | Files | Code lines | Total lines |\n|---|--:|--:|--:|\n");
+            for (language, (files, code_lines, total_lines)) in languages {
+                md.push_str(&format!("| {} | {} | {} | {} |\n", md_cell(language), files, code_lines, total_lines));
+            }
+        }
+
+        if let Some(contributors) = self.contributors.as_ref().filter(|v| !v.is_empty()) {
+            let mut contributors = contributors.iter().collect::<Vec<&Contributor>>();
+            contributors.sort_by(|a, b| {
+                b.commit_count
+                    .cmp(&a.commit_count)
+                    .then_with(|| a.git_id.cmp(&b.git_id))
+            });
+
+            md.push_str("\n### Contributors\n\n| Contributor | Commits | Last commit |\n|---|--:|---|\n");
+            for contributor in contributors {
+                md.push_str(&format!(
+                    "| {} | {} | {} |\n",
+                    md_cell(&contributor.git_id),
+                    contributor.commit_count,
+                    contributor.last_commit_date
+                ));
+            }
+        }
+
+        md
+    }
+}
+
+/// Escapes characters that would break a Markdown table cell.
+fn md_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+#[test]
+fn test_to_markdown() {
+    use super::Tech;
+
+    let mut report = serde_json::from_value::<Report>(serde_json::json!({
+        "timestamp": "2021-11-02T00:00:00+00:00",
+        "date_init": "2019-01-01T00:00:00+00:00",
+        "date_head": "2021-11-01T00:00:00+00:00",
+        "tech": [],
+        "contributors": [
+            {"git_id": "bob@example.com", "name_email_pairs": [], "last_commit_sha1": "b", "last_commit_epoch": 2,
+                "last_commit_date": "2021-10-01T00:00:00+00:00", "commit_count": 5, "touched_files": []},
+            {"git_id": "alice@example.com", "name_email_pairs": [], "last_commit_sha1": "a", "last_commit_epoch": 3,
+                "last_commit_date": "2021-11-01T00:00:00+00:00", "commit_count": 12, "touched_files": []}
+        ]
+    }))
+    .unwrap();
+    report.tech_mut().insert(Tech {
+        muncher_name: "rust.rs".to_owned(),
+        files: 3,
+        total_lines: 120,
+        blank_lines: 10,
+        bracket_only_lines: 10,
+        ..Tech::for_test("Rust", 100)
+    });
+    report.tech_mut().insert(Tech {
+        muncher_name: "rust.cargo.toml".to_owned(),
+        total_lines: 12,
+        blank_lines: 2,
+        ..Tech::for_test("Rust", 10)
+    });
+    report.tech_mut().insert(Tech {
+        files: 2,
+        total_lines: 50,
+        blank_lines: 10,
+        ..Tech::for_test("Markdown", 40)
+    });
+    report.tech_mut().insert(Tech::for_test("C", 40));
+
+    let md = report.to_markdown();
+    assert!(md.contains("* First commit: 2019-01-01T00:00:00+00:00\n"));
+    assert!(md.contains("* Files: 7\n"));
+
+    // the tech records of the same language are combined, ties are sorted by name
+    assert!(md.contains("| Rust | 4 | 110 | 132 |\n| C | 1 | 40 | 40 |\n| Markdown | 2 | 40 | 50 |\n"));
+    assert!(md.contains(
+        "| alice@example.com | 12 | 2021-11-01T00:00:00+00:00 |\n| bob@example.com | 5 | 2021-10-01T00:00:00+00:00 |\n"
+    ));
+}
@@ -3,7 +3,25 @@ pub mod overview;
 pub mod report;
 pub mod tech;
 pub mod commit_time_histo;
+pub mod aggregates;
+pub mod contributors_csv;
+pub mod dependency_delta;
+pub mod diff;
+pub mod keyword_budget;
+pub mod language_category;
+pub mod load_error;
+pub mod markdown;
+pub mod profile_card;
+pub mod sarif;
+pub(crate) mod sorted;
+pub mod tech_csv;
 
+pub use aggregates::{languages_across_reports, ReportTotals};
+pub use dependency_delta::DependencyDelta;
+pub use diff::{LanguageDelta, ReportDiff};
+pub use language_category::LanguageCategory;
+pub use load_error::LoadError;
 pub use overview::{ProjectReportOverview, TechOverview};
-pub use report::Report;
+pub use profile_card::ProfileCard;
+pub use report::{RepoLogHash, Report};
 pub use tech::Tech;
@@ -34,6 +34,18 @@ impl PartialEq for TechOverview {
     }
 }
 
+impl Ord for TechOverview {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.language.cmp(&other.language)
+    }
+}
+
+impl PartialOrd for TechOverview {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 /// An overview of an individual project report included in the combined report
 /// to avoid loading the full project report every time the combined report is looked at.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq)]
@@ -100,6 +112,7 @@ pub struct ProjectReportOverview {
     #[serde(default)]
     pub commit_count_project: u64,
     /// Stats per stack technology.
+    #[serde(serialize_with = "super::sorted::set")]
     pub tech: HashSet<TechOverview>,
     /// The last N commits for matching reports to projects.
     /// Full project reports have the list of commits from all contributors. Contributor reports only have commits for that contributor.
@@ -153,7 +166,7 @@ impl Tech {
             // this is not a good way of doing it
             // there will be some overlap between pkgs and refs,
             // but getting a unique list is not that straight forward and is language specific
-            libs: self.pkgs.len() as u64 + self.refs.len() as u64,
+            libs: self.pkgs.iter().flatten().count() as u64 + self.refs.iter().flatten().count() as u64,
         }
     }
 }
@@ -0,0 +1,188 @@
+use super::Report;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use tracing::warn;
+
+/// A share of a single language in the profile card.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProfileCardLanguage {
+    /// The same as Tech.language
+    pub language: String,
+    /// Lines of code for this language
+    pub loc: u64,
+    /// Percentage of the LoC for this language from the total LoC
+    pub loc_percentage: u64,
+}
+
+/// A minimal public-safe projection of the report for displaying in web profiles and other UIs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProfileCard {
+    /// Top languages by LoC, largest first
+    pub top_languages: Vec<ProfileCardLanguage>,
+    /// Total lines of code across all languages
+    pub total_loc: u64,
+    /// The language with the most LoC
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary_language: Option<String>,
+    /// Build systems and package managers detected from file and muncher names, sorted a-z, e.g. `Cargo`, `npm`
+    pub build_systems: Vec<String>,
+    /// Number of years between the first and the last commit, rounded to 1 decimal
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub years_active: Option<f64>,
+}
+
+impl ProfileCard {
+    /// The max number of languages included in `top_languages`
+    pub const MAX_TOP_LANGUAGES: usize = 5;
+}
+
+/// File names of well-known build files and the build systems they belong to.
+const BUILD_FILES: [(&str, &str); 14] = [
+    ("cargo.toml", "Cargo"),
+    ("package.json", "npm"),
+    ("cmakelists.txt", "CMake"),
+    ("makefile", "Make"),
+    ("pom.xml", "Maven"),
+    ("build.gradle", "Gradle"),
+    ("build.gradle.kts", "Gradle"),
+    ("go.mod", "Go Modules"),
+    ("gemfile", "Bundler"),
+    ("requirements.txt", "pip"),
+    ("setup.py", "pip"),
+    ("pyproject.toml", "pip"),
+    ("dockerfile", "Docker"),
+    ("composer.json", "Composer"),
+];
+
+/// Muncher names that imply a build system. File names are not available in sanitized reports, but muncher names are.
+const BUILD_MUNCHERS: [(&str, &str); 4] = [
+    ("rust.cargo.toml", "Cargo"),
+    ("csharp.csproj", "MSBuild"),
+    ("cmake", "CMake"),
+    ("docker", "Docker"),
+];
+
+impl Report {
+    /// Returns a minimal summary of the report for UIs: top languages, total LoC, build systems and years active.
+    pub fn to_profile_card(&self) -> ProfileCard {
+        let overview = self.get_overview();
+
+        // sort languages by LoC, largest first, and by name for a stable order
+        let mut languages = overview
+            .tech
+            .into_iter()
+            .filter(|t| t.loc > 0)
+            .map(|t| ProfileCardLanguage {
+                language: t.language,
+                loc: t.loc,
+                loc_percentage: t.loc_percentage,
+            })
+            .collect::<Vec<ProfileCardLanguage>>();
+        languages.sort_unstable_by(|a, b| b.loc.cmp(&a.loc).then_with(|| a.language.cmp(&b.language)));
+        languages.truncate(ProfileCard::MAX_TOP_LANGUAGES);
+
+        ProfileCard {
+            primary_language: languages.first().map(|l| l.language.clone()),
+            top_languages: languages,
+            total_loc: overview.loc,
+            build_systems: self.build_systems(),
+            years_active: self.years_active(),
+        }
+    }
+
+    /// Returns a sorted list of build systems detected from tree files, per-file tech file names and muncher names.
+    fn build_systems(&self) -> Vec<String> {
+        let mut build_systems: BTreeSet<String> = BTreeSet::new();
+
+        let file_names = self
+            .tree_files
+            .iter()
+            .flatten()
+            .chain(self.per_file_tech.iter().filter_map(|t| t.file_name.as_ref()));
+        for file_name in file_names {
+            // only the file name matters, not the path
+            let file_name = file_name
+                .rsplit(&['/', '\\'][..])
+                .next()
+                .unwrap_or_default()
+                .to_lowercase();
+            if let Some((_, build_system)) = BUILD_FILES.iter().find(|(name, _)| *name == file_name) {
+                build_systems.insert(build_system.to_string());
+            }
+        }
+
+        for tech in self.tech.iter().chain(self.per_file_tech.iter()) {
+            if let Some((_, build_system)) = BUILD_MUNCHERS.iter().find(|(name, _)| *name == tech.muncher_name) {
+                build_systems.insert(build_system.to_string());
+            }
+        }
+
+        build_systems.into_iter().collect()
+    }
+
+    /// Returns the number of years between `date_init` and `date_head` rounded to 1 decimal or None if either date is missing.
+    fn years_active(&self) -> Option<f64> {
+        let date_init = DateTime::parse_from_rfc3339(self.date_init.as_ref()?);
+        let date_head = DateTime::parse_from_rfc3339(self.date_head.as_ref()?);
+
+        match (date_init, date_head) {
+            (Ok(date_init), Ok(date_head)) => {
+                let days = (date_head - date_init).num_days().max(0) as f64;
+                Some((days / 365.25 * 10.0).round() / 10.0)
+            }
+            _ => {
+                warn!("Invalid INIT or HEAD commit date: {:?} / {:?}", self.date_init, self.date_head);
+                None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_to_profile_card() {
+    use super::Tech;
+
+    let mut report = Report::new();
+    report.merge_tech_record(Tech::for_test("Rust", 300));
+    report.merge_tech_record(Tech::for_test("Go", 100));
+    report.merge_tech_record(Tech {
+        muncher_name: "docker".to_owned(),
+        ..Tech::for_test("Docker", 0)
+    });
+    report.tree_files = Some(
+        ["Cargo.toml", "web/package.json", "src/main.rs"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect(),
+    );
+    report.date_init = Some("2019-01-01T00:00:00+00:00".to_owned());
+    report.date_head = Some("2021-07-02T12:00:00+00:00".to_owned());
+
+    let card = report.to_profile_card();
+    assert_eq!(card.total_loc, 400);
+    assert_eq!(card.primary_language.as_deref(), Some("Rust"));
+    // languages with no code are not listed
+    assert_eq!(
+        card.top_languages,
+        vec![
+            ProfileCardLanguage {
+                language: "Rust".to_owned(),
+                loc: 300,
+                loc_percentage: 75,
+            },
+            ProfileCardLanguage {
+                language: "Go".to_owned(),
+                loc: 100,
+                loc_percentage: 25,
+            },
+        ]
+    );
+    assert_eq!(card.build_systems, vec!["Cargo", "Docker", "npm"]);
+    assert_eq!(card.years_active, Some(2.5));
+
+    let card = Report::new().to_profile_card();
+    assert!(card.top_languages.is_empty());
+    assert!(card.primary_language.is_none());
+    assert!(card.years_active.is_none());
+}
@@ -1,16 +1,22 @@
+use super::aggregates::AggregatesCache;
 use super::commit_time_histo::CommitTimeHisto;
 use super::kwc::{KeywordCounter, KeywordCounterSet};
+use super::load_error::LoadError;
 use super::tech::{Tech, TechHistory};
 use super::ProjectReportOverview;
+use crate::contributor::{Contributor, ContributorFile};
+use crate::mailmap::Mailmap;
 use crate::utils::sha256::hash_str_to_sha256_as_base58;
-use crate::{contributor::Contributor, git::GitLogEntry, utils};
+use crate::{code_rules::CodeRules, git::GitLogEntry, utils};
 use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use path_absolutize::{self, Absolutize};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
@@ -33,12 +39,27 @@ pub struct ListCounts {
     keywords: u64,
 }
 
+/// The git history of a repo merged into a combined report, see `Report.log_hashes_included`.
+/// The repo is identified by `project_id` or `date_init`, the same way as in `Report::is_mergeable_with`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RepoLogHash {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_init: Option<String>,
+    pub log_hash: String,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename = "tech")]
 pub struct Report {
     /// The exact timestamp of the report generation in ISO3389 format.
     /// E.g. 2018-12-09T22:29:40+01:00
     pub timestamp: String,
+    /// The version of the report structure. Reports from before the version was introduced have 0.
+    /// Older reports are brought up to `Report::SCHEMA_VERSION` by `migrate()` when loaded.
+    #[serde(default)]
+    pub schema_version: u32,
     /// Member email address for STM notifications. No update is needed if None. Clear if Some("").
     /// Only used for Inbox reports.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -78,6 +99,21 @@ pub struct Report {
     /// Is `true` if the report was generated by adding a single commit to a cached report
     #[serde(default = "default_as_false")]
     pub is_single_commit: bool,
+    /// Is `true` if the report was generated from files staged in the git index rather than committed files.
+    /// Staged snapshots have no commit info.
+    #[serde(default = "default_as_false")]
+    pub is_staged_snapshot: bool,
+    /// Set to true if munching was stopped after `Config.max_processing_duration` and some files were not processed.
+    #[serde(default = "default_as_false")]
+    pub timed_out: bool,
+    /// Set to true if a report for the same repo, but from a different git history, e.g. from before a force-push, was
+    /// not merged into this one to avoid double-counting. See `is_mergeable_with`. The reports need to be regenerated.
+    #[serde(default = "default_as_false")]
+    pub has_incompatible_history: bool,
+    /// Log hashes of all repos merged into a combined report. `log_hash` of the combined report is reset if it has
+    /// more than one repo, so reports merged later are checked against this list instead.
+    #[serde(skip_serializing_if = "Vec::is_empty", default = "Vec::new")]
+    pub log_hashes_included: Vec<RepoLogHash>,
     /// Git identity of the author of the last (HEAD) commit. Should only be present in the project report.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_commit_author: Option<String>,
@@ -138,29 +174,52 @@ pub struct Report {
     /// List of names or emails of all project contributors (authors and committers) from `contributors` section.
     /// This member is only set on project reports and is missing from individual or combined contributor reports.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "super::sorted::option_set")]
     pub contributor_git_ids: Option<HashSet<String>>,
     /// Contains the number of elements per list contained in this report to help with DB queries.
     /// The values are calculated once before saving the reports.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub list_counts: Option<ListCounts>,
     /// Combined summary per technology, e.g. Rust, C# or CSS
-    /// This member can be shared publicly after some clean up
+    /// This member can be shared publicly after some clean up.
+    /// Modify it via `tech_mut()` or call `invalidate_aggregates()` after modifying it directly
+    /// to keep the memoized aggregates in sync.
+    #[serde(serialize_with = "super::sorted::set")]
     pub tech: HashSet<Tech>,
     /// Per-file technology summary, e.g. Rust/main.rs.
     /// This member should not be shared publicly, unless it's a public project
     /// because file names are sensitive info that can be exploited.
     #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(serialize_with = "super::sorted::set")]
     pub per_file_tech: HashSet<Tech>,
+    /// Per-directory technology summary derived from `per_file_tech` by `compute_per_dir_tech`, keyed by the directory
+    /// path, e.g. `src/report`. Files in the root of the project are under `.`.
+    /// This member should not be shared publicly for the same reason as `per_file_tech`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "super::sorted::option_map_of_sets")]
+    pub per_dir_tech: Option<HashMap<String, HashSet<Tech>>>,
     #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(serialize_with = "super::sorted::set")]
     pub unprocessed_file_names: HashSet<String>,
+    /// Reasons for some of `unprocessed_file_names` not being processed, e.g. the file was too large, keyed by file name.
+    /// Files with no matching munchers are not listed here.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub unprocessed_file_reasons: BTreeMap<String, String>,
     /// A list of all file extensions used in the project with the number of times they were encountered.
     #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(serialize_with = "super::sorted::set")]
     pub file_types: HashSet<KeywordCounter>,
     /// S3 keys of the reports from `report_s3_name` merged into a combined user or org report
     /// This attribute was depricated in favour of projects_included, but has to be in use until
     /// https://github.com/stackmuncher/stm-html/issues/8 is resolved.
     #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(serialize_with = "super::sorted::set")]
     pub reports_included: HashSet<String>,
+    /// IDs from `report_id` of the reports merged into a combined user or org report.
+    /// Used to skip reports that were already merged, e.g. if the merge was retried.
+    #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(serialize_with = "super::sorted::set")]
+    pub report_ids_included: HashSet<String>,
     // Brief details about the projects included into a combined user or org report.
     /// Blank for individual project reports. It is only needed by STM server to display project details on the combined report page
     /// without going to the individual project reports.
@@ -169,6 +228,7 @@ pub struct Report {
     /// A list of GIT identities for the contributors included in the report.
     /// Used only in combined contributor reports
     #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(serialize_with = "super::sorted::set")]
     pub git_ids_included: HashSet<String>,
     /// List of names and emails of all committers for this repo. Only applies to per-project reports.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -179,6 +239,7 @@ pub struct Report {
     pub commit_time_histo: Option<CommitTimeHisto>,
     /// The current list of files in the GIT tree
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "super::sorted::option_set")]
     pub tree_files: Option<HashSet<String>>,
     /// The last N commits for matching projects that changed name, remote URL or any other identifying property
     /// The commits are shortened and joined with their EPOCHs in a single string. E.g. `e29d17e6_1627380297`
@@ -187,7 +248,34 @@ pub struct Report {
     /// A unique list of all keywords found in the report for search. Normalized to lower case and sorted a-z.
     /// Populated during merge.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "super::sorted::option_set")]
     pub keywords: Option<HashSet<String>>,
+    /// Number of commits per conventional commit type, e.g. `feat`, `fix`, `docs`.
+    /// Commits that do not follow the convention are not counted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "super::sorted::option_map")]
+    pub commit_type_counts: Option<HashMap<String, usize>>,
+    /// Up to `Report::HOT_FILES_COUNT` files touched by the most commits with the number of commits, most changed first.
+    /// None if the log has no file lists.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub hot_files: Option<Vec<(String, usize)>>,
+    /// Hashes of the munchers that produced the report as muncher name / hash pairs.
+    /// Only populated if requested with `with_rule_provenance()`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub muncher_hashes: Option<BTreeMap<String, u64>>,
+    /// The version of the rule set the report was produced with, see `CodeRules::RULES_TIMESTAMP`.
+    /// Only populated if requested with `with_rule_provenance()`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules_timestamp: Option<String>,
+    /// The earliest and the latest copyright years found in file headers across all languages, e.g. `(2018, 2023)`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copyright_years: Option<(u32, u32)>,
+    /// Incremented on every change to `tech` to invalidate `aggregates_cache`.
+    #[serde(skip)]
+    pub(crate) tech_generation: u64,
+    /// Memoized totals and other values derived from `tech`, see `Report::totals()`.
+    #[serde(skip)]
+    pub(crate) aggregates_cache: AggregatesCache,
 }
 
 /// A plug for Serde default
@@ -204,14 +292,64 @@ impl Report {
     /// but since ignoring files like nodejs modules is not implemented we'll just ignore such repos.
     pub const MAX_FILES_PER_REPO: u64 = 10000;
 
+    /// The default fraction of files that have to come from the very first commit for the history to be
+    /// considered imported, see `likely_imported_history()`.
+    pub const IMPORTED_HISTORY_THRESHOLD: f64 = 0.5;
+
+    /// Repos with fewer files than this are never considered imported. Small projects are often committed in one go.
+    pub const IMPORTED_HISTORY_MIN_FILES: usize = 20;
+
+    /// The max number of files in `hot_files`
+    pub const HOT_FILES_COUNT: usize = 20;
+
     /// All project reports created prior to this date must be reprocessed
-    pub const REPORT_FORMAT_VERSION: &'static str = "2021-11-02T00:23:00+00:00";
+    pub const REPORT_FORMAT_VERSION: &'static str = "2026-10-18T00:00:00+00:00";
+
+    /// The current version of the report structure. Increment it and add a migration arm to `migrate()` after any
+    /// changes to the meaning of existing fields.
+    pub const SCHEMA_VERSION: u32 = 1;
 
     /// Adds up `tech` totals from `other_report` into `self`, clears unprocessed files and unknown extensions.
+    /// The very first report is taken as the base of the combined report with most of its details intact,
+    /// so the result depends on the order of merging. Use `merge_with_options` for an order-independent result.
     pub fn merge(merge_into: Option<Self>, other_report: Self) -> Option<Self> {
+        Report::merge_with_options(merge_into, other_report, false)
+    }
+
+    /// The same as `merge`, but with an option to normalize the first report.
+    /// * `normalize_first` - start the combined report from a blank one and merge the first report into it the same way
+    ///   as all the other reports, so that the counted fields do not depend on the order of merging. Details that are
+    ///   not merged, e.g. `per_file_tech` or `contributors`, are not copied from the first report either.
+    pub fn merge_with_options(merge_into: Option<Self>, other_report: Self, normalize_first: bool) -> Option<Self> {
         let mut merge_into = merge_into;
         let mut other_report = other_report;
 
+        if merge_into.is_none() && normalize_first {
+            let mut blank_report = Report::new();
+            // any real report is newer than this one
+            blank_report.timestamp = String::new();
+            merge_into = Some(blank_report);
+        }
+
+        // merging the same report twice would double-count everything
+        if let Some(merge_into_inner) = merge_into.as_ref() {
+            if !other_report.report_id.is_empty()
+                && merge_into_inner.report_ids_included.contains(&other_report.report_id)
+            {
+                warn!("Report {} was already merged. Skipping.", other_report.report_id);
+                return merge_into;
+            }
+        }
+
+        // the same code would be counted twice if the history was rewritten between the reports
+        if let Some(merge_into_inner) = merge_into.as_mut() {
+            if !merge_into_inner.is_mergeable_with(&other_report) {
+                warn!("Report {} has an incompatible git history. Skipping.", other_report.report_id);
+                merge_into_inner.has_incompatible_history = true;
+                return merge_into;
+            }
+        }
+
         // prepare an overview of the project being merged into the combined report
         // before `other_report` gets pulled to pieces by the merge
         let other_report_overview = other_report.get_overview();
@@ -224,7 +362,7 @@ impl Report {
 
         // update keyword summaries and muncher name in all tech records
         let mut new_rep_tech = Report::new();
-        for mut tech in other_report.tech.drain() {
+        for mut tech in other_report.tech_mut().drain() {
             tech.refs_kw = Tech::new_kw_summary(&tech.refs);
             tech.pkgs_kw = Tech::new_kw_summary(&tech.pkgs);
             // reset the muncher names on other_report to merge per-language
@@ -233,24 +371,48 @@ impl Report {
             tech.muncher_name = String::new();
             new_rep_tech.merge_tech_record(tech);
         }
-        other_report.tech = new_rep_tech.tech;
+        *other_report.tech_mut() = new_rep_tech.tech;
 
         // the very first report is added with minimal changes
         if merge_into.is_none() {
             info!("Adding 1st report (master)");
             other_report.unprocessed_file_names.clear();
+            other_report.unprocessed_file_reasons.clear();
             other_report.projects_included.clear();
+            if !other_report.report_id.is_empty() {
+                other_report.report_ids_included.insert(other_report.report_id.clone());
+            }
             merge_into = Some(other_report);
         } else {
             // additional reports are merged
             info!("Merging reports");
             let merge_into_inner = merge_into.as_mut().unwrap();
 
+            // the history of every repo is kept for checking reports merged later
+            for repo_log_hash in other_report.repo_log_hashes() {
+                if !merge_into_inner.log_hashes_included.contains(&repo_log_hash) {
+                    merge_into_inner.log_hashes_included.push(repo_log_hash);
+                }
+            }
+            if let Some(repo_log_hash) = merge_into_inner.own_log_hash() {
+                if !merge_into_inner.log_hashes_included.contains(&repo_log_hash) {
+                    merge_into_inner.log_hashes_included.push(repo_log_hash);
+                }
+            }
+
             // merge all tech records
             for tech in other_report.tech {
                 merge_into_inner.merge_tech_record(tech);
             }
 
+            // reports from different repos have no common history to compare against
+            if merge_into_inner.log_hash != other_report.log_hash {
+                merge_into_inner.log_hash = None;
+            }
+            merge_into_inner.has_incompatible_history |= other_report.has_incompatible_history;
+            // the combined report is incomplete if any of its parts is
+            merge_into_inner.timed_out |= other_report.timed_out;
+
             // merge unknown_file_types
             for uft in other_report.file_types {
                 merge_into_inner.file_types.increment_counters(uft);
@@ -260,6 +422,14 @@ impl Report {
             if !other_report.report_s3_name.is_empty() {
                 merge_into_inner.reports_included.insert(other_report.report_s3_name);
             }
+            if !other_report.report_id.is_empty() {
+                merge_into_inner.report_ids_included.insert(other_report.report_id);
+            }
+
+            if let Some(copyright_years) = other_report.copyright_years {
+                merge_into_inner.copyright_years =
+                    Some(Tech::extend_years(merge_into_inner.copyright_years, copyright_years));
+            }
 
             // update the date of the last commit
             // contributor reports may be missing date_head, but would have last_contributor_commit_date_iso
@@ -377,15 +547,112 @@ impl Report {
         merge_into
     }
 
+    /// Returns `false` if both reports are for the same repo, but from different git histories, e.g. one was generated
+    /// before a force-push or a rebase and the other after it. Merging them would double-count the code.
+    /// The repo is the same if the reports have the same `project_id` or the same date of the first commit in `date_init`.
+    /// Combined reports are checked against the history of every repo in `log_hashes_included`.
+    /// Reports with no `log_hash` are always mergeable.
+    pub fn is_mergeable_with(&self, other: &Report) -> bool {
+        let other_log_hashes = other.repo_log_hashes();
+        for repo_log_hash in self.repo_log_hashes() {
+            for other_repo_log_hash in &other_log_hashes {
+                if repo_log_hash.log_hash == other_repo_log_hash.log_hash {
+                    continue;
+                }
+                let is_same_project_id =
+                    repo_log_hash.project_id.is_some() && repo_log_hash.project_id == other_repo_log_hash.project_id;
+                let is_same_date_init =
+                    repo_log_hash.date_init.is_some() && repo_log_hash.date_init == other_repo_log_hash.date_init;
+                if is_same_project_id || is_same_date_init {
+                    debug!(
+                        "Log hash mismatch for the same repo: {} / {}",
+                        repo_log_hash.log_hash, other_repo_log_hash.log_hash
+                    );
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Returns the history of the repo the report was generated for, if it has a `log_hash`.
+    fn own_log_hash(&self) -> Option<RepoLogHash> {
+        Some(RepoLogHash {
+            project_id: self.project_id.clone(),
+            date_init: self.date_init.clone(),
+            log_hash: self.log_hash.clone()?,
+        })
+    }
+
+    /// Returns the histories of all repos included in the report: its own and `log_hashes_included`.
+    fn repo_log_hashes(&self) -> Vec<RepoLogHash> {
+        let mut repo_log_hashes = self.log_hashes_included.clone();
+        if let Some(repo_log_hash) = self.own_log_hash() {
+            if !repo_log_hashes.contains(&repo_log_hash) {
+                repo_log_hashes.push(repo_log_hash);
+            }
+        }
+        repo_log_hashes
+    }
+
+    /// Merges all `reports` into a single combined report with `Report::merge`. Only the latest version of each report
+    /// is merged if the same `report_s3_name` appears more than once to avoid double-counting re-uploaded repos.
+    pub fn merge_latest(reports: Vec<Self>) -> Option<Self> {
+        let mut merge_into: Option<Self> = None;
+        for report in Report::retain_latest_per_repo(reports) {
+            merge_into = Report::merge(merge_into, report);
+        }
+
+        merge_into
+    }
+
+    /// Drops older duplicates of reports with the same `report_s3_name` comparing them by `timestamp`.
+    /// Reports with no `report_s3_name` cannot be matched and are all kept. The order of the remaining reports is preserved.
+    pub fn retain_latest_per_repo(reports: Vec<Self>) -> Vec<Self> {
+        // find the index of the latest report for every s3 name, the last one wins if the timestamps are the same
+        let mut latest: HashMap<&String, (usize, i64)> = HashMap::new();
+        for (idx, report) in reports.iter().enumerate() {
+            if report.report_s3_name.is_empty() {
+                continue;
+            }
+            let timestamp = report.parsed_timestamp();
+            match latest.get(&report.report_s3_name) {
+                Some((_, latest_timestamp)) if *latest_timestamp > timestamp => {}
+                _ => {
+                    latest.insert(&report.report_s3_name, (idx, timestamp));
+                }
+            }
+        }
+        let latest = latest.into_iter().map(|(_, (idx, _))| idx).collect::<HashSet<usize>>();
+
+        reports
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, report)| {
+                if report.report_s3_name.is_empty() || latest.contains(&idx) {
+                    Some(report)
+                } else {
+                    info!("Dropping an older duplicate of {} from {}", report.report_s3_name, report.timestamp);
+                    None
+                }
+            })
+            .collect()
+    }
+
     /// Add a new Tech record merging with the existing records. It removes per-file and some other
     /// potentially sensitive info used for local caching.
     pub(crate) fn merge_tech_record(&mut self, tech: Tech) {
         debug!("Merging Tech, lang: {}, files: {}", tech.language, tech.files);
         // Tech is hashed with the file name for per-file Tech records, but here
         // they are summaries, so it has to be removed to match
-        let tech = tech.reset_file_and_commit_info();
+        let mut tech = tech.reset_file_and_commit_info();
+        // the same language may come under a different name from an older rule set
+        if let Some(language) = CodeRules::canonical_language(&tech.language) {
+            tech.language = language.to_owned();
+        }
         // add totals to the existing record, if any
-        if let Some(mut master) = self.tech.take(&tech) {
+        if let Some(mut master) = self.tech_mut().take(&tech) {
             debug!("Tech match in master, lang: {}, files: {}", master.language, master.files);
             // add up numeric values
             master.docs_comments += tech.docs_comments;
@@ -397,18 +664,33 @@ impl Report {
             master.block_comments += tech.block_comments;
             master.bracket_only_lines += tech.bracket_only_lines;
             master.code_lines += tech.code_lines;
+            master.shebang_lines += tech.shebang_lines;
+            master.string_lines += tech.string_lines;
+            master.todo_comments += tech.todo_comments;
+            master.test_code_lines += tech.test_code_lines;
+            master.generated_code_lines += tech.generated_code_lines;
+            master.duplicated_lines += tech.duplicated_lines;
 
             // add keyword counts
-            for kw in tech.keywords {
-                master.keywords.increment_counters(kw);
+            if let Some(keywords) = tech.keywords {
+                let master_keywords = master.keywords.get_or_insert_with(HashSet::new);
+                for kw in keywords {
+                    master_keywords.increment_counters(kw);
+                }
             }
 
             // add dependencies
-            for kw in tech.refs {
-                master.refs.increment_counters(kw);
+            if let Some(refs) = tech.refs {
+                let master_refs = master.refs.get_or_insert_with(HashSet::new);
+                for kw in refs {
+                    master_refs.increment_counters(kw);
+                }
             }
-            for kw in tech.pkgs {
-                master.pkgs.increment_counters(kw);
+            if let Some(pkgs) = tech.pkgs {
+                let master_pkgs = master.pkgs.get_or_insert_with(HashSet::new);
+                for kw in pkgs {
+                    master_pkgs.increment_counters(kw);
+                }
             }
 
             // add unique words from dependencies - references
@@ -437,13 +719,25 @@ impl Report {
                 }
             }
 
+            if let Some(copyright_years) = tech.copyright_years {
+                master.copyright_years = Some(Tech::extend_years(master.copyright_years, copyright_years));
+            }
+
+            // add tech-debt marker authors
+            if let Some(todo_authors) = tech.todo_authors {
+                let master_todo_authors = master.todo_authors.get_or_insert_with(HashSet::new);
+                for kw in todo_authors {
+                    master_todo_authors.increment_counters(kw);
+                }
+            }
+
             // re-insert the master record
-            self.tech.insert(master);
+            self.tech_mut().insert(master);
         } else {
             // there no matching tech record - add it to the hashmap for the 1st time
             // but reset file-specific data first
             debug!("No matching Tech exists - inserting as-is");
-            self.tech.insert(tech.reset_file_and_commit_info());
+            self.tech_mut().insert(tech.reset_file_and_commit_info());
         }
     }
 
@@ -488,13 +782,60 @@ impl Report {
     }
 
     /// Deletes existing `tech` records and re-creates them from scratch using `per_file_tech` records.
-    pub fn recompute_tech_section(&mut self) {
+    /// * `rebuild_kw_summaries` - regenerates `refs_kw` and `pkgs_kw` of every `tech` record from its `refs` and `pkgs`,
+    ///   the same way `merge` does. If false, the summaries are only what `per_file_tech` records had added up,
+    ///   which is usually None because they are not generated at the file level.
+    pub fn recompute_tech_section(&mut self, rebuild_kw_summaries: bool) {
         debug!("Recomputing tech section");
-        self.tech.clear();
+        self.tech_mut().clear();
 
         for tech in self.per_file_tech.clone() {
             self.merge_tech_record(tech);
         }
+
+        if rebuild_kw_summaries {
+            // the summaries are not a part of the hash, so the records can be updated in place
+            let techs = self
+                .tech_mut()
+                .drain()
+                .map(|mut tech| {
+                    tech.refs_kw = Tech::new_kw_summary(&tech.refs);
+                    tech.pkgs_kw = Tech::new_kw_summary(&tech.pkgs);
+                    tech
+                })
+                .collect();
+            *self.tech_mut() = techs;
+        }
+    }
+
+    /// Groups `per_file_tech` records by the directory of the file and merges them into `per_dir_tech` the same way
+    /// `tech` records are merged. Replaces any existing `per_dir_tech`. Sets it to None if there are no `per_file_tech` records.
+    /// * `max_depth` - the number of leading path components to group by, e.g. `Some(2)` groups `src/report/tech.rs`
+    ///   under `src/report` and `src/a/b/c.rs` under `src/a`. Deeper directories are rolled up into their ancestors.
+    ///   None groups by the full directory path.
+    pub fn compute_per_dir_tech(&mut self, max_depth: Option<usize>) {
+        if self.per_file_tech.is_empty() {
+            self.per_dir_tech = None;
+            return;
+        }
+
+        // a blank report per directory is used to merge tech records with the same rules as in `self.tech`
+        let mut dir_reports: HashMap<String, Report> = HashMap::new();
+        for tech in &self.per_file_tech {
+            let dir = dir_of_file(tech.file_name.as_deref().unwrap_or_default(), max_depth);
+            dir_reports
+                .entry(dir)
+                .or_insert_with(Report::new)
+                .merge_tech_record(tech.clone());
+        }
+
+        debug!("Per-dir tech buckets: {}", dir_reports.len());
+        self.per_dir_tech = Some(
+            dir_reports
+                .into_iter()
+                .map(|(dir, dir_report)| (dir, dir_report.tech))
+                .collect(),
+        );
     }
 
     /// Resets report timestamp, contributor, report IDs and other fields from the individual contributor report
@@ -592,7 +933,10 @@ impl Report {
         self.loc_project = None;
         self.libs_project = None;
         self.unprocessed_file_names.clear();
+        self.unprocessed_file_reasons.clear();
         self.per_file_tech.clear();
+        self.per_dir_tech = None;
+        self.hot_files = None;
 
         self.github_repo_name = None;
         self.github_user_name = None;
@@ -654,6 +998,7 @@ impl Report {
 
         // this can be huge and is not really needed for search
         report.per_file_tech.clear();
+        report.per_dir_tech = None;
 
         // the list of contributors is useful, but indexing every file in the db isn't needed
         if let Some(contributors) = report.contributors.as_mut() {
@@ -682,14 +1027,18 @@ impl Report {
         Report {
             tech: HashSet::new(),
             per_file_tech: HashSet::new(),
+            per_dir_tech: None,
             timestamp: Utc::now().to_rfc3339(),
+            schema_version: Report::SCHEMA_VERSION,
             unprocessed_file_names: HashSet::new(),
+            unprocessed_file_reasons: BTreeMap::new(),
             file_types: HashSet::new(),
             github_user_name: None,
             github_repo_name: None,
             report_s3_name: String::new(),
             report_id: uuid::Uuid::new_v4().to_string(),
             reports_included: HashSet::new(),
+            report_ids_included: HashSet::new(),
             projects_included: Vec::new(),
             git_ids_included: HashSet::new(),
             contributor_git_ids: None,
@@ -699,6 +1048,10 @@ impl Report {
             tree_files: None,
             report_commit_sha1: None,
             is_single_commit: false,
+            is_staged_snapshot: false,
+            timed_out: false,
+            has_incompatible_history: false,
+            log_hashes_included: Vec::new(),
             log_hash: None,
             last_commit_author: None,
             recent_project_commits: None,
@@ -720,51 +1073,137 @@ impl Report {
             commit_time_histo: None,
             keywords: None,
             list_counts: None,
+            muncher_hashes: None,
+            rules_timestamp: None,
+            commit_type_counts: None,
+            hot_files: None,
+            copyright_years: None,
+            tech_generation: 0,
+            aggregates_cache: AggregatesCache::default(),
+        }
+    }
+
+    /// Makes sure `report_id` and `report_s3_name` are populated for reports that were created by older versions
+    /// or had their IDs reset:
+    /// * a new `report_id` is generated if it is empty
+    /// * `report_s3_name` is generated from `github_user_name` and `github_repo_name` if it is empty and both are known,
+    ///   e.g. `rimutaka/stackmuncher.report`
+    ///
+    /// `report_s3_name` remains empty if there is not enough info to generate it.
+    pub fn ensure_ids(&mut self) {
+        if self.report_id.is_empty() {
+            self.report_id = uuid::Uuid::new_v4().to_string();
+            debug!("Generated missing report_id {}", self.report_id);
+        }
+
+        if self.report_s3_name.is_empty() {
+            if let (Some(user_name), Some(repo_name)) = (&self.github_user_name, &self.github_repo_name) {
+                if !user_name.is_empty() && !repo_name.is_empty() {
+                    self.report_s3_name = [user_name, "/", repo_name, Report::REPORT_FILE_NAME_SUFFIX].concat();
+                    debug!("Generated missing report_s3_name {}", self.report_s3_name);
+                }
+            }
         }
     }
 
-    /// Load a report from the local storage, if one exists. Returns None and logs errors on failure.
+    /// Load a report from the local storage, if one exists. Gzipped reports are decompressed.
+    /// Returns None and logs errors on failure. Use `load_from_disk` to tell a corrupt report from a missing one.
     pub fn from_disk(path: &PathBuf) -> Option<Self> {
+        match Report::load_from_disk(path) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed to load report from {} due to {}", path.to_string_lossy(), e);
+                None
+            }
+        }
+    }
+
+    /// Load a report from the local storage. Gzipped reports are decompressed.
+    /// ## Return values
+    /// * `Ok(None)` - there is no report at `path`
+    /// * `Ok(Some)` - the report was loaded
+    /// * `Err` - the report exists, but cannot be read or is corrupt
+    pub fn load_from_disk(path: &PathBuf) -> Result<Option<Self>, LoadError> {
         // check if the file exists at all
         let existing_report_file = Path::new(path);
         if !existing_report_file.exists() {
             info!("No report found at {}. The repo will be processed in full.", path.to_string_lossy());
 
-            return None;
+            return Ok(None);
         }
 
         // try to load the file and read its contents
-        let mut existing_report_file = match File::open(path) {
-            Err(e) => {
-                error!("Cannot read report at {} due to {}.", path.to_string_lossy(), e);
-                return None;
-            }
-            Ok(v) => v,
-        };
-        let mut report_contents = String::new();
-        if let Err(e) = existing_report_file.read_to_string(&mut report_contents) {
-            error!("Failed to read report contents from {} due to {}", path.to_string_lossy(), e);
-            return None;
-        };
+        let report_contents = std::fs::read(path).map_err(|e| LoadError::Read(e.to_string()))?;
+        let report = Report::from_bytes(report_contents)?;
+        info!("Loaded a report from {}", path.to_string_lossy());
 
-        // convert to a struct and return
-        match serde_json::from_str::<Report>(&report_contents) {
-            Err(e) => {
-                error!("Failed to deser report contents from {} due to {}", path.to_string_lossy(), e);
-                return None;
-            }
-            Ok(v) => {
-                info!("Loaded a report from {}", path.to_string_lossy());
-                return Some(v);
+        Ok(Some(report))
+    }
+
+    /// Converts the contents of a report file into a report, e.g. after downloading it from S3.
+    /// Gzipped contents are decompressed first. Older reports are migrated to the current schema version.
+    pub fn from_bytes(contents: Vec<u8>) -> Result<Self, LoadError> {
+        // reports saved with `save_as_local_file_gz` have to be decompressed first
+        let contents = gunzip_if_compressed(contents).map_err(|e| LoadError::Decompress(e.to_string()))?;
+
+        let report = serde_json::from_slice::<Report>(&contents).map_err(|e| LoadError::Parse(e.to_string()))?;
+
+        Ok(report.migrate())
+    }
+
+    /// Upgrades a report from an older `schema_version` to `Report::SCHEMA_VERSION` one version at a time.
+    /// Reports of the current or a newer version are returned unchanged.
+    pub fn migrate(mut self) -> Self {
+        while self.schema_version < Report::SCHEMA_VERSION {
+            match self.schema_version {
+                // reports from before the version marker was introduced have the same structure as v1
+                0 => {}
+                v => {
+                    warn!("No migration from report schema version {}", v);
+                }
             }
+            self.schema_version += 1;
+            debug!("Report migrated to schema version {}", self.schema_version);
+        }
+
+        self
+    }
+
+    /// Loads only the `contributors` section of a report from the local storage. The rest of the report,
+    /// including the bulky `tech` and `per_file_tech` sections, is skipped by the parser without being deserialized.
+    /// Returns an error if the file cannot be read or parsed or has no `contributors` section.
+    pub fn contributors_from_disk(path: &PathBuf) -> Result<Vec<Contributor>, LoadError> {
+        /// Serde skips all unknown members without allocating anything for them.
+        #[derive(Deserialize)]
+        struct ContributorsOnly {
+            contributors: Option<Vec<Contributor>>,
         }
+
+        let report_file = File::open(path).map_err(|e| LoadError::Read(e.to_string()))?;
+
+        // the file is parsed as a stream to avoid loading it into memory in full
+        let contributors_only = serde_json::from_reader::<_, ContributorsOnly>(std::io::BufReader::new(report_file))
+            .map_err(|e| LoadError::Parse(e.to_string()))?;
+
+        let contributors = contributors_only
+            .contributors
+            .ok_or(LoadError::MissingSection("contributors"))?;
+        info!("Loaded {} contributors from {}", contributors.len(), path.to_string_lossy());
+
+        Ok(contributors)
     }
 
     /// Add a file that won't be processed because it is of unknown type and count the number of files
     /// with the same extension.
-    fn add_unprocessed_file(&mut self, file_name: &String) {
+    pub(crate) fn add_unprocessed_file(&mut self, file_name: &str) {
         // add the file name to the list
-        self.unprocessed_file_names.insert(file_name.clone());
+        self.unprocessed_file_names.insert(file_name.to_owned());
+    }
+
+    /// Adds the file to the list of unprocessed files with an explanation why it was not processed.
+    pub(crate) fn add_unprocessed_file_with_reason(&mut self, file_name: &str, reason: String) {
+        self.add_unprocessed_file(file_name);
+        self.unprocessed_file_reasons.insert(file_name.to_owned(), reason);
     }
 
     /// Adds a file extension to a set of counters. Some extensions that look like temp files are excluded.
@@ -800,13 +1239,18 @@ impl Report {
         }
     }
 
-    /// Serializes the report and saves it in the specified location. Panics if either serialize or save fail.
+    /// Serializes the report and saves it in the specified location. Logs and returns an error if either serialize or save fail.
+    /// The report is written into a temp file next to `file_name` first and then renamed over it, so that an existing report
+    /// is either replaced in full or left intact.
     /// Prettified reports can be twice as big as non-formatted ones. Only use this option for reports that the user may want to look at.
-    pub fn save_as_local_file(&self, file_name: &PathBuf, make_pretty: bool) {
-        let absolute_file_name = file_name
-            .absolutize()
-            .expect("Cannot convert rules / file_type dir path to absolute. It's a bug.")
-            .to_path_buf();
+    pub fn save_as_local_file(&self, file_name: &PathBuf, make_pretty: bool) -> Result<(), std::io::Error> {
+        let absolute_file_name = match file_name.absolutize() {
+            Err(e) => {
+                error!("Cannot save a report in {} due to {}", file_name.to_string_lossy(), e);
+                return Err(e);
+            }
+            Ok(v) => v.to_path_buf(),
+        };
 
         // choose the json serializer (pretty or compressed)
         let to_json = if make_pretty {
@@ -819,23 +1263,48 @@ impl Report {
         let payload = match to_json(&self) {
             Err(e) => {
                 error!("Cannot save a report in {} due to {}", absolute_file_name.to_string_lossy(), e);
-                std::process::exit(1);
+                return Err(e.into());
             }
             Ok(v) => v,
         };
 
-        // save into a file
-        if let Err(e) = std::fs::write(file_name, payload.clone()) {
-            error!("Cannot save a report in {} due to {}", absolute_file_name.to_string_lossy(), e);
-            std::process::exit(1);
+        save_atomically(&absolute_file_name, &payload)
+    }
+
+    /// Serializes the report, gzips it and saves it in the specified location, e.g. `project_report.report.gz`.
+    /// The same as `save_as_local_file` otherwise. Use it for large reports with `per_file_tech`.
+    /// `from_disk` detects gzipped reports by their contents regardless of the file name.
+    pub fn save_as_local_file_gz(&self, file_name: &PathBuf) -> Result<(), std::io::Error> {
+        let absolute_file_name = match file_name.absolutize() {
+            Err(e) => {
+                error!("Cannot save a report in {} due to {}", file_name.to_string_lossy(), e);
+                return Err(e);
+            }
+            Ok(v) => v.to_path_buf(),
+        };
+
+        // serialize the report and gzip it
+        let payload = serde_json::to_vec(&self)
+            .map_err(std::io::Error::from)
+            .and_then(|report| {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&report)?;
+                encoder.finish()
+            });
+        let payload = match payload {
+            Err(e) => {
+                error!("Cannot save a report in {} due to {}", absolute_file_name.to_string_lossy(), e);
+                return Err(e);
+            }
+            Ok(v) => v,
         };
 
-        info!("Report saved into {}", absolute_file_name.to_string_lossy());
+        save_atomically(&absolute_file_name, &payload)
     }
 
     /// Adds details about the commit history to the report: head, init, contributors, collaborators, log hash, and remote URLs.
     /// Does not panic (exits early) if `git rev-list` command fails.
-    pub(crate) async fn add_commits_history(self, git_log: Vec<GitLogEntry>) -> Self {
+    pub(crate) async fn add_commits_history(self, git_log: Vec<GitLogEntry>, mailmap: &Mailmap) -> Self {
         let mut report = self;
         debug!("Adding commit history");
 
@@ -847,7 +1316,7 @@ impl Report {
                 report.date_head = Some(commit.date.clone());
                 report.report_commit_sha1 = Some(commit.sha1.clone());
                 report.last_commit_author =
-                    Some(Contributor::git_identity_from_name_email_pair(&commit.author_name_email));
+                    Some(Contributor::git_identity_from_name_email_pair(&commit.author_name_email, mailmap));
             }
         }
 
@@ -873,9 +1342,24 @@ impl Report {
                 .collect::<Vec<String>>(),
         );
 
+        // count conventional commit types, e.g. feat, fix, docs
+        let mut commit_type_counts: HashMap<String, usize> = HashMap::new();
+        for commit_type in git_log
+            .iter()
+            .filter_map(|log_entry| log_entry.conventional_commit_type())
+        {
+            *commit_type_counts.entry(commit_type).or_insert(0) += 1;
+        }
+        debug!("Conventional commit types: {}", commit_type_counts.len());
+        if !commit_type_counts.is_empty() {
+            report.commit_type_counts = Some(commit_type_counts);
+        }
+
+        report.hot_files = Report::hot_files_from_log(&git_log, Report::HOT_FILES_COUNT);
+
         // this part consumes git_log because there is a lot of data in it
         // so should appear at the end
-        report.contributors = Some(Contributor::from_commit_history(git_log));
+        report.contributors = Some(Contributor::from_commit_history(git_log, mailmap));
         report.contributor_git_ids = Some(
             report
                 .contributors
@@ -896,6 +1380,60 @@ impl Report {
         report
     }
 
+    /// Removes automated accounts, e.g. `dependabot[bot]`, from `contributors` and `contributor_git_ids` if any of
+    /// their names or emails match `bot_patterns`. `last_commit_author` is replaced with the remaining contributor
+    /// with the latest commit if it was a bot, or None if there are no contributors left.
+    pub fn remove_bots(&mut self, bot_patterns: &[Regex]) {
+        if bot_patterns.is_empty() {
+            return;
+        }
+        let contributors = match self.contributors.as_mut() {
+            Some(v) => v,
+            None => return,
+        };
+
+        let is_bot = |contributor: &Contributor| {
+            contributor.name_email_pairs.iter().any(|(name, email)| {
+                bot_patterns
+                    .iter()
+                    .any(|pattern| pattern.is_match(name) || pattern.is_match(email))
+            })
+        };
+
+        let mut bot_git_ids: HashSet<String> = HashSet::new();
+        contributors.retain(|contributor| {
+            if is_bot(contributor) {
+                debug!("Removing bot contributor {}", contributor.git_id);
+                bot_git_ids.insert(contributor.git_id.clone());
+                false
+            } else {
+                true
+            }
+        });
+        if bot_git_ids.is_empty() {
+            return;
+        }
+
+        if let Some(contributor_git_ids) = self.contributor_git_ids.as_mut() {
+            contributor_git_ids.retain(|git_id| !bot_git_ids.contains(git_id));
+        }
+        if self.contributor_count.is_some() {
+            self.contributor_count = Some(contributors.len() as u64);
+        }
+
+        // the last commit by a bot is not a commit by a contributor
+        if let Some(last_commit_author) = self.last_commit_author.as_ref() {
+            if bot_git_ids.contains(last_commit_author) {
+                self.last_commit_author = contributors
+                    .iter()
+                    .max_by_key(|contributor| contributor.last_commit_epoch)
+                    .map(|contributor| contributor.git_id.clone());
+            }
+        }
+
+        info!("Removed {} bot contributors", bot_git_ids.len());
+    }
+
     /// Copy the list of collaborators, init and head dates from the old report.
     pub async fn copy_commit_info(self, old_report: &Self) -> Self {
         let mut report = self;
@@ -909,16 +1447,22 @@ impl Report {
     }
 
     /// Adds the entire list of tree files or just the touched files to the report, extracts names of unprocessed files
-    /// and counts their extensions.
+    /// and counts their extensions. The tree file names are stored with `/` separators to match the processed files.
     pub fn update_project_file_lists(self, all_tree_files: HashSet<String>) -> Self {
         // result collector
         let mut report = self;
 
+        // the tree may come from a caller with Windows paths
+        let all_tree_files = all_tree_files
+            .iter()
+            .map(|file_name| utils::normalize_file_name(file_name))
+            .collect::<HashSet<String>>();
+
         // subtract processed files from all files to get the list of unprocessed files
         let processed_files = report
             .per_file_tech
             .iter()
-            .map(|tech| tech.file_name.as_ref().unwrap_or(&String::new()).clone())
+            .map(|tech| tech.file_name.clone().unwrap_or_default())
             .collect::<HashSet<String>>();
         let unprocessed_files = all_tree_files
             .difference(&processed_files)
@@ -960,14 +1504,19 @@ impl Report {
             // it would be computationally prohibitive to try and find a match,
             x.file_name =
                 Some(hash_str_to_sha256_as_base58(&[&salt, x.file_name.unwrap_or_default().as_str()].concat()));
-            x.keywords.clear();
-            x.pkgs.clear();
+            x.keywords = None;
+            x.pkgs = None;
             x.pkgs_kw = None;
-            x.refs.clear();
+            x.refs = None;
             x.refs_kw = None;
+            x.todo_authors = None;
             report.per_file_tech.insert(x);
         }
 
+        // directory names are as sensitive as file names, but cannot be hashed without losing their meaning
+        report.per_dir_tech = None;
+        report.hot_files = None;
+
         // this may be an email address of someone else
         report.last_commit_author = None;
         // someone's else commit hash can be used for matching across devs
@@ -1039,17 +1588,72 @@ impl Report {
         let libs_project = Some(
             self.tech
                 .iter()
-                .map(|t| t.refs.len() as u64 + t.pkgs.len() as u64)
+                .map(|t| t.refs.iter().flatten().count() as u64 + t.pkgs.iter().flatten().count() as u64)
                 .sum::<u64>(),
         );
 
+        let copyright_years = self
+            .tech
+            .iter()
+            .filter_map(|t| t.copyright_years)
+            .fold(None, |years, tech_years| Some(Tech::extend_years(years, tech_years)));
+
         Self {
             loc_project,
             libs_project,
+            copyright_years,
             ..self
         }
     }
 
+    /// Records the hashes of the munchers used to produce the report and the version of the rule set
+    /// to tell if the report is stale by comparing it with the current rules.
+    /// The hashes are taken from `code_rules` for munchers loaded there and from the report itself otherwise.
+    pub fn with_rule_provenance(self, code_rules: &CodeRules) -> Self {
+        let mut report = self;
+
+        let mut muncher_hashes: BTreeMap<String, u64> = BTreeMap::new();
+        for tech in report.tech.iter().chain(report.per_file_tech.iter()) {
+            // merged reports have muncher names reset
+            if tech.muncher_name.is_empty() {
+                continue;
+            }
+            let muncher_hash = match code_rules.munchers.get(&tech.muncher_name) {
+                Some(Some(muncher)) => muncher.muncher_hash,
+                _ => tech.muncher_hash,
+            };
+            muncher_hashes.insert(tech.muncher_name.clone(), muncher_hash);
+        }
+        debug!("Recorded {} muncher hashes", muncher_hashes.len());
+
+        report.muncher_hashes = Some(muncher_hashes);
+        report.rules_timestamp = Some(CodeRules::RULES_TIMESTAMP.to_owned());
+
+        report
+    }
+
+    /// Returns up to `count` files touched by the most commits in `git_log` with the number of commits, most changed
+    /// first, and ties sorted by name a-z. Returns None if none of the log entries have a list of files.
+    pub(crate) fn hot_files_from_log(git_log: &[GitLogEntry], count: usize) -> Option<Vec<(String, usize)>> {
+        let mut commits_per_file: HashMap<&String, usize> = HashMap::new();
+        for file_name in git_log.iter().flat_map(|log_entry| log_entry.files.iter()) {
+            *commits_per_file.entry(file_name).or_insert(0) += 1;
+        }
+        if commits_per_file.is_empty() {
+            debug!("No file lists in the log for hot files");
+            return None;
+        }
+
+        let mut hot_files = commits_per_file
+            .into_iter()
+            .map(|(file_name, commits)| (file_name.clone(), commits))
+            .collect::<Vec<(String, usize)>>();
+        hot_files.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hot_files.truncate(count);
+
+        Some(hot_files)
+    }
+
     /// Parses `self.timestamp` from RFC3339 to an EPOCH. Returns 0 if the value is not valid.
     pub fn parsed_timestamp(&self) -> i64 {
         // check if the report is in an older format and has to be reprocessed regardless
@@ -1061,6 +1665,100 @@ impl Report {
         }
     }
 
+    /// Returns the average number of lines of code added per day between `date_init` and `date_head`.
+    /// Returns None if either date is missing or invalid or if the history is shorter than a day.
+    pub fn loc_per_day(&self) -> Option<f64> {
+        let date_init = match DateTime::parse_from_rfc3339(self.date_init.as_ref()?) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Invalid INIT commit date: {:?} ({}). Expected RFC3339 format.", self.date_init, e);
+                return None;
+            }
+        };
+        let date_head = match DateTime::parse_from_rfc3339(self.date_head.as_ref()?) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Invalid HEAD commit date: {:?} ({}). Expected RFC3339 format.", self.date_head, e);
+                return None;
+            }
+        };
+
+        // single-day repos would produce misleading numbers
+        let days = (date_head - date_init).num_days();
+        if days < 1 {
+            return None;
+        }
+
+        let loc = self.tech.iter().map(|t| t.code_lines).sum::<u64>();
+
+        Some(loc as f64 / days as f64)
+    }
+
+    /// A heuristic that returns `true` if the project history looks like an import of an existing codebase, e.g. a fork
+    /// or vendored code, rather than organic development. Uses the default `IMPORTED_HISTORY_THRESHOLD`.
+    /// See `likely_imported_history_with_threshold()` for details.
+    pub fn likely_imported_history(&self) -> bool {
+        self.likely_imported_history_with_threshold(Report::IMPORTED_HISTORY_THRESHOLD)
+    }
+
+    /// A heuristic that returns `true` if the very first commit of the project is the last commit for more than
+    /// `threshold` fraction (0..1) of all files touched by the contributors.
+    ///
+    /// Only the last commit per file is retained in `contributors`, so the number of files from the first commit is
+    /// a lower bound. That is good enough for imports where most of the imported files are never modified again.
+    /// Returns `false` if there is no contributor data or the project has fewer than `IMPORTED_HISTORY_MIN_FILES` files.
+    pub fn likely_imported_history_with_threshold(&self, threshold: f64) -> bool {
+        let contributors = match self.contributors.as_ref() {
+            Some(v) => v,
+            None => return false,
+        };
+
+        // the same file may be listed by multiple contributors - keep only the latest commit per file
+        let mut latest_per_file: HashMap<&String, &ContributorFile> = HashMap::new();
+        for file in contributors.iter().flat_map(|c| c.touched_files.iter()) {
+            match latest_per_file.get(&file.name) {
+                Some(latest) if latest.date_epoch >= file.date_epoch => {}
+                _ => {
+                    latest_per_file.insert(&file.name, file);
+                }
+            }
+        }
+        let touched_files = latest_per_file.into_values().collect::<Vec<&ContributorFile>>();
+
+        if touched_files.len() < Report::IMPORTED_HISTORY_MIN_FILES {
+            return false;
+        }
+
+        // the oldest commit found in the list of files is the closest we can get to the first commit of the project
+        let first_commit = match touched_files.iter().min_by_key(|f| f.date_epoch) {
+            Some(v) => &v.commit,
+            None => return false,
+        };
+
+        let files_from_first_commit = touched_files.iter().filter(|f| &f.commit == first_commit).count();
+        let fraction = files_from_first_commit as f64 / touched_files.len() as f64;
+        debug!(
+            "{} of {} files were last changed in the first commit {}",
+            files_from_first_commit,
+            touched_files.len(),
+            first_commit
+        );
+
+        fraction > threshold
+    }
+
+    /// Returns the number of tech-debt markers per author across all languages, e.g. `alice` from `TODO(alice)`.
+    /// The markers are recognized by `Muncher.todo_authors` regex.
+    pub fn todo_by_author(&self) -> HashMap<String, usize> {
+        let mut todo_by_author: HashMap<String, usize> = HashMap::new();
+
+        for todo_author in self.tech.iter().filter_map(|t| t.todo_authors.as_ref()).flatten() {
+            *todo_by_author.entry(todo_author.k.clone()).or_default() += todo_author.c as usize;
+        }
+
+        todo_by_author
+    }
+
     /// Parses `REPORT_FORMAT_VERSION` from RFC3339 to an EPOCH. Panics if the value is not valid.
     /// Reports produced prior to that date should be updated.
     pub fn report_format_version() -> i64 {
@@ -1194,14 +1892,20 @@ impl Report {
         }
 
         // place the tech histories into the top level tech records of the report
-        self.tech = self
-            .tech
+        // the keys borrow from the project overviews in `self` and have to be copied before `self` can be modified
+        let mut tech_history_map = tech_history_map
+            .into_iter()
+            .map(|(language, tech_history)| (language.clone(), tech_history))
+            .collect::<HashMap<String, TechHistory>>();
+        let techs = self
+            .tech_mut()
             .drain()
             .map(|mut v| {
                 v.history = tech_history_map.remove(&v.language);
                 v
             })
             .collect::<HashSet<Tech>>();
+        *self.tech_mut() = techs;
     }
 }
 
@@ -1219,12 +1923,177 @@ impl std::fmt::Display for Report {
     }
 }
 
+/// Returns the directory part of the file path relative to the project root with no more than `max_depth` components,
+/// e.g. `src/report` for `src/report/tech.rs`. Returns `.` for files in the root.
+fn dir_of_file(file_name: &str, max_depth: Option<usize>) -> String {
+    let mut components = file_name.split('/').collect::<Vec<&str>>();
+    // the last component is the file name
+    components.pop();
+    if let Some(max_depth) = max_depth {
+        components.truncate(max_depth);
+    }
+
+    if components.is_empty() {
+        ".".to_owned()
+    } else {
+        components.join("/")
+    }
+}
+
+/// Saves `payload` into a temp file next to `file_name` and renames it over `file_name`. Logs errors.
+fn save_atomically(file_name: &Path, payload: &[u8]) -> Result<(), std::io::Error> {
+    let temp_file_name = match write_temp_file(file_name, payload) {
+        Err(e) => {
+            error!("Cannot save a report in {} due to {}", file_name.to_string_lossy(), e);
+            return Err(e);
+        }
+        Ok(v) => v,
+    };
+    if let Err(e) = std::fs::rename(&temp_file_name, file_name) {
+        error!("Cannot save a report in {} due to {}", file_name.to_string_lossy(), e);
+        let _ = std::fs::remove_file(&temp_file_name);
+        return Err(e);
+    };
+
+    info!("Report saved into {}", file_name.to_string_lossy());
+    Ok(())
+}
+
+/// Returns the contents as-is or decompressed if they start with the gzip magic bytes.
+fn gunzip_if_compressed(contents: Vec<u8>) -> Result<Vec<u8>, std::io::Error> {
+    if !contents.starts_with(&GZIP_MAGIC_BYTES) {
+        return Ok(contents);
+    }
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(contents.as_slice()).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// All gzip streams start with these 2 bytes.
+const GZIP_MAGIC_BYTES: [u8; 2] = [0x1F, 0x8B];
+
+/// Writes `payload` into a new temp file in the same folder as `file_name` and returns the name of the temp file.
+/// The temp file has to be on the same file system for the rename to be atomic.
+fn write_temp_file(file_name: &Path, payload: &[u8]) -> Result<PathBuf, std::io::Error> {
+    let temp_file_name = file_name.with_file_name(
+        [
+            ".",
+            &file_name.file_name().unwrap_or_default().to_string_lossy(),
+            ".",
+            &uuid::Uuid::new_v4().to_string(),
+            ".tmp",
+        ]
+        .concat(),
+    );
+
+    let mut temp_file = File::create(&temp_file_name)?;
+    if let Err(e) = temp_file.write_all(payload).and_then(|_| temp_file.sync_all()) {
+        let _ = std::fs::remove_file(&temp_file_name);
+        return Err(e);
+    }
+
+    Ok(temp_file_name)
+}
+
 #[cfg(test)]
 mod test_report {
     use super::Report;
+    use crate::report::kwc::KeywordCounter;
+    use crate::report::tech::Tech;
+    use std::collections::{BTreeMap, HashSet};
     use std::fs::File;
     use std::io::prelude::*;
 
+    /// Returns a report for a single-language repo with the given LoC
+    fn repo_report(s3_name: &str, timestamp: &str, code_lines: u64) -> Report {
+        let mut report = Report::new();
+        report.report_s3_name = s3_name.to_string();
+        report.timestamp = timestamp.to_string();
+        report.merge_tech_record(Tech::for_test("Rust", code_lines));
+        report
+    }
+
+    #[test]
+    fn test_merge_latest_drops_older_duplicates() {
+        let reports = vec![
+            repo_report("repo_a.report", "2021-11-01T00:00:00+00:00", 100),
+            repo_report("repo_b.report", "2021-11-01T00:00:00+00:00", 10),
+            repo_report("repo_a.report", "2021-11-05T00:00:00+00:00", 150),
+        ];
+
+        let latest = Report::retain_latest_per_repo(reports.clone());
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[0].report_s3_name, "repo_b.report");
+        assert_eq!(latest[1].timestamp, "2021-11-05T00:00:00+00:00");
+
+        // only the newer version of repo_a is counted
+        let merged = Report::merge_latest(reports).unwrap();
+        assert_eq!(merged.tech.iter().map(|t| t.code_lines).sum::<u64>(), 160);
+    }
+
+    #[test]
+    fn test_merge_language_aliases() {
+        let mut report_old_rules = Report::new();
+        report_old_rules.merge_tech_record(Tech::for_test("JS", 100));
+        let mut report_new_rules = Report::new();
+        report_new_rules.merge_tech_record(Tech::for_test("JavaScript", 50));
+
+        let merged = Report::merge(None, report_old_rules);
+        let merged = Report::merge(merged, report_new_rules).unwrap();
+
+        assert_eq!(merged.tech.len(), 1);
+        let tech = merged.tech.iter().next().unwrap();
+        assert_eq!(tech.language, "JavaScript");
+        assert_eq!(tech.code_lines, 150);
+    }
+
+    #[test]
+    fn test_merge_same_report_twice() {
+        let report_a = repo_report("repo_a.report", "2021-11-01T00:00:00+00:00", 100);
+        let report_b = repo_report("repo_b.report", "2021-11-01T00:00:00+00:00", 10);
+
+        let merged = Report::merge(None, report_a.clone());
+        let merged = Report::merge(merged, report_b.clone()).unwrap();
+        let code_lines = merged.tech.iter().map(|t| t.code_lines).sum::<u64>();
+        assert_eq!(code_lines, 110);
+
+        // a retried merge of either report changes nothing
+        let merged = Report::merge(Some(merged), report_b).unwrap();
+        let merged = Report::merge(Some(merged), report_a).unwrap();
+        assert_eq!(merged.tech.iter().map(|t| t.code_lines).sum::<u64>(), code_lines);
+        assert_eq!(merged.report_ids_included.len(), 2);
+    }
+
+    #[test]
+    fn test_merge_normalized_is_order_independent() {
+        let mut report_a = repo_report("repo_a.report", "2021-11-01T00:00:00+00:00", 100);
+        report_a.date_head = Some("2021-11-01T00:00:00+00:00".to_string());
+        report_a.unprocessed_file_names.insert("a.bin".to_string());
+        let mut report_b = repo_report("repo_b.report", "2021-11-05T00:00:00+00:00", 10);
+        report_b.merge_tech_record(Tech::for_test("Go", 20));
+        report_b.date_head = Some("2021-11-05T00:00:00+00:00".to_string());
+
+        let a_then_b = Report::merge_with_options(None, report_a.clone(), true);
+        let a_then_b = Report::merge_with_options(a_then_b, report_b.clone(), true).unwrap();
+        let b_then_a = Report::merge_with_options(None, report_b, true);
+        let b_then_a = Report::merge_with_options(b_then_a, report_a, true).unwrap();
+
+        let loc_per_language = |report: &Report| {
+            report
+                .tech
+                .iter()
+                .map(|t| (t.language.clone(), t.code_lines))
+                .collect::<BTreeMap<String, u64>>()
+        };
+        assert_eq!(loc_per_language(&a_then_b), loc_per_language(&b_then_a));
+        assert_eq!(a_then_b.report_ids_included, b_then_a.report_ids_included);
+        assert_eq!(a_then_b.reports_included, b_then_a.reports_included);
+        assert_eq!(a_then_b.date_head, b_then_a.date_head);
+        assert_eq!(a_then_b.unprocessed_file_names, b_then_a.unprocessed_file_names);
+        assert_eq!(a_then_b.projects_included.len(), b_then_a.projects_included.len());
+    }
+
     #[test]
     fn test_merge() {
         tracing_subscriber::fmt()
@@ -1265,7 +2134,7 @@ mod test_report {
             .chain(r2.tech.iter())
             .map(|t| {
                 if t.language == "C#" {
-                    let rs: u64 = t.refs.iter().map(|tr| tr.c).sum();
+                    let rs: u64 = t.refs.iter().flatten().map(|tr| tr.c).sum();
                     rs
                 } else {
                     0
@@ -1278,7 +2147,7 @@ mod test_report {
             .chain(r2.tech.iter())
             .map(|t| {
                 if t.language == "C#" {
-                    let rs: u64 = t.pkgs.iter().map(|tr| tr.c).sum();
+                    let rs: u64 = t.pkgs.iter().flatten().map(|tr| tr.c).sum();
                     rs
                 } else {
                     0
@@ -1315,7 +2184,7 @@ mod test_report {
             .iter()
             .map(|t| {
                 if t.language == "C#" {
-                    let rs: u64 = t.refs.iter().map(|tr| tr.c).sum();
+                    let rs: u64 = t.refs.iter().flatten().map(|tr| tr.c).sum();
                     rs
                 } else {
                     0
@@ -1330,7 +2199,7 @@ mod test_report {
             .iter()
             .map(|t| {
                 if t.language == "C#" {
-                    let rs: u64 = t.pkgs.iter().map(|tr| tr.c).sum();
+                    let rs: u64 = t.pkgs.iter().flatten().map(|tr| tr.c).sum();
                     rs
                 } else {
                     0
@@ -1340,4 +2209,690 @@ mod test_report {
         println!("Pkgs counts, merged: {}, expected {}", cs_pkgs_rm, cs_pkgs);
         assert_eq!(cs_pkgs_rm, cs_pkgs, "C# pkgs count");
     }
+
+    #[test]
+    fn test_legacy_report_is_migrated_on_load() {
+        let path = std::env::temp_dir().join(format!("stm_legacy_report_{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"{"timestamp": "2021-01-01T00:00:00+00:00", "tech": []}"#).unwrap();
+
+        let report = Report::from_disk(&path).unwrap();
+        assert_eq!(report.schema_version, Report::SCHEMA_VERSION);
+
+        // new reports are created with the current version
+        assert_eq!(Report::new().schema_version, Report::SCHEMA_VERSION);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_interrupted_save_leaves_report_intact() {
+        let report_dir = std::env::temp_dir().join(format!("stm_save_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&report_dir).unwrap();
+        let file_name = report_dir.join("project_report.json");
+
+        let mut report = Report::new();
+        report.tech_mut().insert(Tech::for_test("Rust", 10));
+        report.save_as_local_file(&file_name, false).unwrap();
+
+        // the process dies after writing the new report into the temp file, but before the rename
+        let mut new_report = Report::new();
+        new_report.tech_mut().insert(Tech::for_test("Go", 20));
+        let temp_file_name = super::write_temp_file(&file_name, &serde_json::to_vec(&new_report).unwrap()).unwrap();
+        assert_ne!(temp_file_name, file_name);
+        assert_eq!(temp_file_name.parent(), file_name.parent());
+
+        let saved_report = Report::from_disk(&file_name).unwrap();
+        assert_eq!(saved_report.tech.iter().next().unwrap().language, "Rust");
+        std::fs::remove_file(&temp_file_name).unwrap();
+
+        // a complete save replaces the report and leaves no temp files behind
+        new_report.save_as_local_file(&file_name, true).unwrap();
+        let saved_report = Report::from_disk(&file_name).unwrap();
+        assert_eq!(saved_report.tech.iter().next().unwrap().language, "Go");
+        assert_eq!(std::fs::read_dir(&report_dir).unwrap().count(), 1);
+
+        // a missing folder is an error, not a panic
+        assert!(new_report
+            .save_as_local_file(&report_dir.join("no_such_dir").join("report.json"), false)
+            .is_err());
+
+        let _ = std::fs::remove_dir_all(&report_dir);
+    }
+
+    #[test]
+    fn test_gzipped_report_round_trip() {
+        let report_dir = std::env::temp_dir().join(format!("stm_gz_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&report_dir).unwrap();
+
+        let mut report = Report::new();
+        report.tech_mut().insert(Tech::for_test("Rust", 10));
+        report.tech_mut().insert(Tech::for_test("Go", 20));
+        let mut file_tech = Tech::for_test("Rust", 10);
+        file_tech.file_name = Some("src/main.rs".to_owned());
+        report.per_file_tech.insert(file_tech);
+
+        let gz_file_name = report_dir.join("project_report.report.gz");
+        report.save_as_local_file_gz(&gz_file_name).unwrap();
+        assert!(std::fs::read(&gz_file_name)
+            .unwrap()
+            .starts_with(&super::GZIP_MAGIC_BYTES));
+        let gz_report = Report::from_disk(&gz_file_name).unwrap();
+
+        // uncompressed reports load as before
+        let json_file_name = report_dir.join("project_report.json");
+        report.save_as_local_file(&json_file_name, false).unwrap();
+        let json_report = Report::from_disk(&json_file_name).unwrap();
+
+        let expected = serde_json::to_string(&report).unwrap();
+        assert_eq!(serde_json::to_string(&gz_report).unwrap(), expected);
+        assert_eq!(serde_json::to_string(&json_report).unwrap(), expected);
+
+        let _ = std::fs::remove_dir_all(&report_dir);
+    }
+
+    #[test]
+    fn test_load_missing_valid_and_corrupt_reports() {
+        use crate::report::LoadError;
+
+        let report_dir = std::env::temp_dir().join(format!("stm_load_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&report_dir).unwrap();
+
+        // missing is not an error
+        assert!(Report::load_from_disk(&report_dir.join("missing.json"))
+            .unwrap()
+            .is_none());
+
+        let mut report = Report::new();
+        report.tech_mut().insert(Tech::for_test("Rust", 10));
+        let json_file_name = report_dir.join("project_report.json");
+        report.save_as_local_file(&json_file_name, false).unwrap();
+        let gz_file_name = report_dir.join("project_report.report.gz");
+        report.save_as_local_file_gz(&gz_file_name).unwrap();
+        for file_name in [&json_file_name, &gz_file_name].iter() {
+            let loaded = Report::load_from_disk(file_name).unwrap().unwrap();
+            assert_eq!(loaded.tech.iter().next().unwrap().code_lines, 10);
+        }
+
+        // truncated files are errors, but the old signature still returns None
+        let json = std::fs::read(&json_file_name).unwrap();
+        std::fs::write(&json_file_name, &json[..json.len() / 2]).unwrap();
+        assert!(matches!(Report::load_from_disk(&json_file_name), Err(LoadError::Parse(_))));
+        assert!(Report::from_disk(&json_file_name).is_none());
+
+        let gz = std::fs::read(&gz_file_name).unwrap();
+        assert!(matches!(Report::from_bytes(gz[..gz.len() / 2].to_vec()), Err(LoadError::Decompress(_))));
+        assert!(matches!(Report::from_bytes(Vec::new()), Err(LoadError::Parse(_))));
+
+        let _ = std::fs::remove_dir_all(&report_dir);
+    }
+
+    #[test]
+    fn test_contributors_from_disk() {
+        use crate::report::LoadError;
+
+        let report_dir = std::env::temp_dir().join(format!("stm_contributors_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&report_dir).unwrap();
+        let report_file_name = report_dir.join("project_report.json");
+
+        assert!(matches!(Report::contributors_from_disk(&report_file_name), Err(LoadError::Read(_))));
+
+        let mut report = Report::new();
+        report.tech_mut().insert(Tech::for_test("Rust", 10));
+        report.save_as_local_file(&report_file_name, false).unwrap();
+        assert!(matches!(
+            Report::contributors_from_disk(&report_file_name),
+            Err(LoadError::MissingSection("contributors"))
+        ));
+
+        let mut git_log_entry = crate::git::GitLogEntry::new();
+        git_log_entry.sha1 = "a".repeat(40);
+        git_log_entry.author_name_email = ("Dev".to_owned(), "dev@example.com".to_owned());
+        let report = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(report.add_commits_history(vec![git_log_entry], &crate::mailmap::Mailmap::default()));
+        report.save_as_local_file(&report_file_name, false).unwrap();
+        let contributors = Report::contributors_from_disk(&report_file_name).unwrap();
+        assert_eq!(contributors.len(), 1);
+        assert_eq!(contributors[0].git_id, "dev@example.com");
+
+        std::fs::write(&report_file_name, "{\"contributors\": [").unwrap();
+        assert!(matches!(Report::contributors_from_disk(&report_file_name), Err(LoadError::Parse(_))));
+
+        let _ = std::fs::remove_dir_all(&report_dir);
+    }
+
+    #[test]
+    fn test_test_code_lines_are_merged() {
+        let mut report = Report::new();
+        for (file_name, code_lines) in [("src/foo.rs", 100), ("src/foo_test.rs", 30), ("tests/it.rs", 20)].iter() {
+            let mut file_tech = Tech::for_test("Rust", *code_lines);
+            file_tech.file_name = Some(file_name.to_string());
+            file_tech.set_is_test(file_name.contains("test"));
+            report.merge_tech_record(file_tech);
+        }
+
+        let rust = report.tech.iter().next().unwrap();
+        assert_eq!(rust.code_lines, 150);
+        assert_eq!(rust.test_code_lines, 50);
+        assert!(!rust.is_test);
+    }
+
+    #[test]
+    fn test_generated_code_lines_are_merged() {
+        let mut report = Report::new();
+        for (file_name, code_lines) in [("src/foo.rs", 100), ("src/foo.pb.rs", 30)].iter() {
+            let mut file_tech = Tech::for_test("Rust", *code_lines);
+            file_tech.file_name = Some(file_name.to_string());
+            file_tech.set_is_generated(file_name.contains(".pb."));
+            report.merge_tech_record(file_tech);
+        }
+
+        let rust = report.tech.iter().next().unwrap();
+        assert_eq!(rust.code_lines, 130);
+        assert_eq!(rust.generated_code_lines, 30);
+        assert!(!rust.is_generated);
+    }
+
+    #[test]
+    fn test_compute_per_dir_tech() {
+        let mut report = Report::new();
+
+        // skipped cleanly with no per-file records
+        report.compute_per_dir_tech(None);
+        assert!(report.per_dir_tech.is_none());
+
+        for (file_name, language, code_lines) in [
+            ("src/main.rs", "Rust", 10),
+            ("src/report/tech.rs", "Rust", 20),
+            ("src/report/style.css", "CSS", 5),
+            ("tests/integration.rs", "Rust", 7),
+        ]
+        .iter()
+        {
+            let mut file_tech = Tech::for_test(language, *code_lines);
+            file_tech.file_name = Some(file_name.to_string());
+            report.per_file_tech.insert(file_tech);
+        }
+
+        // only the top directory
+        report.compute_per_dir_tech(Some(1));
+        let per_dir_tech = report.per_dir_tech.as_ref().unwrap();
+        assert_eq!(per_dir_tech.len(), 2);
+        let src = &per_dir_tech["src"];
+        assert_eq!(src.len(), 2);
+        let src_rust = src.iter().find(|tech| tech.language == "Rust").unwrap();
+        assert_eq!(src_rust.code_lines, 30);
+        assert_eq!(src_rust.files, 2);
+        assert!(src_rust.file_name.is_none());
+        assert_eq!(per_dir_tech["tests"].iter().next().unwrap().code_lines, 7);
+
+        // the full directory path
+        report.compute_per_dir_tech(None);
+        let mut dirs = report
+            .per_dir_tech
+            .as_ref()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+        dirs.sort();
+        assert_eq!(dirs, vec!["src", "src/report", "tests"]);
+
+        assert_eq!(super::dir_of_file("Cargo.toml", None), ".");
+    }
+
+    #[test]
+    fn test_serialization_is_deterministic() {
+        let tech = |language: &str| {
+            let mut tech = serde_json::to_value(Tech::for_test(language, 10)).unwrap();
+            tech["keywords"] = serde_json::json!([{"k": "while", "c": 1}, {"k": "for", "c": 2}, {"k": "if", "c": 3}, {"k": "match", "c": 4}]);
+            tech["refs"] =
+                serde_json::json!([{"k": "std::io", "c": 1}, {"k": "serde", "c": 1}, {"k": "regex", "c": 1}]);
+            tech
+        };
+        // the old unordered form is still accepted
+        let json = serde_json::json!({
+            "timestamp": "2021-01-01T00:00:00+00:00",
+            "tech": [tech("Rust"), tech("Go"), tech("TypeScript"), tech("CSharp"), tech("Python")],
+            "unprocessed_file_names": ["z.bin", "a.bin", "m.bin", "b.bin"],
+            "keywords": ["zip", "alpha", "mid", "beta"],
+        })
+        .to_string();
+
+        // every deserialized report has its own hash sets with a different order of items
+        let outputs = (0..5)
+            .map(|_| serde_json::to_string(&serde_json::from_str::<Report>(&json).unwrap()).unwrap())
+            .collect::<Vec<String>>();
+        assert!(outputs.iter().all(|output| output == &outputs[0]));
+
+        let report = serde_json::from_str::<serde_json::Value>(&outputs[0]).unwrap();
+        let languages = report["tech"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tech| tech["language"].as_str().unwrap())
+            .collect::<Vec<&str>>();
+        assert_eq!(languages, vec!["CSharp", "Go", "Python", "Rust", "TypeScript"]);
+        assert_eq!(report["keywords"], serde_json::json!(["alpha", "beta", "mid", "zip"]));
+        assert_eq!(report["tech"][0]["keywords"][0]["k"], "for");
+    }
+
+    #[test]
+    fn test_diff_with_added_language() {
+        let older = repo_report("older", "2021-01-01T00:00:00Z", 100);
+        let mut newer = repo_report("newer", "2021-02-01T00:00:00Z", 120);
+        newer.merge_tech_record(Tech::for_test("Go", 40));
+
+        let diff = older.diff(&newer);
+        assert_eq!(diff.added_languages, vec!["Go"]);
+        assert!(diff.removed_languages.is_empty());
+        assert_eq!(diff.languages["Rust"].code_lines, 20);
+        assert_eq!(diff.languages["Rust"].files, 0);
+        assert_eq!(diff.languages["Go"].code_lines, 40);
+        assert_eq!(diff.languages["Go"].files, 1);
+
+        // the reverse diff has the language removed
+        let diff = newer.diff(&older);
+        assert_eq!(diff.removed_languages, vec!["Go"]);
+        assert_eq!(diff.languages["Go"].code_lines, -40);
+
+        // no changes, no entries
+        assert!(older.diff(&older).languages.is_empty());
+    }
+
+    #[test]
+    fn test_diff_with_removed_dependency() {
+        let with_deps = |deps: &[&str]| {
+            let mut rust = Tech::for_test("Rust", 100);
+            for dep in deps {
+                rust.refs
+                    .get_or_insert_with(Default::default)
+                    .insert(crate::report::kwc::KeywordCounter {
+                        k: dep.to_string(),
+                        t: None,
+                        c: 1,
+                    });
+            }
+            let mut report = Report::new();
+            report.merge_tech_record(rust);
+            report
+        };
+
+        let older = with_deps(&["regex", "serde"]);
+        let newer = with_deps(&["serde"]);
+
+        let diff = older.diff(&newer);
+        assert!(diff.added_languages.is_empty());
+        assert!(diff.removed_languages.is_empty());
+        let rust = &diff.languages["Rust"];
+        assert_eq!(rust.code_lines, 0);
+        assert!(rust.dependencies.added.is_empty());
+        assert_eq!(rust.dependencies.removed, vec!["regex"]);
+
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(json["languages"]["Rust"]["dependencies"]["removed"][0], "regex");
+        assert_eq!(serde_json::from_value::<crate::report::ReportDiff>(json).unwrap(), diff);
+    }
+
+    #[test]
+    fn test_remove_bots() {
+        let contributor = |name: &str, email: &str, last_commit_epoch: i64| {
+            let mut name_email_pairs = std::collections::HashSet::new();
+            name_email_pairs.insert((name.to_owned(), email.to_owned()));
+            crate::contributor::Contributor {
+                git_id: email.to_owned(),
+                name_email_pairs,
+                last_commit_sha1: String::new(),
+                last_commit_epoch,
+                last_commit_date: String::new(),
+                commit_count: 1,
+                lines_added: 0,
+                lines_removed: 0,
+                commit_type_counts: None,
+                touched_files: std::collections::HashSet::new(),
+                commits: Vec::new(),
+            }
+        };
+
+        let mut report = Report::new();
+        report.contributors = Some(vec![
+            contributor("Alice", "alice@example.com", 100),
+            contributor("dependabot[bot]", "49699333+dependabot[bot]@users.noreply.github.com", 300),
+            contributor("Bob", "bob@example.com", 200),
+            contributor("github-actions[bot]", "41898282+github-actions[bot]@users.noreply.github.com", 250),
+            contributor("Renovate Bot", "renovate@whitesourcesoftware.com", 50),
+        ]);
+        report.contributor_git_ids = Some(
+            report
+                .contributors
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|c| c.git_id.clone())
+                .collect(),
+        );
+        report.contributor_count = Some(5);
+        report.last_commit_author = Some("49699333+dependabot[bot]@users.noreply.github.com".to_owned());
+
+        report.remove_bots(&crate::code_rules::CodeRules::new().bot_patterns);
+
+        let git_ids = report
+            .contributors
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|c| c.git_id.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(git_ids, vec!["alice@example.com", "bob@example.com"]);
+        assert_eq!(report.contributor_git_ids.as_ref().unwrap().len(), 2);
+        assert!(report.contributor_git_ids.as_ref().unwrap().contains("bob@example.com"));
+        assert_eq!(report.contributor_count, Some(2));
+        // the latest human commit
+        assert_eq!(report.last_commit_author.as_deref(), Some("bob@example.com"));
+    }
+
+    #[test]
+    fn test_update_project_file_lists_with_windows_paths() {
+        let mut report = Report::new();
+        for file_name in ["src/main.rs", "docs/guide.md"].iter() {
+            let mut file_tech = Tech::for_test("Rust", 1);
+            file_tech.file_name = Some(file_name.to_string());
+            report.per_file_tech.insert(file_tech);
+        }
+        report.add_unprocessed_file_with_reason("build/bundle.min.js", "minified".to_owned());
+
+        let all_tree_files = ["src\\main.rs", "src/lib.rs", "docs/guide.md", "build\\bundle.min.js"]
+            .iter()
+            .map(|file_name| file_name.to_string())
+            .collect::<std::collections::HashSet<String>>();
+        let report = report.update_project_file_lists(all_tree_files);
+
+        // the same file with different separators is not double-counted as processed and unprocessed
+        let mut unprocessed = report.unprocessed_file_names.iter().cloned().collect::<Vec<String>>();
+        unprocessed.sort();
+        assert_eq!(unprocessed, vec!["build/bundle.min.js", "src/lib.rs"]);
+        assert!(report.unprocessed_file_reasons.contains_key("build/bundle.min.js"));
+
+        let tree_files = report.tree_files.as_ref().unwrap();
+        assert_eq!(tree_files.len(), 4);
+        assert!(tree_files.iter().all(|file_name| !file_name.contains('\\')));
+        assert!(tree_files.contains("src/main.rs"));
+    }
+
+    #[test]
+    fn test_is_mergeable_with_log_hashes() {
+        let history = |log_hash: Option<&str>, date_init: &str| {
+            let mut report = repo_report("repo_a.report", "2021-11-01T00:00:00+00:00", 100);
+            report.log_hash = log_hash.map(|v| v.to_owned());
+            report.date_init = Some(date_init.to_owned());
+            report
+        };
+        let before_rebase = history(Some("a5f3"), "2020-01-01T00:00:00+00:00");
+        let after_rebase = history(Some("9c1e"), "2020-01-01T00:00:00+00:00");
+        let same_history = history(Some("a5f3"), "2020-01-01T00:00:00+00:00");
+        let other_repo = history(Some("9c1e"), "2019-05-05T00:00:00+00:00");
+        let no_history = history(None, "2020-01-01T00:00:00+00:00");
+        let before_rebase_again = before_rebase.clone();
+        let after_rebase_again = after_rebase.clone();
+
+        assert!(before_rebase.is_mergeable_with(&same_history));
+        assert!(!before_rebase.is_mergeable_with(&after_rebase));
+        assert!(!after_rebase.is_mergeable_with(&before_rebase));
+        assert!(before_rebase.is_mergeable_with(&other_repo));
+        assert!(before_rebase.is_mergeable_with(&no_history));
+
+        // a mismatch is flagged instead of adding up the totals
+        let merged = Report::merge(Some(before_rebase.clone()), after_rebase).unwrap();
+        assert!(merged.has_incompatible_history);
+        assert_eq!(merged.tech.iter().map(|tech| tech.code_lines).sum::<u64>(), 100);
+
+        let merged = Report::merge(Some(before_rebase), same_history).unwrap();
+        assert!(!merged.has_incompatible_history);
+        assert_eq!(merged.tech.iter().map(|tech| tech.code_lines).sum::<u64>(), 200);
+        assert_eq!(merged.log_hash.as_deref(), Some("a5f3"));
+
+        // the combined report of different repos has no single history
+        let merged = Report::merge(Some(merged), other_repo).unwrap();
+        assert!(merged.log_hash.is_none());
+        assert_eq!(merged.log_hashes_included.len(), 2);
+
+        // the histories of the merged repos are still checked
+        let merged = Report::merge(Some(merged), history(Some("77b2"), "2020-01-01T00:00:00+00:00")).unwrap();
+        assert!(merged.has_incompatible_history);
+        assert_eq!(merged.tech.iter().map(|tech| tech.code_lines).sum::<u64>(), 300);
+
+        // the same when the combined report starts from a blank one
+        let merged = [before_rebase_again, after_rebase_again]
+            .iter()
+            .cloned()
+            .fold(None, |merged, report| Report::merge_with_options(merged, report, true))
+            .unwrap();
+        assert!(merged.has_incompatible_history);
+    }
+
+    #[test]
+    fn test_top_languages() {
+        let mut report = Report::new();
+        report.tech_mut().insert(Tech::for_test("Rust", 300));
+        report.tech_mut().insert(Tech::for_test("Python", 120));
+        report.tech_mut().insert(Tech::for_test("Go", 250));
+        report.tech_mut().insert(Tech::for_test("Shell", 250));
+        // a second muncher for the same language is added to the total
+        let mut python_tests = Tech::for_test("Python", 200);
+        python_tests.muncher_name = "python_tests".to_owned();
+        report.tech_mut().insert(python_tests);
+
+        assert_eq!(report.top_languages(2), vec![("Python".to_owned(), 320), ("Rust".to_owned(), 300)]);
+        // ties are sorted by name
+        assert_eq!(report.top_languages(4)[2..], [("Go".to_owned(), 250), ("Shell".to_owned(), 250)]);
+        assert_eq!(report.top_languages(10).len(), 4);
+        assert!(Report::new().top_languages(2).is_empty());
+    }
+
+    #[test]
+    fn test_hot_files_from_log() {
+        let commit = |files: &[&str]| {
+            let mut commit = crate::git::GitLogEntry::new();
+            commit.files = files.iter().map(|file_name| file_name.to_string()).collect();
+            commit
+        };
+        let git_log = vec![
+            commit(&["src/main.rs", "README.md"]),
+            commit(&["src/main.rs", "src/lib.rs"]),
+            commit(&["src/lib.rs", "src/main.rs", "Cargo.toml"]),
+            commit(&[]),
+        ];
+
+        let hot_files = Report::hot_files_from_log(&git_log, 3).unwrap();
+        assert_eq!(
+            hot_files,
+            vec![
+                ("src/main.rs".to_owned(), 3),
+                ("src/lib.rs".to_owned(), 2),
+                ("Cargo.toml".to_owned(), 1)
+            ]
+        );
+
+        // a log collected with no file lists
+        assert!(Report::hot_files_from_log(&[commit(&[]), commit(&[])], 3).is_none());
+        assert!(Report::hot_files_from_log(&[], 3).is_none());
+    }
+
+    #[test]
+    fn test_loc_per_day() {
+        let mut report = Report::new();
+        report.merge_tech_record(Tech::for_test("Rust", 300));
+        report.merge_tech_record(Tech::for_test("Go", 100));
+        assert!(report.loc_per_day().is_none());
+
+        report.date_init = Some("2021-11-01T10:00:00+00:00".to_owned());
+        report.date_head = Some("2021-11-11T12:00:00+02:00".to_owned());
+        assert_eq!(report.loc_per_day(), Some(40.0));
+
+        // a history shorter than a day
+        report.date_head = Some("2021-11-01T20:00:00+00:00".to_owned());
+        assert!(report.loc_per_day().is_none());
+
+        report.date_head = Some("11/11/2021".to_owned());
+        assert!(report.loc_per_day().is_none());
+    }
+
+    #[test]
+    fn test_with_rule_provenance() {
+        let mut code_rules = crate::code_rules::CodeRules::new();
+        let rust_muncher = code_rules.get_muncher(&"src/main.rs".to_owned()).unwrap();
+        let (rust_muncher_name, rust_muncher_hash) = (rust_muncher.muncher_name.clone(), rust_muncher.muncher_hash);
+
+        // the hash of a loaded muncher is taken from the rules
+        let mut report = Report::new();
+        report.merge_tech_record(Tech {
+            muncher_name: rust_muncher_name.clone(),
+            ..Tech::for_test("Rust", 10)
+        });
+        report.merge_tech_record(Tech {
+            muncher_hash: 42,
+            ..Tech::for_test("Go", 10)
+        });
+        // merged reports have no muncher names
+        report.merge_tech_record(Tech {
+            muncher_name: String::new(),
+            ..Tech::for_test("Python", 10)
+        });
+        assert!(report.muncher_hashes.is_none());
+
+        let report = report.with_rule_provenance(&code_rules);
+        let expected: BTreeMap<String, u64> = [("go".to_owned(), 42), (rust_muncher_name, rust_muncher_hash)]
+            .iter()
+            .cloned()
+            .collect();
+        assert_eq!(report.muncher_hashes, Some(expected));
+        assert_eq!(report.rules_timestamp.as_deref(), Some(crate::code_rules::CodeRules::RULES_TIMESTAMP));
+    }
+
+    #[test]
+    fn test_likely_imported_history() {
+        use crate::contributor::{Contributor, ContributorFile};
+
+        // `imported` files were last changed in the first commit and the rest in later commits
+        let report = |imported: usize, changed: usize| {
+            let file = |idx: usize, commit: &str, date_epoch: i64| ContributorFile {
+                name: format!("src/file_{}.rs", idx),
+                commit: commit.to_owned(),
+                date_epoch,
+                date_iso: String::new(),
+            };
+            let mut touched_files = (0..imported).map(|idx| file(idx, "first", 100)).collect::<HashSet<_>>();
+            touched_files.extend((imported..imported + changed).map(|idx| file(idx, "later", 200)));
+
+            let mut report = Report::new();
+            report.contributors = Some(vec![Contributor {
+                git_id: "dev@example.com".to_owned(),
+                name_email_pairs: HashSet::new(),
+                last_commit_sha1: "later".to_owned(),
+                last_commit_epoch: 200,
+                last_commit_date: String::new(),
+                commit_count: 2,
+                lines_added: 0,
+                lines_removed: 0,
+                commit_type_counts: None,
+                touched_files,
+                commits: Vec::new(),
+            }]);
+            report
+        };
+
+        assert!(report(15, 5).likely_imported_history());
+        assert!(!report(10, 10).likely_imported_history());
+        assert!(report(10, 10).likely_imported_history_with_threshold(0.4));
+        // too few files to tell
+        assert!(!report(15, 4).likely_imported_history());
+        assert!(!Report::new().likely_imported_history());
+    }
+
+    #[test]
+    fn test_ensure_ids() {
+        let mut report = Report::new();
+        report.report_id = String::new();
+        report.report_s3_name = String::new();
+
+        // not enough info for the S3 name
+        report.github_user_name = Some("rimutaka".to_owned());
+        report.ensure_ids();
+        assert!(!report.report_id.is_empty());
+        assert!(report.report_s3_name.is_empty());
+
+        let report_id = report.report_id.clone();
+        report.github_repo_name = Some("stackmuncher".to_owned());
+        report.ensure_ids();
+        assert_eq!(report.report_id, report_id);
+        assert_eq!(report.report_s3_name, ["rimutaka/stackmuncher", Report::REPORT_FILE_NAME_SUFFIX].concat());
+
+        // existing values are kept
+        report.report_s3_name = "other.report".to_owned();
+        report.ensure_ids();
+        assert_eq!(report.report_s3_name, "other.report");
+    }
+
+    #[test]
+    fn test_merge_timed_out() {
+        let complete = repo_report("repo_a.report", "2021-11-01T00:00:00+00:00", 100);
+        let mut timed_out = repo_report("repo_b.report", "2021-11-01T00:00:00+00:00", 10);
+        timed_out.timed_out = true;
+
+        // the flag is kept regardless of the merge order
+        let merged = Report::merge(Some(complete.clone()), timed_out.clone()).unwrap();
+        assert!(merged.timed_out);
+        let merged = Report::merge(Some(timed_out), complete.clone()).unwrap();
+        assert!(merged.timed_out);
+
+        let merged = Report::merge(Some(complete.clone()), complete).unwrap();
+        assert!(!merged.timed_out);
+    }
+
+    #[test]
+    fn test_recompute_tech_section() {
+        let refs = |k: &str, c: u64| {
+            Some(
+                [KeywordCounter {
+                    k: k.to_owned(),
+                    t: None,
+                    c,
+                }]
+                .iter()
+                .cloned()
+                .collect::<HashSet<KeywordCounter>>(),
+            )
+        };
+        let mut report = Report::new();
+        report.per_file_tech.insert(Tech {
+            file_name: Some("a.cs".to_owned()),
+            refs: refs("System.Text.Regex", 2),
+            ..Tech::for_test("C#", 10)
+        });
+        report.per_file_tech.insert(Tech {
+            file_name: Some("b.cs".to_owned()),
+            refs: refs("System.IO", 1),
+            ..Tech::for_test("C#", 5)
+        });
+        // a stale record that is replaced
+        report.merge_tech_record(Tech::for_test("Rust", 100));
+
+        // per-file records have no summaries
+        report.recompute_tech_section(false);
+        assert_eq!(report.tech.len(), 1);
+        let tech = report.tech.iter().next().unwrap();
+        assert_eq!(tech.code_lines, 15);
+        assert!(tech.refs_kw.is_none());
+
+        report.recompute_tech_section(true);
+        let tech = report.tech.iter().next().unwrap();
+        let mut refs_kw = tech
+            .refs_kw
+            .iter()
+            .flatten()
+            .map(|kwc| (kwc.k.as_str(), kwc.c))
+            .collect::<Vec<(&str, u64)>>();
+        refs_kw.sort();
+        assert_eq!(refs_kw, vec![("Regex", 2), ("System", 3), ("Text", 2)]);
+    }
 }
@@ -0,0 +1,90 @@
+use super::Report;
+use serde_json::{json, Value};
+
+impl Report {
+    /// The rule ID of SARIF results for files with no matching muncher.
+    pub const SARIF_RULE_UNKNOWN_FILE_TYPE: &'static str = "stm/unknown-file-type";
+    /// The rule ID of SARIF results for files that were skipped for a reason from `unprocessed_file_reasons`.
+    pub const SARIF_RULE_UNPROCESSED_FILE: &'static str = "stm/unprocessed-file";
+
+    /// Returns a minimal SARIF 2.1.0 log with a result for every file in `unprocessed_file_names`, e.g. for GitHub code
+    /// scanning. Files with a reason in `unprocessed_file_reasons` are reported under `SARIF_RULE_UNPROCESSED_FILE`
+    /// with the reason as the message, the rest under `SARIF_RULE_UNKNOWN_FILE_TYPE` with the file extension.
+    /// The results are sorted by file name.
+    pub fn to_sarif(&self) -> Value {
+        let mut file_names = self.unprocessed_file_names.iter().collect::<Vec<&String>>();
+        file_names.sort();
+
+        let results = file_names
+            .into_iter()
+            .map(|file_name| {
+                let (rule_id, message) = match self.unprocessed_file_reasons.get(file_name) {
+                    Some(reason) => (Report::SARIF_RULE_UNPROCESSED_FILE, reason.clone()),
+                    None => {
+                        let ext = match file_name.rsplit_once('.') {
+                            Some((_, ext)) if !ext.contains('/') => ext,
+                            _ => file_name.rsplit('/').next().unwrap_or_default(),
+                        };
+                        (Report::SARIF_RULE_UNKNOWN_FILE_TYPE, ["Unknown file type: ", ext].concat())
+                    }
+                };
+
+                json!({
+                    "ruleId": rule_id,
+                    "level": "note",
+                    "message": { "text": message },
+                    "locations": [{
+                        "physicalLocation": { "artifactLocation": { "uri": file_name } }
+                    }]
+                })
+            })
+            .collect::<Vec<Value>>();
+
+        json!({
+            "version": "2.1.0",
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "stackmuncher",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": "https://stackmuncher.com",
+                        "rules": [
+                            {
+                                "id": Report::SARIF_RULE_UNKNOWN_FILE_TYPE,
+                                "shortDescription": { "text": "The file type is not recognized by any muncher" }
+                            },
+                            {
+                                "id": Report::SARIF_RULE_UNPROCESSED_FILE,
+                                "shortDescription": { "text": "The file was not processed" }
+                            }
+                        ]
+                    }
+                },
+                "results": results
+            }]
+        })
+    }
+}
+
+#[test]
+fn test_to_sarif() {
+    let mut report = Report::new();
+    report.add_unprocessed_file("src/main.xyz");
+    report.add_unprocessed_file("Jenkinsfile");
+    report.add_unprocessed_file_with_reason("dist/app.min.js", "The file is likely minified".to_owned());
+
+    // the output must survive a roundtrip through text
+    let sarif = serde_json::from_str::<Value>(&report.to_sarif().to_string()).unwrap();
+    assert_eq!(sarif["version"], "2.1.0");
+    assert_eq!(sarif["runs"][0]["tool"]["driver"]["name"], "stackmuncher");
+
+    let results = sarif["runs"][0]["results"].as_array().unwrap();
+    assert_eq!(results.len(), report.unprocessed_file_names.len());
+    assert_eq!(results[0]["ruleId"], Report::SARIF_RULE_UNKNOWN_FILE_TYPE);
+    assert_eq!(results[0]["message"]["text"], "Unknown file type: Jenkinsfile");
+    assert_eq!(results[1]["ruleId"], Report::SARIF_RULE_UNPROCESSED_FILE);
+    assert_eq!(results[1]["message"]["text"], "The file is likely minified");
+    assert_eq!(results[2]["message"]["text"], "Unknown file type: xyz");
+    assert_eq!(results[2]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"], "src/main.xyz");
+}
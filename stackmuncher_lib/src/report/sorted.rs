@@ -0,0 +1,67 @@
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Serializes the set as a sequence sorted by `Ord` of its items, so that the same report is always serialized
+/// into the same JSON. Use it with `#[serde(serialize_with = "...")]`. Deserialization accepts any order.
+pub(crate) fn set<T, S>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Ord + Serialize,
+    S: Serializer,
+{
+    let mut items = set.iter().collect::<Vec<&T>>();
+    items.sort();
+    serializer.collect_seq(items)
+}
+
+/// Serializes the set, if any, as a sequence sorted by `Ord` of its items.
+pub(crate) fn option_set<T, S>(set: &Option<HashSet<T>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Ord + Serialize,
+    S: Serializer,
+{
+    match set {
+        Some(set) => {
+            let mut items = set.iter().collect::<Vec<&T>>();
+            items.sort();
+            serializer.serialize_some(&items)
+        }
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Serializes the map, if any, with its keys sorted.
+pub(crate) fn option_map<K, V, S>(map: &Option<HashMap<K, V>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    match map {
+        Some(map) => serializer.serialize_some(&map.iter().collect::<BTreeMap<&K, &V>>()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Serializes the map of sets, if any, with its keys and the items of every set sorted.
+pub(crate) fn option_map_of_sets<K, T, S>(
+    map: &Option<HashMap<K, HashSet<T>>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    T: Ord + Serialize,
+    S: Serializer,
+{
+    match map {
+        Some(map) => serializer.serialize_some(
+            &map.iter()
+                .map(|(key, set)| {
+                    let mut items = set.iter().collect::<Vec<&T>>();
+                    items.sort();
+                    (key, items)
+                })
+                .collect::<BTreeMap<&K, Vec<&T>>>(),
+        ),
+        None => serializer.serialize_none(),
+    }
+}
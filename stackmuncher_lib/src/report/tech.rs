@@ -1,7 +1,7 @@
 use super::kwc::{KeywordCounter, KeywordCounterSet};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use tracing::{debug, trace, warn};
 
 /// Contains time-range data for its parent Tech.
@@ -58,30 +58,90 @@ pub struct Tech {
     pub line_comments: u64,
     pub block_comments: u64,
     pub docs_comments: u64,
+    /// Set to 1 if the first line of the file is a shebang, e.g. `#!/bin/bash`. It is not counted as code.
+    #[serde(default)]
+    pub shebang_lines: u64,
+    /// Blank lines inside multi-line strings, e.g. Python docstrings. They are not counted as `blank_lines`.
+    #[serde(default)]
+    pub string_lines: u64,
+    /// Number of tech-debt markers in comments, e.g. `TODO`, `FIXME` or `HACK`. The comments are also counted in their
+    /// respective comment categories.
+    #[serde(default)]
+    pub todo_comments: u64,
+    /// Set to true for files matching `Config.test_path_patterns`, e.g. `src/foo_test.rs`.
+    /// Only `per_file_tech` records have it set. Aggregated records keep the test part in `test_code_lines`.
+    #[serde(default)]
+    pub is_test: bool,
+    /// Code lines in test files. They are also included in `code_lines`.
+    #[serde(default)]
+    pub test_code_lines: u64,
+    /// Set to true for files with a header matching `Config.generated_markers`, e.g. `// Code generated ... DO NOT EDIT.`
+    /// The lines are counted as usual. Only `per_file_tech` records have it set, so consumers can exclude them.
+    /// Aggregated records keep the generated part in `generated_code_lines`.
+    #[serde(default)]
+    pub is_generated: bool,
+    /// Code lines in generated files. They are also included in `code_lines`.
+    #[serde(default)]
+    pub generated_code_lines: u64,
+    /// Code lines that are the same as a code line of a file processed earlier, ignoring the whitespace,
+    /// e.g. copy-pasted boilerplate. They are also included in `code_lines`. Only counted if
+    /// `Config.detect_duplicate_lines` is set.
+    #[serde(default)]
+    pub duplicated_lines: u64,
+    /// Hashes of all code lines of the file for counting `duplicated_lines` across files. It is only collected
+    /// on request and is never stored.
+    #[serde(skip)]
+    pub(crate) code_line_hashes: Option<Vec<u64>>,
     /// Historical stats for this tech record: first/last commits, LoC changes.
     /// Populated on STM server.
     /// See https://github.com/stackmuncher/stm_app/issues/46 for more info.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub history: Option<TechHistory>,
     /// Language-specific keywords, e.g. static, class, try-catch
-    #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
-    pub keywords: HashSet<KeywordCounter>, // has to be Option<>
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[serde(serialize_with = "super::sorted::option_set")]
+    pub keywords: Option<HashSet<KeywordCounter>>,
     /// References to other libs, packages and namespaces
     /// E.g. `use` keyword
-    #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
-    pub refs: HashSet<KeywordCounter>, // has to be Option<>
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[serde(serialize_with = "super::sorted::option_set")]
+    pub refs: Option<HashSet<KeywordCounter>>,
     /// Unique words from refs. Only populated during the final merge of
     /// all user reports.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "super::sorted::option_set")]
     pub refs_kw: Option<HashSet<KeywordCounter>>,
     /// References to other libs and packages in pkg managers
     /// E.g. refs from NuGet or Cargo.toml
-    #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
-    pub pkgs: HashSet<KeywordCounter>, // has to be Option<>
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[serde(serialize_with = "super::sorted::option_set")]
+    pub pkgs: Option<HashSet<KeywordCounter>>,
     /// Unique words from pkgs. Only populated during the final merge of
     /// all user reports.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "super::sorted::option_set")]
     pub pkgs_kw: Option<HashSet<KeywordCounter>>,
+    /// Authors of tech-debt markers in comments, e.g. `alice` from `TODO(alice)`, with the number of markers per author.
+    /// Removed from per-file records before submission.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[serde(serialize_with = "super::sorted::option_set")]
+    pub todo_authors: Option<HashSet<KeywordCounter>>,
+    /// Line ranges of the file that belong to `language` as 1-based inclusive `(first, last)` pairs for files with
+    /// multiple embedded languages, e.g. CSS inside HTML. None for single-language files and combined tech records.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub line_ranges: Option<Vec<(usize, usize)>>,
+    /// The earliest and the latest copyright years found in file headers, e.g. `(2018, 2023)` for `Copyright (c) 2018-2023`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub copyright_years: Option<(u32, u32)>,
+    /// The encoding the file was decoded with, e.g. `UTF-8`, `UTF-16LE` or `windows-1252`.
+    /// None for combined tech records and files that could not be decoded.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub detected_encoding: Option<String>,
+    /// The 1-based line number where each ref first appeared in the file. It is only collected on request
+    /// for `per_file_tech` records and is None in combined tech records.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[serde(serialize_with = "super::sorted::option_map")]
+    pub ref_first_seen: Option<HashMap<String, usize>>,
     // PRIVACY REMINDER
     // Any additions to this struct should be considered for clean up before submission to stackmuncher.com
     // to avoid sending out any info that doesn't need to be sent.
@@ -111,8 +171,85 @@ impl PartialEq for Tech {
     }
 }
 
+/// Sorted by language, then muncher, then file name, which are the same members as in `PartialEq`
+impl Ord for Tech {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (&self.language, &self.muncher_name, &self.file_name).cmp(&(
+            &other.language,
+            &other.muncher_name,
+            &other.file_name,
+        ))
+    }
+}
+
+impl PartialOrd for Tech {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Tech {
-    /// Sets `file_name` and commit info to None to match tech records on `muncher_name` and `language` only.
+    /// The name of the regex capture group that holds the name of a ref or a package, e.g. `(?P<name>[a-z_]+)`.
+    pub const MATCH_NAME_GROUP: &'static str = "name";
+
+    /// Returns `code_lines` as a fraction of all lines that could contain code, in the range of 0..1.
+    /// The shebang line is boilerplate and is excluded from the total. Blank, comment and string lines are included.
+    /// Generated files are not counted here at all because they are excluded by `ignore_paths`.
+    /// Returns 0.0 if there are no such lines, e.g. for an empty file.
+    pub fn effective_code_ratio(&self) -> f64 {
+        let total_lines = self.total_lines.saturating_sub(self.shebang_lines);
+        if total_lines == 0 {
+            return 0.0;
+        }
+
+        self.code_lines as f64 / total_lines as f64
+    }
+
+    /// Returns the number of docs, block and line comments per line of code as a rough measure of how well the code
+    /// is documented. Inline comments are not included because they usually explain a single line.
+    /// Returns 0.0 if there are no code lines.
+    pub fn doc_ratio(&self) -> f64 {
+        if self.code_lines == 0 {
+            return 0.0;
+        }
+
+        (self.docs_comments + self.block_comments + self.line_comments) as f64 / self.code_lines as f64
+    }
+
+    /// Adds the hash of a code line to `code_line_hashes` if they are being collected.
+    pub(crate) fn add_code_line_hash(&mut self, line: &str) {
+        if let Some(code_line_hashes) = self.code_line_hashes.as_mut() {
+            code_line_hashes.push(crate::utils::hash_normalized_line(line));
+        }
+    }
+
+    /// Flags the record as coming from a test file or not and counts its code lines as test code accordingly.
+    pub(crate) fn set_is_test(&mut self, is_test: bool) {
+        self.is_test = is_test;
+        self.test_code_lines = if is_test { self.code_lines } else { 0 };
+    }
+
+    /// Flags the record as coming from a generated file or not and counts its code lines as generated code accordingly.
+    pub(crate) fn set_is_generated(&mut self, is_generated: bool) {
+        self.is_generated = is_generated;
+        self.generated_code_lines = if is_generated { self.code_lines } else { 0 };
+    }
+
+    /// Sets the file name and commit info to those of `file_name` and `blob`, e.g. for a record munched from the same
+    /// blob at a different path or in a different commit.
+    pub(crate) fn with_file_and_commit_info(self, file_name: &str, blob: &crate::git::GitBlob) -> Self {
+        let mut tech = self;
+
+        tech.file_name = Some(file_name.to_owned());
+        tech.commit_sha1 = Some(blob.commit_sha1.clone());
+        tech.commit_date_epoch = Some(blob.commit_date_epoch);
+        tech.commit_date_iso = Some(blob.commit_date_iso.clone());
+
+        tech
+    }
+
+    /// Sets `file_name`, `line_ranges`, `detected_encoding`, `ref_first_seen` and commit info to None to match tech records on `muncher_name` and `language` only.
+    /// `is_test` and `is_generated` are reset to false because an aggregate may include files of both kinds.
     /// `per_file_tech` records are matched with all that info present because it is specific to the file.
     /// `tech` records in the report are aggregates across multiple files and should have that info removed.
     pub(crate) fn reset_file_and_commit_info(self) -> Self {
@@ -122,26 +259,81 @@ impl Tech {
         tech.commit_sha1 = None;
         tech.commit_date_epoch = None;
         tech.commit_date_iso = None;
+        tech.line_ranges = None;
+        tech.detected_encoding = None;
+        tech.ref_first_seen = None;
+        tech.code_line_hashes = None;
+        tech.is_test = false;
+        tech.is_generated = false;
 
         tech
     }
 
-    /// Extract and count matches for `self.refs`
+    /// Extract and count matches for `self.refs`. Also records `line_number` for refs seen for the first time
+    /// if `self.ref_first_seen` is Some. Every match on the line is counted if `all_matches` is true, otherwise
+    /// only the first match of every regex.
     #[inline]
-    pub(crate) fn count_refs(&mut self, regex: &Option<Vec<Regex>>, line: &String) {
-        Self::count_matches(regex, line, &mut self.refs, &KeywordCounter::new_ref);
+    pub(crate) fn count_refs(&mut self, regex: &Option<Vec<Regex>>, line: &str, line_number: usize, all_matches: bool) {
+        let refs = &mut self.refs;
+        let ref_first_seen = &mut self.ref_first_seen;
+        Self::for_each_match(regex, line, all_matches, |cap| {
+            let kwc = KeywordCounter::new_ref(cap, 1);
+            if let Some(ref_first_seen) = ref_first_seen.as_mut() {
+                ref_first_seen.entry(kwc.k.clone()).or_insert(line_number);
+            }
+            refs.get_or_insert_with(HashSet::new).increment_counters(kwc);
+        });
     }
 
-    /// Extract and count keywords for `self.keywords`
+    /// Extract and count keywords for `self.keywords`. See `count_refs` for `all_matches`.
     #[inline]
-    pub(crate) fn count_keywords(&mut self, regex: &Option<Vec<Regex>>, line: &String) {
-        Self::count_matches(regex, line, &mut self.keywords, &KeywordCounter::new_keyword);
+    pub(crate) fn count_keywords(&mut self, regex: &Option<Vec<Regex>>, line: &str, all_matches: bool) {
+        Self::count_matches(regex, line, all_matches, &mut self.keywords, &KeywordCounter::new_keyword);
     }
 
-    /// Extract and count matches for `self.pkgs`
+    /// Extract and count matches for `self.pkgs`. See `count_refs` for `all_matches`.
     #[inline]
-    pub(crate) fn count_pkgs(&mut self, regex: &Option<Vec<Regex>>, line: &String) {
-        Self::count_matches(regex, line, &mut self.pkgs, &KeywordCounter::new_ref);
+    pub(crate) fn count_pkgs(&mut self, regex: &Option<Vec<Regex>>, line: &str, all_matches: bool) {
+        Self::count_matches(regex, line, all_matches, &mut self.pkgs, &KeywordCounter::new_ref);
+    }
+
+    /// Extract and count authors of tech-debt markers for `self.todo_authors`. Only comment lines should be passed here.
+    #[inline]
+    pub(crate) fn count_todo_authors(&mut self, regex: &Option<Vec<Regex>>, line: &str) {
+        Self::count_matches(regex, line, false, &mut self.todo_authors, &KeywordCounter::new_keyword);
+    }
+
+    /// Counts tech-debt markers for `self.todo_comments`. Only comment lines should be passed here.
+    #[inline]
+    pub(crate) fn count_todo_comments(&mut self, regex: &Option<Vec<Regex>>, line: &str) {
+        for r in regex.iter().flatten() {
+            self.todo_comments += r.find_iter(line).count() as u64;
+        }
+    }
+
+    /// Extracts copyright years from the line and extends `self.copyright_years` to include them.
+    /// Only lines from the file header should be passed here.
+    pub(crate) fn add_copyright_years(&mut self, regex: &Option<Vec<Regex>>, line: &str) {
+        for r in regex.iter().flatten() {
+            if let Some(groups) = r.captures(line) {
+                let years = groups
+                    .iter()
+                    .skip(1)
+                    .filter_map(|g| g?.as_str().parse::<u32>().ok())
+                    .collect::<Vec<u32>>();
+                for year in years {
+                    self.copyright_years = Some(Tech::extend_years(self.copyright_years, (year, year)));
+                }
+            }
+        }
+    }
+
+    /// Returns a `(min, max)` range of years that includes both ranges.
+    pub(crate) fn extend_years(years: Option<(u32, u32)>, other: (u32, u32)) -> (u32, u32) {
+        match years {
+            Some((min, max)) => (min.min(other.0), max.max(other.1)),
+            None => other,
+        }
     }
 
     /// Count `regex` matches in the given `line` using `kw_counter_factory` Fn
@@ -149,23 +341,43 @@ impl Tech {
     #[inline]
     fn count_matches<B>(
         regex: &Option<Vec<Regex>>,
-        line: &String,
-        kw_counter: &mut HashSet<KeywordCounter>,
+        line: &str,
+        all_matches: bool,
+        kw_counter: &mut Option<HashSet<KeywordCounter>>,
         kw_counter_factory: &B,
     ) where
         B: Fn(String, u64) -> KeywordCounter,
+    {
+        // most lines have no matches - the set is only allocated on the first one
+        Self::for_each_match(regex, line, all_matches, |cap| {
+            kw_counter
+                .get_or_insert_with(HashSet::new)
+                .increment_counters(kw_counter_factory(cap, 1));
+        });
+    }
+
+    /// Calls `f` with every non-empty `regex` match in the given `line`. The match is the `name` capture group if the
+    /// regex has one and it matched, otherwise all capture groups joined with a space or the whole match.
+    /// Only the first match of every regex is used unless `all_matches` is true.
+    #[inline]
+    fn for_each_match<F>(regex: &Option<Vec<Regex>>, line: &str, all_matches: bool, mut f: F)
+    where
+        F: FnMut(String),
     {
         // process if there is a regex in the list of rules
         if let Some(v) = regex {
+            let max_matches = if all_matches { usize::MAX } else { 1 };
             for r in v {
-                if let Some(groups) = r.captures(line) {
+                for groups in r.captures_iter(line).take(max_matches) {
                     // The regex may or may not have capture groups. The counts depend on that.
                     // We'll assume that if there is only capture[0], which is the whole string,
                     // then it's one match. If there is > 1, then it's .len()-1, because capture[0]
                     // is always present as the full string match.
 
-                    // grab the exact match, if any, otherwise grab the whole string match
-                    let cap = if groups.len() > 1 {
+                    // a named group is the most precise, then the exact match, if any, otherwise the whole string match
+                    let cap = if let Some(name) = groups.name(Tech::MATCH_NAME_GROUP) {
+                        name.as_str().trim().to_string()
+                    } else if groups.len() > 1 {
                         let gr_ar: Vec<&str> = groups
                             .iter()
                             .skip(1)
@@ -186,18 +398,18 @@ impl Tech {
                     // Python imports may start with . which needs to be removed
                     let cap = cap.trim_matches('.').trim().to_owned();
 
-                    // add the counts depending with different factory functions for different Tech fields
-                    kw_counter.increment_counters(kw_counter_factory(cap, 1));
+                    f(cap);
                 }
             }
         }
     }
 
     /// Generate a summary of keywords for Tech.refs_kw or Tech.pkgs_kw
-    pub(crate) fn new_kw_summary(refs: &HashSet<KeywordCounter>) -> Option<HashSet<KeywordCounter>> {
+    pub(crate) fn new_kw_summary(refs: &Option<HashSet<KeywordCounter>>) -> Option<HashSet<KeywordCounter>> {
         // exit early if there are no refs
-        if refs.is_empty() {
-            return None;
+        let refs = match refs {
+            Some(v) if !v.is_empty() => v,
+            _ => return None,
         };
 
         // a collector of split keywords with their counts, e.g. System, Text, Regex
@@ -232,7 +444,7 @@ impl Tech {
             warn!("No tree files supplied for local import removal.");
             return self;
         }
-        if self.refs.is_empty() {
+        if self.refs.as_ref().is_none_or(|refs| refs.is_empty()) {
             return self;
         }
 
@@ -260,6 +472,7 @@ impl Tech {
         let all_imports_normalized = tech
             .refs
             .iter()
+            .flatten()
             .map(|kwc| (kwc, kwc.k.replace("::", ".").replace(":", ".").to_lowercase()))
             .collect::<Vec<(&KeywordCounter, String)>>();
 
@@ -307,28 +520,265 @@ impl Tech {
             }
         }
 
-        // remove the local imports from the list
-        for local_import in local_imports {
-            tech.refs.remove(&local_import);
+        // remove the local imports from the list and from the line numbers
+        if let Some(refs) = tech.refs.as_mut() {
+            for local_import in local_imports {
+                if let Some(ref_first_seen) = tech.ref_first_seen.as_mut() {
+                    ref_first_seen.remove(&local_import.k);
+                }
+                refs.remove(&local_import);
+            }
         }
 
         // some TypeScript refs start with @, e.g. @angular/core
         // it's a valid name, but @ will get in the way of users searching for "angular"
-        tech.refs = tech
-            .refs
-            .into_iter()
-            .map(|kwc| {
-                if kwc.k.starts_with("@") {
-                    KeywordCounter {
-                        k: kwc.k.trim_start_matches("@").to_string(),
-                        ..kwc
+        tech.refs = tech.refs.map(|refs| {
+            refs.into_iter()
+                .map(|kwc| {
+                    if kwc.k.starts_with("@") {
+                        KeywordCounter {
+                            k: kwc.k.trim_start_matches("@").to_string(),
+                            ..kwc
+                        }
+                    } else {
+                        kwc
                     }
-                } else {
-                    kwc
-                }
-            })
-            .collect::<HashSet<KeywordCounter>>();
+                })
+                .collect::<HashSet<KeywordCounter>>()
+        });
+        // no data is None rather than an empty set
+        if tech.refs.as_ref().is_some_and(|refs| refs.is_empty()) {
+            tech.refs = None;
+        }
+        tech.ref_first_seen = tech.ref_first_seen.map(|ref_first_seen| {
+            let mut trimmed: HashMap<String, usize> = HashMap::with_capacity(ref_first_seen.len());
+            for (k, line_number) in ref_first_seen {
+                let first_seen = trimmed
+                    .entry(k.trim_start_matches('@').to_string())
+                    .or_insert(line_number);
+                *first_seen = (*first_seen).min(line_number);
+            }
+            trimmed
+        });
 
         tech
     }
 }
+
+#[cfg(test)]
+impl Tech {
+    /// Returns a record for tests with a single file of `code_lines` lines of code and no other lines.
+    /// The muncher name is the language in lower case. Other members can be set with the struct update syntax,
+    /// e.g. `Tech { blank_lines: 1, ..Tech::for_test("Rust", 10) }`.
+    pub(crate) fn for_test(language: &str, code_lines: u64) -> Self {
+        serde_json::from_value(serde_json::json!({
+            "language": language,
+            "muncher_name": language.to_lowercase(),
+            "files": 1,
+            "total_lines": code_lines,
+            "blank_lines": 0,
+            "bracket_only_lines": 0,
+            "code_lines": code_lines,
+            "inline_comments": 0,
+            "line_comments": 0,
+            "block_comments": 0,
+            "docs_comments": 0,
+        }))
+        .expect("Invalid test tech record. It's a bug.")
+    }
+}
+
+#[test]
+fn test_refs_with_named_capture_group() {
+    let mut tech = Tech::for_test("Rust", 0);
+    let refs = |tech: &Tech| {
+        let mut refs = tech
+            .refs
+            .iter()
+            .flatten()
+            .map(|kwc| (kwc.k.clone(), kwc.c))
+            .collect::<Vec<_>>();
+        refs.sort();
+        refs
+    };
+
+    // only the crate name is recorded, so different members of the same crate are counted together
+    let rust_use = Some(vec![Regex::new(r"^\s*use\s+(?P<name>[a-z_][a-z0-9_]*)(::[^;]+)?;").unwrap()]);
+    tech.count_refs(&rust_use, "use std::collections::HashMap;", 1, false);
+    tech.count_refs(&rust_use, "use std::fs;", 2, false);
+    assert_eq!(refs(&tech), vec![("std".to_owned(), 2)]);
+
+    // the whole dotted name of a Python module
+    tech.refs = None;
+    let py_import = Some(vec![Regex::new(r"^\s*import\s+(?P<name>[A-Za-z0-9_\.]+)(\s+as\s+\w+)?").unwrap()]);
+    tech.count_refs(&py_import, "import x.y.z as xyz", 1, false);
+    assert_eq!(refs(&tech), vec![("x.y.z".to_owned(), 1)]);
+}
+
+#[test]
+fn test_file_with_no_refs_has_no_refs_field() {
+    let mut tech = Tech::for_test("Rust", 0);
+
+    // a line with no matches does not allocate the set
+    let rust_use = Some(vec![Regex::new(r"^\s*use\s+(?P<name>[a-z_][a-z0-9_]*)").unwrap()]);
+    tech.count_refs(&rust_use, "fn main() {}", 1, false);
+    tech.count_keywords(&rust_use, "fn main() {}", false);
+    assert!(tech.refs.is_none());
+    assert!(tech.keywords.is_none());
+
+    let json = serde_json::to_value(&tech).unwrap();
+    assert!(json.get("refs").is_none());
+    assert!(json.get("keywords").is_none());
+    assert!(json.get("pkgs").is_none());
+
+    // the field is back once there is some data
+    tech.count_refs(&rust_use, "use std::fs;", 2, false);
+    assert_eq!(serde_json::to_value(&tech).unwrap()["refs"][0]["k"], "std");
+}
+
+#[test]
+fn test_effective_code_ratio() {
+    assert_eq!(Tech::for_test("Rust", 0).effective_code_ratio(), 0.0);
+
+    let tech = Tech {
+        total_lines: 9,
+        blank_lines: 2,
+        line_comments: 2,
+        shebang_lines: 1,
+        ..Tech::for_test("Shell", 4)
+    };
+    // the shebang is not counted, but blank and comment lines are
+    assert_eq!(tech.effective_code_ratio(), 0.5);
+
+    // a script with only a shebang in it
+    let tech = Tech {
+        total_lines: 1,
+        shebang_lines: 1,
+        ..Tech::for_test("Shell", 0)
+    };
+    assert_eq!(tech.effective_code_ratio(), 0.0);
+}
+
+#[test]
+fn test_doc_ratio() {
+    let muncher = crate::code_rules::CodeRules::new()
+        .get_muncher(&"lib.rs".to_owned())
+        .unwrap()
+        .clone();
+
+    let documented = "/// Adds two numbers.\n/// Returns the sum.\npub fn add(a: u64, b: u64) -> u64 {\n    // no overflow check\n    a + b\n}\n";
+    let documented = crate::processors::analyze_bytes(documented.as_bytes(), &muncher, "lib.rs");
+    assert_eq!(documented.code_lines, 2);
+    assert_eq!(documented.doc_ratio(), 1.5);
+
+    let bare = "pub fn add(a: u64, b: u64) -> u64 {\n    a + b // inline\n}\n";
+    let bare = crate::processors::analyze_bytes(bare.as_bytes(), &muncher, "lib.rs");
+    assert_eq!(bare.doc_ratio(), 0.0);
+
+    // the report adds up the lines before dividing
+    let mut report = crate::report::Report::new();
+    report.merge_tech_record(documented);
+    report.merge_tech_record(bare);
+    assert_eq!(report.doc_ratio(), 0.75);
+
+    // no code at all
+    let tech = crate::processors::analyze_bytes(b"// TODO\n", &muncher, "lib.rs");
+    assert_eq!(tech.doc_ratio(), 0.0);
+}
+
+#[test]
+fn test_copyright_years() {
+    let muncher = crate::code_rules::CodeRules::new()
+        .get_muncher(&"lib.rs".to_owned())
+        .unwrap()
+        .clone();
+
+    let header =
+        "// Copyright (c) 2018-2021 Alice\n// Copyright 2023 Bob\npub fn add(a: u64, b: u64) -> u64 {\n    a + b\n}\n";
+    let tech = crate::processors::analyze_bytes(header.as_bytes(), &muncher, "lib.rs");
+    assert_eq!(tech.copyright_years, Some((2018, 2023)));
+    // the notices do not change the line types
+    assert_eq!(tech.line_comments, 2);
+
+    // notices below the header are ignored
+    let below_header = format!(
+        "{}// Copyright 2001\n",
+        "fn f() {}\n".repeat(crate::muncher::Muncher::COPYRIGHT_HEADER_LINES)
+    );
+    let tech = crate::processors::analyze_bytes(below_header.as_bytes(), &muncher, "lib.rs");
+    assert_eq!(tech.copyright_years, None);
+
+    // a single year
+    let mut tech = Tech::for_test("Rust", 0);
+    tech.add_copyright_years(&muncher.copyright_regex, "# COPYRIGHT © 2015 Carol");
+    assert_eq!(tech.copyright_years, Some((2015, 2015)));
+    tech.add_copyright_years(&muncher.copyright_regex, "// not a copyright notice 1999");
+    assert_eq!(tech.copyright_years, Some((2015, 2015)));
+
+    // the report summary has the range across all files
+    let mut report = crate::report::Report::new();
+    report.merge_tech_record(tech);
+    report.merge_tech_record(crate::processors::analyze_bytes(header.as_bytes(), &muncher, "lib.rs"));
+    assert_eq!(report.with_summary().copyright_years, Some((2015, 2023)));
+}
+
+#[test]
+fn test_remove_local_imports_from_ref_first_seen() {
+    let muncher = crate::code_rules::CodeRules::new()
+        .get_muncher(&"main.ts".to_owned())
+        .unwrap()
+        .clone();
+    let mut tech = Tech {
+        ref_first_seen: Some(HashMap::new()),
+        ..Tech::for_test("TypeScript", 0)
+    };
+    let lines = [
+        "import { get } from 'axios';",
+        "",
+        "import { parse } from 'utils';",
+        "import { Component } from '@angular/core';",
+    ];
+    for (idx, line) in lines.iter().enumerate() {
+        tech.count_refs(&muncher.refs_regex, line, idx + 1, false);
+    }
+
+    let all_tree_files = ["src/main.ts", "src/utils.ts"]
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<HashSet<String>>();
+    let tech = tech.remove_local_imports(Some(&all_tree_files));
+
+    // the line numbers are only kept for the refs that are left and have the same names
+    let mut ref_first_seen = tech
+        .ref_first_seen
+        .unwrap()
+        .into_iter()
+        .collect::<Vec<(String, usize)>>();
+    ref_first_seen.sort();
+    assert_eq!(ref_first_seen, vec![("angular".to_owned(), 4), ("axios".to_owned(), 1)]);
+    let mut refs = tech.refs.unwrap().into_iter().map(|kwc| kwc.k).collect::<Vec<String>>();
+    refs.sort();
+    assert_eq!(refs, vec!["angular", "axios"]);
+}
+
+#[test]
+fn test_remove_all_local_imports_leaves_no_refs() {
+    let muncher = crate::code_rules::CodeRules::new()
+        .get_muncher(&"main.ts".to_owned())
+        .unwrap()
+        .clone();
+    let mut tech = Tech {
+        ref_first_seen: Some(HashMap::new()),
+        ..Tech::for_test("TypeScript", 0)
+    };
+    tech.count_refs(&muncher.refs_regex, "import { parse } from 'utils';", 1, false);
+
+    let all_tree_files = ["src/main.ts", "src/utils.ts"]
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<HashSet<String>>();
+    let tech = tech.remove_local_imports(Some(&all_tree_files));
+
+    assert!(tech.refs.is_none());
+    assert!(tech.ref_first_seen.unwrap().is_empty());
+}
@@ -0,0 +1,97 @@
+use super::contributors_csv::csv_field;
+use super::{Report, Tech};
+
+impl Report {
+    /// The header row of `to_csv(false)`. The order of columns is fixed.
+    pub const TECH_CSV_HEADER: &'static str =
+        "language,files,total_lines,code_lines,line_comments,block_comments,docs_comments,inline_comments,blank_lines";
+
+    /// The header row of `to_csv(true)`. It is the same as `TECH_CSV_HEADER` with the file name in front.
+    pub const PER_FILE_TECH_CSV_HEADER: &'static str = "file_name,language,files,total_lines,code_lines,line_comments,block_comments,docs_comments,inline_comments,blank_lines";
+
+    /// Returns a CSV with one row per `tech` record sorted by language, or one row per `per_file_tech` record sorted by
+    /// file name and language if `per_file` is true. Returns only the header if there are no records.
+    pub fn to_csv(&self, per_file: bool) -> String {
+        let (header, techs) = if per_file {
+            (Report::PER_FILE_TECH_CSV_HEADER, &self.per_file_tech)
+        } else {
+            (Report::TECH_CSV_HEADER, &self.tech)
+        };
+
+        let mut techs = techs.iter().collect::<Vec<&Tech>>();
+        techs.sort_by(|a, b| {
+            a.file_name
+                .cmp(&b.file_name)
+                .then_with(|| a.language.cmp(&b.language))
+                .then_with(|| a.muncher_name.cmp(&b.muncher_name))
+        });
+
+        let mut csv = String::from(header);
+        csv.push('\n');
+
+        for tech in techs {
+            if per_file {
+                csv.push_str(&csv_field(tech.file_name.as_deref().unwrap_or_default()));
+                csv.push(',');
+            }
+            csv.push_str(&csv_field(&tech.language));
+            for value in [
+                tech.files,
+                tech.total_lines,
+                tech.code_lines,
+                tech.line_comments,
+                tech.block_comments,
+                tech.docs_comments,
+                tech.inline_comments,
+                tech.blank_lines,
+            ] {
+                csv.push(',');
+                csv.push_str(&value.to_string());
+            }
+            csv.push('\n');
+        }
+
+        csv
+    }
+}
+
+#[test]
+fn test_to_csv() {
+    let tech = |language: &str, file_name: Option<&str>, code_lines: u64| Tech {
+        total_lines: code_lines + 1,
+        blank_lines: 1,
+        file_name: file_name.map(|v| v.to_owned()),
+        ..Tech::for_test(language, code_lines)
+    };
+
+    let mut report = Report::new();
+    report.tech_mut().insert(tech("Rust", None, 10));
+    report.tech_mut().insert(tech("C, C++", None, 5));
+    report.per_file_tech.insert(tech("Rust", Some("src/main.rs"), 10));
+    report.per_file_tech.insert(tech("C, C++", Some("src/a,b.c"), 5));
+
+    assert_eq!(
+        report.to_csv(false),
+        [
+            Report::TECH_CSV_HEADER,
+            "\"C, C++\",1,6,5,0,0,0,0,1",
+            "Rust,1,11,10,0,0,0,0,1",
+            ""
+        ]
+        .join("\n")
+    );
+
+    assert_eq!(
+        report.to_csv(true),
+        [
+            Report::PER_FILE_TECH_CSV_HEADER,
+            "\"src/a,b.c\",\"C, C++\",1,6,5,0,0,0,0,1",
+            "src/main.rs,Rust,1,11,10,0,0,0,0,1",
+            ""
+        ]
+        .join("\n")
+    );
+
+    // only the header for an empty report
+    assert_eq!(Report::new().to_csv(true), [Report::PER_FILE_TECH_CSV_HEADER, "\n"].concat());
+}
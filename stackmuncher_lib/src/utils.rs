@@ -1,4 +1,5 @@
 use sha1::{Digest, Sha1};
+use std::convert::TryInto;
 
 /// Returns a string representation of a hash hex using SHA1.
 /// E.g. `6bdf08b30f8cc1173729d8559933bea5c024c25`
@@ -20,6 +21,108 @@ pub fn hash_vec_sha1(vec_of_strings: Vec<String>) -> String {
     format!("{:x}", hasher.finalize())
 }
 
+/// Returns the first 8 bytes of the SHA1 of `bytes` as a number, which is more than enough to detect a change.
+/// Unlike `std::hash::Hash` it is stable across Rust versions.
+pub(crate) fn hash_sha1_u64(bytes: &[u8]) -> u64 {
+    let mut hasher = Sha1::new();
+    hasher.update(bytes);
+    let digest = hasher.finalize();
+    u64::from_be_bytes(
+        digest[..8]
+            .try_into()
+            .expect("SHA1 is shorter than 8 bytes. It's a bug."),
+    )
+}
+
+/// Returns the file name with `\` separators replaced by `/`, e.g. `src\main.rs` as `src/main.rs`.
+/// Git always uses `/`, so all file names in `Tech` and `Report` are normalized to it for comparisons to work
+/// regardless of where the name came from.
+pub fn normalize_file_name(file_name: &str) -> String {
+    file_name.replace('\\', "/")
+}
+
+/// Returns a 64-bit FNV-1a hash of the line with the leading and trailing whitespace removed and any other
+/// whitespace collapsed into a single space, so that `let  x = 1;` and `    let x = 1;` have the same hash.
+/// It is fast, but not cryptographically secure.
+pub(crate) fn hash_normalized_line(line: &str) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    let mut hash = FNV_OFFSET_BASIS;
+    for (idx, word) in line.split_whitespace().enumerate() {
+        if idx > 0 {
+            hash = (hash ^ b' ' as u64).wrapping_mul(FNV_PRIME);
+        }
+        for byte in word.bytes() {
+            hash = (hash ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    hash
+}
+
+/// A git repo in a new temp dir for tests. The dir is deleted when the repo goes out of scope.
+#[cfg(test)]
+pub(crate) struct TestRepo {
+    pub dir: std::path::PathBuf,
+}
+
+#[cfg(test)]
+impl TestRepo {
+    /// Runs `git init` in a new temp dir with `name` in the dir name.
+    pub(crate) fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("stm_{}_{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("Cannot create a test repo dir");
+        let repo = TestRepo { dir };
+        repo.git(&["init", "-q"]);
+        repo
+    }
+
+    /// Runs git with `args` in the repo and returns its stdout with the whitespace trimmed. Panics if git fails.
+    pub(crate) fn git(&self, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(&self.dir)
+            .output()
+            .expect("Cannot run git");
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8(output.stdout)
+            .expect("Invalid git output")
+            .trim()
+            .to_owned()
+    }
+
+    /// Writes `contents` into `file_name` relative to the root of the repo, creating any missing dirs.
+    pub(crate) fn write<C: AsRef<[u8]>>(&self, file_name: &str, contents: C) {
+        let path = self.dir.join(file_name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("Cannot create a test file dir");
+        }
+        std::fs::write(path, contents).expect("Cannot write a test file");
+    }
+
+    /// Stages all files in the working tree and commits them as `Dev <dev@example.com>`.
+    pub(crate) fn commit_all(&self, message: &str) {
+        self.git(&["add", "-A"]);
+        self.git(&[
+            "-c",
+            "user.name=Dev",
+            "-c",
+            "user.email=dev@example.com",
+            "commit",
+            "-qm",
+            message,
+        ]);
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
 // The mod was created to avoid having Digest twice, for SHA1 and SHA2.
 // It compiles with just one Digest, but the implications are unknown.
 